@@ -0,0 +1,326 @@
+//! Bearer token check of the HTTP API (`bearer_sc` in the Thing Description).
+//!
+//! Tokens are JWTs signed with HS256 and a shared secret or with RS256 and a key of a
+//! JWKS. GET and HEAD requests need the read scope, all other requests the write scope,
+//! except for changing the own password.
+//! Public paths are served without a token.
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::Method;
+use actix_web::{HttpMessage, ResponseError};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+
+use crate::config::{ApiAuthAlgorithm, ApiAuthConfig};
+use crate::error::AppError;
+
+/// Minimum time between two fetches of the JWKS URL, so unknown key IDs cannot flood it.
+const JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// `typ` claim of the refresh tokens of local users, see `functions::users`.
+pub const REFRESH_TOKEN_TYPE: &str = "refresh";
+
+/// Claims of a validated token, available to handlers as a request extension.
+#[derive(Debug, Clone)]
+pub struct Claims(pub Map<String, Value>);
+
+impl Claims {
+    /// Whether the token grants `scope`, read from the space separated `scope` claim or
+    /// the `scp` claim (string or array).
+    pub fn has_scope(&self, scope: &str) -> bool {
+        ["scope", "scp"].iter().any(|claim| match self.0.get(*claim) {
+            Some(Value::String(scopes)) => scopes.split_whitespace().any(|s| s == scope),
+            Some(Value::Array(scopes)) => scopes.iter().any(|s| s.as_str() == Some(scope)),
+            _ => false,
+        })
+    }
+}
+
+struct Jwks {
+    keys: JwkSet,
+    fetched_at: Option<Instant>,
+}
+
+pub struct ApiAuth {
+    config: ApiAuthConfig,
+    client: reqwest::Client,
+    jwks: RwLock<Jwks>,
+}
+
+impl ApiAuth {
+    /// Loads the JWKS of RS256 from its file or URL.
+    pub async fn new(config: ApiAuthConfig, client: reqwest::Client) -> Result<ApiAuth, String> {
+        let auth = ApiAuth {
+            config,
+            client,
+            jwks: RwLock::new(Jwks { keys: JwkSet { keys: Vec::new() }, fetched_at: None }),
+        };
+
+        if auth.config.algorithm == ApiAuthAlgorithm::RS256 {
+            let keys = match (&auth.config.jwks_file, &auth.config.jwks_url) {
+                (Some(path), _) => {
+                    let contents = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+                    serde_json::from_str(&contents).map_err(|e| format!("Invalid JWKS in {}: {}", path, e))?
+                }
+                (None, Some(url)) => auth.fetch_jwks(url).await.map_err(|e| e.to_string())?,
+                (None, None) => return Err("RS256 needs a JWKS file or URL".to_string()),
+            };
+            auth.jwks.write().await.keys = keys;
+        }
+        Ok(auth)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.algorithm != ApiAuthAlgorithm::None
+    }
+
+    pub fn is_public(&self, path: &str) -> bool {
+        self.config.public_paths.iter().any(|public| match public.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == public,
+        })
+    }
+
+    /// Scope needed for a request. Users change their own password with the read scope, the
+    /// handler checks their current password instead.
+    pub fn required_scope(&self, method: &Method, path: &str) -> &str {
+        if method == Method::GET || method == Method::HEAD || is_password_path(path) {
+            &self.config.read_scope
+        } else {
+            &self.config.write_scope
+        }
+    }
+
+    /// Validates the `Authorization` header and returns the claims of its token.
+    pub async fn authenticate(&self, authorization: Option<&str>) -> Result<Claims, AppError> {
+        let token = authorization
+            .and_then(|header| header.strip_prefix("Bearer ").or_else(|| header.strip_prefix("bearer ")))
+            .map(str::trim)
+            .ok_or_else(|| AppError::Unauthorized("Missing bearer token".to_string()))?;
+
+        let (algorithm, key) = match self.config.algorithm {
+            ApiAuthAlgorithm::HS256 => (
+                Algorithm::HS256,
+                DecodingKey::from_secret(self.config.secret.as_deref().unwrap_or_default().as_bytes()),
+            ),
+            ApiAuthAlgorithm::RS256 => (Algorithm::RS256, self.rs256_key(token).await?),
+            ApiAuthAlgorithm::None => return Ok(Claims(Map::new())),
+        };
+
+        let claims = jsonwebtoken::decode::<Map<String, Value>>(token, &key, &validation(&self.config, algorithm))
+            .map(|data| Claims(data.claims))
+            .map_err(|e| AppError::Unauthorized(format!("Invalid bearer token: {}", e)))?;
+        if claims.0.get("typ").and_then(Value::as_str) == Some(REFRESH_TOKEN_TYPE) {
+            return Err(AppError::Unauthorized("Refresh tokens cannot be used as bearer tokens".to_string()));
+        }
+        Ok(claims)
+    }
+
+    /// Key named by the `kid` of the token, fetching the JWKS URL again if it is unknown.
+    async fn rs256_key(&self, token: &str) -> Result<DecodingKey, AppError> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|e| AppError::Unauthorized(format!("Invalid bearer token: {}", e)))?;
+        let kid = header.kid.unwrap_or_default();
+
+        if let Some(key) = self.find_key(&kid).await {
+            return key;
+        }
+
+        if let Some(url) = &self.config.jwks_url {
+            let mut jwks = self.jwks.write().await;
+            let may_refetch = match jwks.fetched_at {
+                Some(at) => at.elapsed() >= JWKS_REFETCH_INTERVAL,
+                None => true,
+            };
+            if may_refetch {
+                jwks.fetched_at = Some(Instant::now());
+                match self.fetch_jwks(url).await {
+                    Ok(keys) => jwks.keys = keys,
+                    Err(e) => eprintln!("Failed to reload JWKS: {}", e),
+                }
+            }
+        }
+
+        self.find_key(&kid)
+            .await
+            .unwrap_or_else(|| Err(AppError::Unauthorized(format!("Unknown signing key '{}'", kid))))
+    }
+
+    async fn find_key(&self, kid: &str) -> Option<Result<DecodingKey, AppError>> {
+        let jwks = self.jwks.read().await;
+        // Tokens without kid are accepted if the set has a single key
+        let jwk = match (kid, jwks.keys.keys.as_slice()) {
+            ("", [jwk]) => Some(jwk),
+            _ => jwks.keys.find(kid),
+        }?;
+        Some(DecodingKey::from_jwk(jwk).map_err(|e| AppError::Internal(format!("Unusable JWK '{}': {}", kid, e))))
+    }
+
+    async fn fetch_jwks(&self, url: &str) -> Result<JwkSet, AppError> {
+        let response = self.client.get(url).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(AppError::upstream(status, format!("Failed to fetch JWKS from {}", url)));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| AppError::upstream(status, format!("Invalid JWKS at {}: {}", url, e)))
+    }
+}
+
+/// Checks of the signature, expiry and the configured issuer and audience.
+pub fn validation(config: &ApiAuthConfig, algorithm: Algorithm) -> Validation {
+    let mut validation = Validation::new(algorithm);
+    validation.validate_aud = config.audience.is_some();
+    if let Some(audience) = &config.audience {
+        validation.set_audience(&[audience]);
+    }
+    if let Some(issuer) = &config.issuer {
+        validation.set_issuer(&[issuer]);
+    }
+    validation
+}
+
+/// `/users/{username}/password`.
+fn is_password_path(path: &str) -> bool {
+    matches!(path.split('/').collect::<Vec<_>>().as_slice(), ["", "users", username, "password"] if !username.is_empty())
+}
+
+/// Middleware answering 401 without a valid token and 403 without the required scope.
+pub struct JwtAuth {
+    auth: Arc<ApiAuth>,
+}
+
+impl JwtAuth {
+    pub fn new(auth: Arc<ApiAuth>) -> Self {
+        JwtAuth { auth }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for JwtAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = JwtAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(JwtAuthMiddleware { service: Rc::new(service), auth: self.auth.clone() }))
+    }
+}
+
+pub struct JwtAuthMiddleware<S> {
+    service: Rc<S>,
+    auth: Arc<ApiAuth>,
+}
+
+impl<S, B> Service<ServiceRequest> for JwtAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let auth = self.auth.clone();
+
+        Box::pin(async move {
+            if !auth.is_enabled() || auth.is_public(req.path()) {
+                return service.call(req).await.map(ServiceResponse::map_into_left_body);
+            }
+
+            let authorization = req
+                .headers()
+                .get(actix_web::http::header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let scope = auth.required_scope(req.method(), req.path()).to_string();
+
+            let result = auth.authenticate(authorization.as_deref()).await.and_then(|claims| {
+                if claims.has_scope(&scope) {
+                    Ok(claims)
+                } else {
+                    Err(AppError::Forbidden(format!("The token lacks the scope '{}'", scope)))
+                }
+            });
+
+            match result {
+                Ok(claims) => {
+                    req.extensions_mut().insert(claims);
+                    service.call(req).await.map(ServiceResponse::map_into_left_body)
+                }
+                Err(e) => Ok(req.into_response(e.error_response()).map_into_right_body()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+    use jsonwebtoken::{EncodingKey, Header};
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn token(scope: &str) -> String {
+        let claims = serde_json::json!({ "sub": "tester", "scope": scope, "exp": chrono::Utc::now().timestamp() + 60 });
+        jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(SECRET.as_bytes())).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_requires_token_with_scope_outside_public_paths() {
+        let config = ApiAuthConfig {
+            algorithm: ApiAuthAlgorithm::HS256,
+            secret: Some(SECRET.to_string()),
+            ..Default::default()
+        };
+        let auth = Arc::new(ApiAuth::new(config, reqwest::Client::new()).await.unwrap());
+        let app = test::init_service(
+            App::new()
+                .wrap(JwtAuth::new(auth))
+                .route("/health/live", web::get().to(HttpResponse::Ok))
+                .route("/submodels", web::get().to(HttpResponse::Ok))
+                .route("/submodels", web::patch().to(HttpResponse::Ok))
+                .route("/users/{username}/password", web::put().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let status = |req: test::TestRequest| {
+            let app = &app;
+            async move { test::call_service(app, req.to_request()).await.status().as_u16() }
+        };
+        let bearer = |token: &str| ("Authorization", format!("Bearer {}", token));
+
+        assert_eq!(status(test::TestRequest::get().uri("/health/live")).await, 200);
+        assert_eq!(status(test::TestRequest::get().uri("/submodels")).await, 401);
+        assert_eq!(status(test::TestRequest::get().uri("/submodels").insert_header(bearer("garbage"))).await, 401);
+        assert_eq!(status(test::TestRequest::get().uri("/submodels").insert_header(bearer(&token("aas:read")))).await, 200);
+        assert_eq!(status(test::TestRequest::patch().uri("/submodels").insert_header(bearer(&token("aas:read")))).await, 403);
+        assert_eq!(
+            status(test::TestRequest::patch().uri("/submodels").insert_header(bearer(&token("aas:read aas:write")))).await,
+            200
+        );
+        // Viewers change their own password, the handler checks it
+        assert_eq!(status(test::TestRequest::put().uri("/users/tester/password").insert_header(bearer(&token("aas:read")))).await, 200);
+        assert_eq!(status(test::TestRequest::put().uri("/users/tester/password/x").insert_header(bearer(&token("aas:read")))).await, 403);
+    }
+}
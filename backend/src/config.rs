@@ -0,0 +1,1270 @@
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Configuration file read when `CONFIG_FILE` is not set. It is optional.
+const DEFAULT_CONFIG_FILE: &str = "./config.yaml";
+
+/// Typed client configuration.
+///
+/// Values are read from a YAML file first and then overlaid with the environment
+/// variables documented in `aas_client.env`, so a container can keep using its env file
+/// while devices with a mounted config file only override what differs.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub rocksdb_path: String,
+    pub aas_id_short: String,
+    /// ID of the shell. Optional if the shell is looked up through `discovery`.
+    pub aas_identifier: String,
+    /// Global asset ID the onboarded shell must describe, if set.
+    pub aas_asset_identifier: Option<String>,
+    /// AAS server for everything the registry does not describe. Optional with a registry.
+    pub aasx_server: String,
+    pub device_name: String,
+    /// Seconds between two onboarding attempts.
+    pub onboarding_retry_interval: u64,
+    /// Seconds the device stays OFFBOARDED before it onboards itself again.
+    pub offboarding_time: i64,
+    /// Seconds between two polls of the AAS server.
+    pub server_polling_interval: u64,
+    /// Seconds between two runs of the collector script and push to the AAS server.
+    pub client_polling_interval: u64,
+    /// Implementation of the AAS server, `auto` to detect it from `/description`.
+    pub aas_server_dialect: ServerDialect,
+    /// How pushes reach the AAS server.
+    pub push_mode: PushMode,
+    /// How PATCH payloads that do not fit the stored submodel are handled.
+    pub patch_validation: PatchValidation,
+    pub activate_timeseries: bool,
+    /// Seconds between two time series samples.
+    pub timeseries_interval: u64,
+    /// Number of samples kept per submodel, older ones are dropped.
+    pub timeseries_max_samples: usize,
+    /// Submodels sampled into the time series, by idShort. Empty for the one of
+    /// `submodels.system_information`.
+    pub timeseries_submodels: Vec<String>,
+    pub submodels: SubmodelsConfig,
+    pub discovery: DiscoveryConfig,
+    pub registration: RegistrationConfig,
+    pub aasx: AasxConfig,
+    pub operations: OperationsConfig,
+    pub concept_descriptions: ConceptDescriptionsConfig,
+    pub jobs: JobsConfig,
+    pub health: HealthConfig,
+    pub shutdown: ShutdownConfig,
+    pub http_server: HttpServerConfig,
+    pub http_client: HttpClientConfig,
+    pub server_auth: ServerAuthConfig,
+    pub api_auth: ApiAuthConfig,
+    pub users: UsersConfig,
+}
+
+/// AAS server implementations, which differ in details of the AAS API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ServerDialect {
+    #[default]
+    Auto,
+    /// Any server following the AAS API v3, accepting common deviations.
+    Generic,
+    /// Eclipse BaSyx.
+    Basyx,
+    /// Eclipse AASX Server.
+    AasxServer,
+    /// FA³ST Service, serving the API below `/api/v3.0`.
+    Faaast,
+}
+
+impl FromStr for ServerDialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ServerDialect::Auto),
+            "generic" => Ok(ServerDialect::Generic),
+            "basyx" => Ok(ServerDialect::Basyx),
+            "aasx-server" => Ok(ServerDialect::AasxServer),
+            "faaast" => Ok(ServerDialect::Faaast),
+            _ => Err("expected auto, generic, basyx, aasx-server or faaast".to_string()),
+        }
+    }
+}
+
+/// Request used to push changed values to the AAS server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PushMode {
+    /// `PATCH .../submodel-elements/{idShort}/$value` per changed top-level element.
+    #[default]
+    Element,
+    /// `PATCH .../$value` of the submodel with the changed elements.
+    Submodel,
+    /// `PUT` of the whole submodel, for servers without `$value` support.
+    Put,
+}
+
+impl FromStr for PushMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "element" => Ok(PushMode::Element),
+            "submodel" => Ok(PushMode::Submodel),
+            "put" => Ok(PushMode::Put),
+            _ => Err("expected element, submodel or put".to_string()),
+        }
+    }
+}
+
+/// Handling of PATCH payloads with unknown idShorts, wrong structure or values that do not
+/// fit their valueType.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PatchValidation {
+    /// Rejected with 422 listing every offending path.
+    #[default]
+    Strict,
+    /// Merged as far as they fit, the offending paths are logged.
+    Lenient,
+}
+
+impl FromStr for PatchValidation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "strict" => Ok(PatchValidation::Strict),
+            "lenient" => Ok(PatchValidation::Lenient),
+            _ => Err("expected strict or lenient".to_string()),
+        }
+    }
+}
+
+/// Signature algorithm of the bearer tokens accepted by the HTTP API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiAuthAlgorithm {
+    /// No token is checked.
+    #[default]
+    None,
+    /// HMAC with the shared `secret`.
+    HS256,
+    /// RSA with the public keys of a JWKS.
+    RS256,
+}
+
+impl FromStr for ApiAuthAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "" | "none" => Ok(ApiAuthAlgorithm::None),
+            "hs256" => Ok(ApiAuthAlgorithm::HS256),
+            "rs256" => Ok(ApiAuthAlgorithm::RS256),
+            _ => Err("expected none, hs256 or rs256".to_string()),
+        }
+    }
+}
+
+/// Bearer token check of the HTTP API, advertised as `bearer_sc` in the Thing Description.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiAuthConfig {
+    pub algorithm: ApiAuthAlgorithm,
+    /// Shared secret of HS256.
+    pub secret: Option<String>,
+    /// JWKS file with the RS256 keys.
+    pub jwks_file: Option<String>,
+    /// JWKS URL with the RS256 keys, fetched again when a token names an unknown key.
+    pub jwks_url: Option<String>,
+    /// Required `iss` claim, if set.
+    pub issuer: Option<String>,
+    /// Required `aud` claim, if set.
+    pub audience: Option<String>,
+    /// Scope required for GET and HEAD requests.
+    pub read_scope: String,
+    /// Scope required for all other requests.
+    pub write_scope: String,
+    /// Scope additionally required to manage the local users.
+    pub admin_scope: String,
+    /// Paths served without a token. A trailing `*` matches any rest of the path.
+    pub public_paths: Vec<String>,
+}
+
+impl Default for ApiAuthConfig {
+    fn default() -> Self {
+        ApiAuthConfig {
+            algorithm: ApiAuthAlgorithm::None,
+            secret: None,
+            jwks_file: None,
+            jwks_url: None,
+            issuer: None,
+            audience: None,
+            read_scope: "aas:read".to_string(),
+            write_scope: "aas:write".to_string(),
+            admin_scope: "aas:admin".to_string(),
+            public_paths: ["/", "/health/*", "/metrics", "/openapi", "/login", "/login/refresh", "/logout"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+/// Local user accounts logging in through `POST /login`. Their tokens are signed with the
+/// HS256 secret of `api_auth`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsersConfig {
+    /// Name of the admin created when no user exists yet.
+    pub admin_username: String,
+    /// Password of that admin. Without it no user is created at startup.
+    pub admin_password: Option<String>,
+    /// Seconds an access token is valid.
+    pub access_token_lifetime: u64,
+    /// Seconds a refresh token is valid.
+    pub refresh_token_lifetime: u64,
+}
+
+impl Default for UsersConfig {
+    fn default() -> Self {
+        UsersConfig {
+            admin_username: "admin".to_string(),
+            admin_password: None,
+            access_token_lifetime: 900,
+            refresh_token_lifetime: 7 * 24 * 3600,
+        }
+    }
+}
+
+/// How the client authenticates at the AAS server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMethod {
+    #[default]
+    None,
+    /// Static bearer token.
+    Bearer,
+    /// HTTP basic authentication.
+    Basic,
+    /// OAuth2 client credentials grant, e.g. against Keycloak.
+    OAuth2,
+}
+
+impl FromStr for AuthMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "" | "none" => Ok(AuthMethod::None),
+            "bearer" => Ok(AuthMethod::Bearer),
+            "basic" => Ok(AuthMethod::Basic),
+            "oauth2" => Ok(AuthMethod::OAuth2),
+            _ => Err("expected none, bearer, basic or oauth2".to_string()),
+        }
+    }
+}
+
+/// Credentials sent with every request to the AAS server.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerAuthConfig {
+    pub method: AuthMethod,
+    /// Token of the `bearer` method.
+    pub token: Option<String>,
+    /// User of the `basic` method.
+    pub username: Option<String>,
+    /// Password of the `basic` method.
+    pub password: Option<String>,
+    /// Token endpoint of the `oauth2` method.
+    pub token_url: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// Space separated scopes requested with the token.
+    pub scope: Option<String>,
+    /// Seconds before its expiry a cached token is replaced.
+    pub refresh_margin: u64,
+}
+
+impl Default for ServerAuthConfig {
+    fn default() -> Self {
+        ServerAuthConfig {
+            method: AuthMethod::None,
+            token: None,
+            username: None,
+            password: None,
+            token_url: None,
+            client_id: None,
+            client_secret: None,
+            scope: None,
+            refresh_margin: 30,
+        }
+    }
+}
+
+/// Address the HTTP API listens on.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ListenAddress {
+    /// Plain HTTP on `host:port`.
+    Http(String),
+    /// HTTPS on `https://host:port`, with the certificate of `http_server`.
+    Https(String),
+    /// Plain HTTP on the Unix domain socket `unix:/path`, for local processes only.
+    Unix(String),
+}
+
+impl FromStr for ListenAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            if !path.starts_with('/') {
+                return Err("expected an absolute socket path after unix:".to_string());
+            }
+            return Ok(ListenAddress::Unix(path.to_string()));
+        }
+        let https = s.strip_prefix("https://");
+        let address = https.or_else(|| s.strip_prefix("http://")).unwrap_or(s);
+        // IPv6 addresses are written in brackets, e.g. [::]:18000
+        match address.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => match https {
+                Some(_) => Ok(ListenAddress::Https(address.to_string())),
+                None => Ok(ListenAddress::Http(address.to_string())),
+            },
+            _ => Err("expected host:port, https://host:port or unix:/path".to_string()),
+        }
+    }
+}
+
+impl TryFrom<String> for ListenAddress {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddress::Http(address) => write!(f, "http://{}", address),
+            ListenAddress::Https(address) => write!(f, "https://{}", address),
+            ListenAddress::Unix(path) => write!(f, "unix:{}", path),
+        }
+    }
+}
+
+/// Listeners of the HTTP API.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpServerConfig {
+    /// Addresses to listen on. On Linux `[::]:port` accepts IPv4 connections as well, so it
+    /// cannot be combined with `0.0.0.0:port`.
+    pub listen: Vec<ListenAddress>,
+    /// PEM file with the certificate (chain) of the `https://` addresses.
+    pub tls_cert: Option<String>,
+    /// PEM file with the private key of `tls_cert`.
+    pub tls_key: Option<String>,
+    /// Seconds between two checks of the certificate files for changes, 0 disables the reload.
+    pub tls_reload_interval: u64,
+}
+
+impl Default for HttpServerConfig {
+    fn default() -> Self {
+        HttpServerConfig {
+            listen: vec![ListenAddress::Http("0.0.0.0:18000".to_string())],
+            tls_cert: None,
+            tls_key: None,
+            tls_reload_interval: 30,
+        }
+    }
+}
+
+/// HTTP client shared by all requests to the AAS server.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpClientConfig {
+    /// PEM file with CA certificates trusted in addition to the system ones.
+    pub ca_bundle: Option<String>,
+    /// PEM file with the client certificate (chain) for mTLS.
+    pub client_cert: Option<String>,
+    /// PEM file with the PKCS#8 private key of `client_cert`.
+    pub client_key: Option<String>,
+    /// Proxy for all requests. Without it the HTTP_PROXY/HTTPS_PROXY/NO_PROXY variables apply.
+    pub proxy: Option<String>,
+    /// Comma separated hosts, domains and IP ranges reached without `proxy`.
+    pub no_proxy: Option<String>,
+    /// Seconds to establish a connection.
+    pub connect_timeout: u64,
+    /// Seconds a whole request may take.
+    pub timeout: u64,
+    /// Seconds an idle pooled connection is kept open.
+    pub pool_idle_timeout: u64,
+    /// Seconds between TCP keep-alive probes.
+    pub tcp_keepalive: u64,
+    /// Accept any server certificate. For testing only.
+    pub insecure: bool,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        HttpClientConfig {
+            ca_bundle: None,
+            client_cert: None,
+            client_key: None,
+            proxy: None,
+            no_proxy: None,
+            connect_timeout: 5,
+            timeout: 10,
+            pool_idle_timeout: 90,
+            tcp_keepalive: 60,
+            insecure: false,
+        }
+    }
+}
+
+/// Behaviour on SIGTERM and SIGINT.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// Seconds granted to running jobs and HTTP requests to finish and to the final
+    /// `ManagedDevice` update. Keep it below the stop timeout of the container runtime.
+    pub grace_period: u64,
+    /// `BoardingStatus` pushed to the AAS server before exiting, OFFBOARDED or SHUTDOWN.
+    /// Nothing is pushed if it is empty.
+    pub boarding_status: Option<String>,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig {
+            // Docker sends SIGKILL 10 seconds after SIGTERM by default
+            grace_period: 8,
+            boarding_status: Some("OFFBOARDED".to_string()),
+        }
+    }
+}
+
+/// Thresholds of the readiness check.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Seconds after which the last successful push counts as stale.
+    /// Defaults to three push intervals.
+    pub max_push_age: Option<u64>,
+    /// Seconds after which the last successful poll counts as stale.
+    /// Defaults to three poll intervals.
+    pub max_poll_age: Option<u64>,
+    /// Seconds to wait for the AAS server to answer the reachability check.
+    pub server_timeout: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            max_push_age: None,
+            max_poll_age: None,
+            server_timeout: 3,
+        }
+    }
+}
+
+/// Services locating the shell and its submodels. Without them the shell `aas_identifier`
+/// and its submodels are read from `aasx_server`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    /// AAS Discovery service looking up the shell of `aas_asset_identifier`.
+    pub discovery_url: Option<String>,
+    /// AAS Registry with the descriptor of the shell, whose endpoints are used.
+    pub registry_url: Option<String>,
+    /// Submodel Registry for shell descriptors without submodel descriptors.
+    pub submodel_registry_url: Option<String>,
+}
+
+/// What happens to the registered descriptors when the device is offboarded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OffboardingAction {
+    /// The endpoints of the device are removed.
+    #[default]
+    Delete,
+    /// The endpoints stay, the `EdgeClientStatus` extension says OFFBOARDED.
+    Mark,
+}
+
+impl FromStr for OffboardingAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "delete" => Ok(OffboardingAction::Delete),
+            "mark" => Ok(OffboardingAction::Mark),
+            _ => Err("expected delete or mark".to_string()),
+        }
+    }
+}
+
+/// Registration of the shell and submodels served by this device in an AAS Registry.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistrationConfig {
+    /// URL under which other systems reach the HTTP API of this device. Enables the registration.
+    pub public_url: Option<String>,
+    /// Registry to register in. Defaults to `discovery.registry_url`.
+    pub registry_url: Option<String>,
+    /// Seconds between two refreshes of the descriptors.
+    pub heartbeat_interval: u64,
+    pub on_offboarding: OffboardingAction,
+}
+
+impl Default for RegistrationConfig {
+    fn default() -> Self {
+        RegistrationConfig {
+            public_url: None,
+            registry_url: None,
+            heartbeat_interval: 60,
+            on_offboarding: OffboardingAction::Delete,
+        }
+    }
+}
+
+/// Submodels the client itself reads and writes, found by their semanticId so that servers
+/// may name them differently.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubmodelsConfig {
+    pub managed_device: WellKnownSubmodel,
+    pub system_information: WellKnownSubmodel,
+}
+
+impl Default for SubmodelsConfig {
+    fn default() -> Self {
+        SubmodelsConfig {
+            managed_device: WellKnownSubmodel::new("ManagedDevice"),
+            system_information: WellKnownSubmodel::new("SystemInformation"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WellKnownSubmodel {
+    /// First key of the `semanticId` of the submodel.
+    pub semantic_id: Option<String>,
+    /// idShort used when no stored submodel has `semantic_id`.
+    pub id_short: String,
+}
+
+impl WellKnownSubmodel {
+    fn new(id_short: &str) -> Self {
+        WellKnownSubmodel {
+            semantic_id: None,
+            id_short: id_short.to_string(),
+        }
+    }
+}
+
+/// AASX packages to onboard from without an AAS server.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AasxConfig {
+    /// Package the shell and submodels are read from instead of the AAS server.
+    pub import_path: Option<String>,
+}
+
+/// Local actions behind the `Operation` elements of the submodels.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OperationsConfig {
+    /// Property of `ManagedDevice` through which the AAS server triggers operations.
+    pub command: Option<String>,
+    /// Property of `ManagedDevice` the result of the last command is written to.
+    pub command_result: Option<String>,
+    /// Seconds after which an operation is cancelled unless its handler sets a timeout.
+    pub timeout: u64,
+    pub handlers: Vec<OperationHandlerConfig>,
+}
+
+impl Default for OperationsConfig {
+    fn default() -> Self {
+        OperationsConfig {
+            command: None,
+            command_result: None,
+            timeout: 60,
+            handlers: Vec::new(),
+        }
+    }
+}
+
+/// Action run when the Operation at `path` of `submodel` is invoked. Exactly one of
+/// `script`, `builtin` and `http` is set.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OperationHandlerConfig {
+    /// idShort of the submodel.
+    pub submodel: String,
+    /// idShortPath of the Operation, e.g. `Maintenance.Restart`.
+    pub path: String,
+    /// Script run with bash, see `functions::operations`.
+    pub script: Option<String>,
+    pub builtin: Option<BuiltinOperation>,
+    pub http: Option<HttpOperationConfig>,
+    /// Seconds, defaults to `operations.timeout`.
+    pub timeout: Option<u64>,
+}
+
+/// Functions of the client that can be bound to an Operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuiltinOperation {
+    /// Answers the input arguments as output arguments of the same idShort.
+    Echo,
+    /// Runs the collector script and answers its values.
+    SystemInformation,
+    /// Requests offboarding, as the AAS server does through `BoardingStatus`.
+    Offboard,
+}
+
+/// HTTP request sending the input arguments as a JSON object and reading the output
+/// arguments from the JSON object answered.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpOperationConfig {
+    pub url: String,
+    pub method: HttpMethod,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    Get,
+    #[default]
+    Post,
+    Put,
+    Patch,
+}
+
+/// ConceptDescriptions of the semanticIds in the submodels.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConceptDescriptionsConfig {
+    /// Server with the `/concept-descriptions` endpoint. Defaults to `aasx_server`, nothing
+    /// is fetched without either.
+    pub url: Option<String>,
+    /// Seconds between two refreshes, 0 to fetch them only after onboarding.
+    pub refresh_interval: u64,
+}
+
+impl Default for ConceptDescriptionsConfig {
+    fn default() -> Self {
+        ConceptDescriptionsConfig {
+            url: None,
+            refresh_interval: 3600,
+        }
+    }
+}
+
+/// Scheduling of one background job.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobConfig {
+    /// Seconds between two runs. Defaults to the matching `*_INTERVAL` setting.
+    pub interval: Option<u64>,
+    /// Cron expression with a leading seconds field, e.g. "*/5 * * * * *". Wins over `interval`.
+    pub cron: Option<String>,
+    /// Upper bound of a random delay added before each run, in milliseconds.
+    pub jitter_ms: u64,
+    /// Seconds after which a run is cancelled.
+    pub timeout: Option<u64>,
+    /// Start a new run even if the previous one has not finished yet.
+    pub allow_overlap: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    pub server_pushing: JobConfig,
+    pub server_polling: JobConfig,
+    pub timeseries: JobConfig,
+    pub registration: JobConfig,
+    pub concept_descriptions: JobConfig,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        // HTTP requests to the AAS server time out after 10 seconds
+        let job = JobConfig { timeout: Some(30), ..JobConfig::default() };
+        JobsConfig {
+            server_pushing: job.clone(),
+            server_polling: job.clone(),
+            timeseries: job.clone(),
+            registration: job,
+            // One request per ConceptDescription
+            concept_descriptions: JobConfig { timeout: Some(300), ..JobConfig::default() },
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            rocksdb_path: "rocksdb".to_string(),
+            aas_id_short: String::new(),
+            aas_identifier: String::new(),
+            aas_asset_identifier: None,
+            aasx_server: String::new(),
+            device_name: String::new(),
+            onboarding_retry_interval: 10,
+            offboarding_time: 30,
+            server_polling_interval: 10,
+            client_polling_interval: 5,
+            aas_server_dialect: ServerDialect::Auto,
+            push_mode: PushMode::Element,
+            patch_validation: PatchValidation::Strict,
+            activate_timeseries: false,
+            timeseries_interval: 10,
+            timeseries_max_samples: 1000,
+            timeseries_submodels: Vec::new(),
+            submodels: SubmodelsConfig::default(),
+            discovery: DiscoveryConfig::default(),
+            registration: RegistrationConfig::default(),
+            aasx: AasxConfig::default(),
+            operations: OperationsConfig::default(),
+            concept_descriptions: ConceptDescriptionsConfig::default(),
+            jobs: JobsConfig::default(),
+            health: HealthConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            http_server: HttpServerConfig::default(),
+            http_client: HttpClientConfig::default(),
+            server_auth: ServerAuthConfig::default(),
+            api_auth: ApiAuthConfig::default(),
+            users: UsersConfig::default(),
+        }
+    }
+}
+
+/// All problems found while loading the configuration.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid configuration ({} problem(s)):", self.errors.len())?;
+        for error in &self.errors {
+            writeln!(f, "  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Whether there is an AAS server to push to and poll, which a device onboarded from an
+    /// AASX package may lack.
+    pub fn has_aas_server(&self) -> bool {
+        !self.aasx_server.is_empty() || self.discovery.registry_url.is_some()
+    }
+
+    /// Loads the configuration from `CONFIG_FILE` (or `./config.yaml` if present) and the
+    /// process environment.
+    pub fn load() -> Result<Config, ConfigError> {
+        let (path, required) = match std::env::var("CONFIG_FILE") {
+            Ok(path) => (path, true),
+            Err(_) => (DEFAULT_CONFIG_FILE.to_string(), false),
+        };
+        Config::from_sources(Path::new(&path), required, |key| std::env::var(key).ok())
+    }
+
+    fn from_sources(
+        path: &Path,
+        required: bool,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, ConfigError> {
+        let mut errors = Vec::new();
+
+        let mut config = match std::fs::read_to_string(path) {
+            Ok(contents) => match serde_yaml::from_str::<Config>(&contents) {
+                Ok(config) => config,
+                Err(e) => {
+                    errors.push(format!("{}: {}", path.display(), e));
+                    Config::default()
+                }
+            },
+            Err(e) if required || e.kind() != std::io::ErrorKind::NotFound => {
+                errors.push(format!("Cannot read {}: {}", path.display(), e));
+                Config::default()
+            }
+            Err(_) => Config::default(),
+        };
+
+        config.apply_env(&lookup, &mut errors);
+        config.validate(&mut errors);
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError { errors })
+        }
+    }
+
+    /// Overlays the values found in the environment.
+    fn apply_env(&mut self, lookup: &impl Fn(&str) -> Option<String>, errors: &mut Vec<String>) {
+        env_value(lookup, "ROCKSDB_PATH", &mut self.rocksdb_path, errors);
+        env_value(lookup, "AAS_IDSHORT", &mut self.aas_id_short, errors);
+        env_value(lookup, "AAS_IDENTIFIER", &mut self.aas_identifier, errors);
+        env_option(lookup, "AAS_ASSET_IDENTIFIER", &mut self.aas_asset_identifier, errors);
+        env_value(lookup, "AASX_SERVER", &mut self.aasx_server, errors);
+        env_value(lookup, "DEVICE_NAME", &mut self.device_name, errors);
+        env_value(lookup, "ONBOARDING_RETRY_INTERVAL", &mut self.onboarding_retry_interval, errors);
+        env_value(lookup, "OFFBOARDING_TIME", &mut self.offboarding_time, errors);
+        env_value(lookup, "SERVER_POLLING_INTERVAL", &mut self.server_polling_interval, errors);
+        env_value(lookup, "CLIENT_POLLING_INTERVAL", &mut self.client_polling_interval, errors);
+        env_value(lookup, "AAS_SERVER_DIALECT", &mut self.aas_server_dialect, errors);
+        env_value(lookup, "PUSH_MODE", &mut self.push_mode, errors);
+        env_value(lookup, "PATCH_VALIDATION", &mut self.patch_validation, errors);
+        env_value(lookup, "ACTIVATE_TIMESERIES", &mut self.activate_timeseries, errors);
+        env_value(lookup, "TIMESERIES_INTERVAL", &mut self.timeseries_interval, errors);
+        env_value(lookup, "TIMESERIES_MAX_SAMPLES", &mut self.timeseries_max_samples, errors);
+        env_option(lookup, "MANAGED_DEVICE_SEMANTIC_ID", &mut self.submodels.managed_device.semantic_id, errors);
+        env_value(lookup, "MANAGED_DEVICE_ID_SHORT", &mut self.submodels.managed_device.id_short, errors);
+        env_option(lookup, "SYSTEM_INFORMATION_SEMANTIC_ID", &mut self.submodels.system_information.semantic_id, errors);
+        env_value(lookup, "SYSTEM_INFORMATION_ID_SHORT", &mut self.submodels.system_information.id_short, errors);
+        env_option(lookup, "AAS_DISCOVERY_URL", &mut self.discovery.discovery_url, errors);
+        env_option(lookup, "AAS_REGISTRY_URL", &mut self.discovery.registry_url, errors);
+        env_option(lookup, "AAS_SUBMODEL_REGISTRY_URL", &mut self.discovery.submodel_registry_url, errors);
+        env_option(lookup, "REGISTRATION_PUBLIC_URL", &mut self.registration.public_url, errors);
+        env_option(lookup, "REGISTRATION_REGISTRY_URL", &mut self.registration.registry_url, errors);
+        env_value(lookup, "REGISTRATION_HEARTBEAT_INTERVAL", &mut self.registration.heartbeat_interval, errors);
+        env_value(lookup, "REGISTRATION_ON_OFFBOARDING", &mut self.registration.on_offboarding, errors);
+        env_option(lookup, "AASX_IMPORT_PATH", &mut self.aasx.import_path, errors);
+        env_option(lookup, "CONCEPT_DESCRIPTIONS_URL", &mut self.concept_descriptions.url, errors);
+        env_value(lookup, "CONCEPT_DESCRIPTIONS_REFRESH_INTERVAL", &mut self.concept_descriptions.refresh_interval, errors);
+        env_option(lookup, "OPERATIONS_COMMAND", &mut self.operations.command, errors);
+        env_option(lookup, "OPERATIONS_COMMAND_RESULT", &mut self.operations.command_result, errors);
+        env_value(lookup, "OPERATIONS_TIMEOUT", &mut self.operations.timeout, errors);
+        env_value(lookup, "SHUTDOWN_GRACE_PERIOD", &mut self.shutdown.grace_period, errors);
+        env_option(lookup, "SHUTDOWN_BOARDING_STATUS", &mut self.shutdown.boarding_status, errors);
+        env_list(lookup, "HTTP_SERVER_LISTEN", &mut self.http_server.listen, errors);
+        env_option(lookup, "HTTP_SERVER_TLS_CERT", &mut self.http_server.tls_cert, errors);
+        env_option(lookup, "HTTP_SERVER_TLS_KEY", &mut self.http_server.tls_key, errors);
+        env_value(lookup, "HTTP_SERVER_TLS_RELOAD_INTERVAL", &mut self.http_server.tls_reload_interval, errors);
+        env_option(lookup, "HTTP_CLIENT_CA_BUNDLE", &mut self.http_client.ca_bundle, errors);
+        env_option(lookup, "HTTP_CLIENT_CERT", &mut self.http_client.client_cert, errors);
+        env_option(lookup, "HTTP_CLIENT_KEY", &mut self.http_client.client_key, errors);
+        env_option(lookup, "HTTP_CLIENT_PROXY", &mut self.http_client.proxy, errors);
+        env_option(lookup, "HTTP_CLIENT_NO_PROXY", &mut self.http_client.no_proxy, errors);
+        env_value(lookup, "HTTP_CLIENT_TIMEOUT", &mut self.http_client.timeout, errors);
+        env_value(lookup, "HTTP_CLIENT_INSECURE", &mut self.http_client.insecure, errors);
+        env_value(lookup, "SERVER_AUTH_METHOD", &mut self.server_auth.method, errors);
+        env_option(lookup, "SERVER_AUTH_TOKEN", &mut self.server_auth.token, errors);
+        env_option(lookup, "SERVER_AUTH_USERNAME", &mut self.server_auth.username, errors);
+        env_option(lookup, "SERVER_AUTH_PASSWORD", &mut self.server_auth.password, errors);
+        env_option(lookup, "SERVER_AUTH_TOKEN_URL", &mut self.server_auth.token_url, errors);
+        env_option(lookup, "SERVER_AUTH_CLIENT_ID", &mut self.server_auth.client_id, errors);
+        env_option(lookup, "SERVER_AUTH_CLIENT_SECRET", &mut self.server_auth.client_secret, errors);
+        env_option(lookup, "SERVER_AUTH_SCOPE", &mut self.server_auth.scope, errors);
+        env_value(lookup, "API_AUTH_ALGORITHM", &mut self.api_auth.algorithm, errors);
+        env_option(lookup, "API_AUTH_SECRET", &mut self.api_auth.secret, errors);
+        env_option(lookup, "API_AUTH_JWKS_FILE", &mut self.api_auth.jwks_file, errors);
+        env_option(lookup, "API_AUTH_JWKS_URL", &mut self.api_auth.jwks_url, errors);
+        env_option(lookup, "API_AUTH_ISSUER", &mut self.api_auth.issuer, errors);
+        env_option(lookup, "API_AUTH_AUDIENCE", &mut self.api_auth.audience, errors);
+        env_value(lookup, "USERS_ADMIN_USERNAME", &mut self.users.admin_username, errors);
+        env_option(lookup, "USERS_ADMIN_PASSWORD", &mut self.users.admin_password, errors);
+        env_value(lookup, "USERS_ACCESS_TOKEN_LIFETIME", &mut self.users.access_token_lifetime, errors);
+        env_value(lookup, "USERS_REFRESH_TOKEN_LIFETIME", &mut self.users.refresh_token_lifetime, errors);
+    }
+
+    /// Checks the merged values and normalizes the ones the rest of the client relies on.
+    fn validate(&mut self, errors: &mut Vec<String>) {
+        if self.aas_id_short.is_empty() {
+            errors.push("AAS_IDSHORT must be set".to_string());
+        }
+        if self.device_name.is_empty() {
+            errors.push("DEVICE_NAME must be set".to_string());
+        }
+        if self.aas_asset_identifier.as_deref() == Some("") {
+            self.aas_asset_identifier = None;
+        }
+        if self.aasx.import_path.as_deref() == Some("") {
+            self.aasx.import_path = None;
+        }
+        for (name, submodel) in [
+            ("MANAGED_DEVICE", &mut self.submodels.managed_device),
+            ("SYSTEM_INFORMATION", &mut self.submodels.system_information),
+        ] {
+            if submodel.semantic_id.as_deref() == Some("") {
+                submodel.semantic_id = None;
+            }
+            if submodel.id_short.is_empty() {
+                errors.push(format!("{}_ID_SHORT must not be empty", name));
+            }
+        }
+        // URLs are built as "{server}shells/..." everywhere
+        base_url("AASX_SERVER", &mut self.aasx_server, errors);
+        let discovery = &mut self.discovery;
+        for (key, value) in [
+            ("AAS_DISCOVERY_URL", &mut discovery.discovery_url),
+            ("AAS_REGISTRY_URL", &mut discovery.registry_url),
+            ("AAS_SUBMODEL_REGISTRY_URL", &mut discovery.submodel_registry_url),
+            ("REGISTRATION_PUBLIC_URL", &mut self.registration.public_url),
+            ("REGISTRATION_REGISTRY_URL", &mut self.registration.registry_url),
+            ("CONCEPT_DESCRIPTIONS_URL", &mut self.concept_descriptions.url),
+        ] {
+            if let Some(url) = value {
+                base_url(key, url, errors);
+            }
+            if value.as_deref() == Some("") {
+                *value = None;
+            }
+        }
+        match &discovery.discovery_url {
+            Some(_) if self.aas_asset_identifier.is_none() => {
+                errors.push("AAS_DISCOVERY_URL needs AAS_ASSET_IDENTIFIER to look up the shell".to_string());
+            }
+            None if self.aas_identifier.is_empty() && self.aasx.import_path.is_none() => {
+                errors.push("AAS_IDENTIFIER must be set unless AAS_DISCOVERY_URL or AASX_IMPORT_PATH is".to_string());
+            }
+            _ => (),
+        }
+        if self.aasx_server.is_empty() && discovery.registry_url.is_none() && self.aasx.import_path.is_none() {
+            errors.push("AASX_SERVER must be set unless AAS_REGISTRY_URL or AASX_IMPORT_PATH is".to_string());
+        }
+        if self.concept_descriptions.url.is_none() && !self.aasx_server.is_empty() {
+            self.concept_descriptions.url = Some(self.aasx_server.clone());
+        }
+        let registration = &mut self.registration;
+        if registration.registry_url.is_none() {
+            registration.registry_url = discovery.registry_url.clone();
+        }
+        if registration.public_url.is_some() && registration.registry_url.is_none() {
+            errors.push("REGISTRATION_PUBLIC_URL needs REGISTRATION_REGISTRY_URL or AAS_REGISTRY_URL".to_string());
+        }
+        if registration.heartbeat_interval == 0 {
+            errors.push("REGISTRATION_HEARTBEAT_INTERVAL must be greater than 0".to_string());
+        }
+        if self.onboarding_retry_interval == 0 {
+            errors.push("ONBOARDING_RETRY_INTERVAL must be greater than 0".to_string());
+        }
+        if self.offboarding_time < 0 {
+            errors.push("OFFBOARDING_TIME must not be negative".to_string());
+        }
+        if self.server_polling_interval == 0 {
+            errors.push("SERVER_POLLING_INTERVAL must be greater than 0".to_string());
+        }
+        if self.client_polling_interval == 0 {
+            errors.push("CLIENT_POLLING_INTERVAL must be greater than 0".to_string());
+        }
+        for (name, job) in [
+            ("server_pushing", &self.jobs.server_pushing),
+            ("server_polling", &self.jobs.server_polling),
+            ("timeseries", &self.jobs.timeseries),
+            ("registration", &self.jobs.registration),
+            ("concept_descriptions", &self.jobs.concept_descriptions),
+        ] {
+            if job.interval == Some(0) {
+                errors.push(format!("jobs.{}.interval must be greater than 0", name));
+            }
+            if job.timeout == Some(0) {
+                errors.push(format!("jobs.{}.timeout must be greater than 0", name));
+            }
+            if let Some(expression) = &job.cron {
+                match cron::Schedule::from_str(expression) {
+                    Ok(schedule) if schedule.upcoming(chrono::Utc).next().is_none() => {
+                        errors.push(format!("jobs.{}.cron '{}' never fires", name, expression));
+                    }
+                    Ok(_) => (),
+                    Err(e) => errors.push(format!("jobs.{}.cron '{}' is invalid: {}", name, expression, e)),
+                }
+            }
+        }
+        let operations = &mut self.operations;
+        for value in [&mut operations.command, &mut operations.command_result] {
+            if value.as_deref() == Some("") {
+                *value = None;
+            }
+        }
+        if operations.timeout == 0 {
+            errors.push("OPERATIONS_TIMEOUT must be greater than 0".to_string());
+        }
+        for (index, handler) in operations.handlers.iter().enumerate() {
+            if handler.submodel.is_empty() || handler.path.is_empty() {
+                errors.push(format!("operations.handlers[{}] needs submodel and path", index));
+            }
+            let actions = [handler.script.is_some(), handler.builtin.is_some(), handler.http.is_some()];
+            if actions.into_iter().filter(|set| *set).count() != 1 {
+                errors.push(format!("operations.handlers[{}] needs exactly one of script, builtin and http", index));
+            }
+            if handler.http.as_ref().is_some_and(|http| http.url.is_empty()) {
+                errors.push(format!("operations.handlers[{}].http needs a url", index));
+            }
+            if handler.timeout == Some(0) {
+                errors.push(format!("operations.handlers[{}].timeout must be greater than 0", index));
+            }
+        }
+        if self.health.server_timeout == 0 {
+            errors.push("health.server_timeout must be greater than 0".to_string());
+        }
+        if self.shutdown.grace_period == 0 {
+            errors.push("SHUTDOWN_GRACE_PERIOD must be greater than 0".to_string());
+        }
+        match self.shutdown.boarding_status.as_deref() {
+            Some("") => self.shutdown.boarding_status = None,
+            None | Some("OFFBOARDED") | Some("SHUTDOWN") => (),
+            Some(status) => errors.push(format!(
+                "SHUTDOWN_BOARDING_STATUS must be OFFBOARDED, SHUTDOWN or empty, got '{}'",
+                status
+            )),
+        }
+        let http_server = &mut self.http_server;
+        for value in [&mut http_server.tls_cert, &mut http_server.tls_key] {
+            if value.as_deref() == Some("") {
+                *value = None;
+            }
+        }
+        if http_server.listen.is_empty() {
+            errors.push("HTTP_SERVER_LISTEN must name at least one address".to_string());
+        }
+        if http_server.tls_cert.is_some() != http_server.tls_key.is_some() {
+            errors.push("HTTP_SERVER_TLS_CERT and HTTP_SERVER_TLS_KEY must be set together".to_string());
+        }
+        let https = http_server.listen.iter().any(|address| matches!(address, ListenAddress::Https(_)));
+        if https && http_server.tls_cert.is_none() {
+            errors.push("https:// addresses in HTTP_SERVER_LISTEN need HTTP_SERVER_TLS_CERT and HTTP_SERVER_TLS_KEY".to_string());
+        }
+        let http_client = &mut self.http_client;
+        for value in [&mut http_client.ca_bundle, &mut http_client.client_cert, &mut http_client.client_key, &mut http_client.proxy, &mut http_client.no_proxy] {
+            if value.as_deref() == Some("") {
+                *value = None;
+            }
+        }
+        if http_client.client_cert.is_some() != http_client.client_key.is_some() {
+            errors.push("HTTP_CLIENT_CERT and HTTP_CLIENT_KEY must be set together".to_string());
+        }
+        if http_client.connect_timeout == 0 || http_client.timeout == 0 {
+            errors.push("http_client.connect_timeout and HTTP_CLIENT_TIMEOUT must be greater than 0".to_string());
+        }
+        let auth = &self.server_auth;
+        let required: &[(&str, &Option<String>)] = match auth.method {
+            AuthMethod::None => &[],
+            AuthMethod::Bearer => &[("SERVER_AUTH_TOKEN", &auth.token)],
+            AuthMethod::Basic => &[("SERVER_AUTH_USERNAME", &auth.username)],
+            AuthMethod::OAuth2 => &[
+                ("SERVER_AUTH_TOKEN_URL", &auth.token_url),
+                ("SERVER_AUTH_CLIENT_ID", &auth.client_id),
+                ("SERVER_AUTH_CLIENT_SECRET", &auth.client_secret),
+            ],
+        };
+        for (name, value) in required {
+            if value.as_deref().unwrap_or_default().is_empty() {
+                errors.push(format!("{} must be set for SERVER_AUTH_METHOD {:?}", name, auth.method));
+            }
+        }
+        let api_auth = &mut self.api_auth;
+        for value in [&mut api_auth.secret, &mut api_auth.jwks_file, &mut api_auth.jwks_url, &mut api_auth.issuer, &mut api_auth.audience] {
+            if value.as_deref() == Some("") {
+                *value = None;
+            }
+        }
+        match api_auth.algorithm {
+            ApiAuthAlgorithm::None => (),
+            ApiAuthAlgorithm::HS256 => {
+                if api_auth.secret.as_ref().map(String::len).unwrap_or(0) < 32 {
+                    errors.push("API_AUTH_SECRET must be set to at least 32 characters for API_AUTH_ALGORITHM hs256".to_string());
+                }
+            }
+            ApiAuthAlgorithm::RS256 => {
+                if api_auth.jwks_file.is_some() == api_auth.jwks_url.is_some() {
+                    errors.push("Exactly one of API_AUTH_JWKS_FILE and API_AUTH_JWKS_URL must be set for API_AUTH_ALGORITHM rs256".to_string());
+                }
+            }
+        }
+        if self.users.admin_password.as_deref() == Some("") {
+            self.users.admin_password = None;
+        }
+        if self.users.admin_password.is_some() && self.api_auth.algorithm != ApiAuthAlgorithm::HS256 {
+            errors.push("USERS_ADMIN_PASSWORD needs API_AUTH_ALGORITHM hs256 to sign the tokens of local users".to_string());
+        }
+        if self.users.admin_username.is_empty() {
+            errors.push("USERS_ADMIN_USERNAME must not be empty".to_string());
+        }
+        if self.users.access_token_lifetime == 0 || self.users.refresh_token_lifetime == 0 {
+            errors.push("USERS_ACCESS_TOKEN_LIFETIME and USERS_REFRESH_TOKEN_LIFETIME must be greater than 0".to_string());
+        }
+        if self.activate_timeseries {
+            if self.timeseries_interval == 0 {
+                errors.push("TIMESERIES_INTERVAL must be greater than 0".to_string());
+            }
+            if self.timeseries_max_samples == 0 {
+                errors.push("TIMESERIES_MAX_SAMPLES must be greater than 0".to_string());
+            }
+        }
+    }
+}
+
+/// Overwrites `target` with the parsed environment variable `key`, if it is set.
+fn env_value<T>(
+    lookup: &impl Fn(&str) -> Option<String>,
+    key: &str,
+    target: &mut T,
+    errors: &mut Vec<String>,
+) where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    if let Some(raw) = lookup(key) {
+        match raw.trim().parse::<T>() {
+            Ok(value) => *target = value,
+            Err(e) => errors.push(format!("{} has an invalid value '{}': {}", key, raw, e)),
+        }
+    }
+}
+
+/// Checks that a non-empty `url` is an http(s) URL and appends the missing trailing slash.
+fn base_url(key: &str, url: &mut String, errors: &mut Vec<String>) {
+    if url.is_empty() {
+        return;
+    }
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        errors.push(format!("{} must be an http(s) URL, got '{}'", key, url));
+    } else if !url.ends_with('/') {
+        url.push('/');
+    }
+}
+
+/// Reads a comma separated list, replacing the configured one.
+fn env_list<T>(
+    lookup: &impl Fn(&str) -> Option<String>,
+    key: &str,
+    target: &mut Vec<T>,
+    errors: &mut Vec<String>,
+) where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    if let Some(raw) = lookup(key) {
+        let values: Result<Vec<T>, String> = raw
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| item.parse::<T>().map_err(|e| format!("'{}': {}", item, e)))
+            .collect();
+        match values {
+            Ok(values) => *target = values,
+            Err(e) => errors.push(format!("{} has an invalid value {}", key, e)),
+        }
+    }
+}
+
+fn env_option<T>(
+    lookup: &impl Fn(&str) -> Option<String>,
+    key: &str,
+    target: &mut Option<T>,
+    errors: &mut Vec<String>,
+) where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    if let Some(raw) = lookup(key) {
+        match raw.trim().parse::<T>() {
+            Ok(value) => *target = Some(value),
+            Err(e) => errors.push(format!("{} has an invalid value '{}': {}", key, raw, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(name: &str, yaml: Option<&str>, env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let path = std::env::temp_dir().join(format!("aas_client_{}_{}.yaml", name, std::process::id()));
+        if let Some(yaml) = yaml {
+            std::fs::write(&path, yaml).unwrap();
+        }
+        let env: HashMap<String, String> =
+            env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let result = Config::from_sources(&path, yaml.is_some(), |key| env.get(key).cloned());
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let yaml = "aas_id_short: FileShell\naas_identifier: https://example.com/aas/1\naasx_server: http://server:8081\ndevice_name: Device\nserver_polling_interval: 30\n";
+        let config = load("env_overrides_file", Some(yaml), &[("AAS_IDSHORT", "EnvShell"), ("CLIENT_POLLING_INTERVAL", "2")]).unwrap();
+
+        assert_eq!(config.aas_id_short, "EnvShell");
+        assert_eq!(config.aasx_server, "http://server:8081/");
+        assert_eq!(config.server_polling_interval, 30);
+        assert_eq!(config.client_polling_interval, 2);
+        assert!(!config.activate_timeseries);
+    }
+
+    #[test]
+    fn test_listen_addresses() {
+        let required = [("AAS_IDSHORT", "Shell"), ("AAS_IDENTIFIER", "id"), ("AASX_SERVER", "http://server"), ("DEVICE_NAME", "Device")];
+        let listen = [("HTTP_SERVER_LISTEN", "0.0.0.0:18000, [::1]:18001,unix:/run/aas.sock")];
+        let config = load("listen", None, &[&required[..], &listen[..]].concat()).unwrap();
+        assert_eq!(
+            config.http_server.listen,
+            vec![
+                ListenAddress::Http("0.0.0.0:18000".to_string()),
+                ListenAddress::Http("[::1]:18001".to_string()),
+                ListenAddress::Unix("/run/aas.sock".to_string()),
+            ]
+        );
+
+        let https = [("HTTP_SERVER_LISTEN", "https://0.0.0.0:18443")];
+        let err = load("listen_https", None, &[&required[..], &https[..]].concat()).unwrap_err();
+        assert!(err.to_string().contains("need HTTP_SERVER_TLS_CERT"), "{}", err);
+
+        let invalid = [("HTTP_SERVER_LISTEN", "18000")];
+        let err = load("listen_invalid", None, &[&required[..], &invalid[..]].concat()).unwrap_err();
+        assert!(err.to_string().contains("HTTP_SERVER_LISTEN has an invalid value '18000'"), "{}", err);
+    }
+
+    #[test]
+    fn test_reports_all_errors_at_once() {
+        let err = load("all_errors", None, &[("AASX_SERVER", "server"), ("OFFBOARDING_TIME", "soon"), ("ACTIVATE_TIMESERIES", "yes")])
+            .unwrap_err();
+
+        let text = err.to_string();
+        assert_eq!(err.errors.len(), 6, "{}", text);
+        assert!(text.contains("AAS_IDSHORT must be set"));
+        assert!(text.contains("OFFBOARDING_TIME has an invalid value 'soon'"));
+        assert!(text.contains("ACTIVATE_TIMESERIES has an invalid value 'yes'"));
+        assert!(text.contains("AASX_SERVER must be an http(s) URL"));
+    }
+
+    #[test]
+    fn test_reports_job_errors_of_every_job() {
+        let jobs = ["server_pushing", "server_polling", "timeseries", "registration", "concept_descriptions"];
+        let required = "aas_id_short: FileShell\naas_identifier: https://example.com/aas/1\naasx_server: http://server:8081\ndevice_name: Device\n";
+        let with_jobs = |job: &str| {
+            let jobs: String = jobs.iter().map(|name| format!("  {}: {}\n", name, job)).collect();
+            format!("{}jobs:\n{}", required, jobs)
+        };
+
+        let err = load("job_errors", Some(&with_jobs("{ interval: 0, timeout: 0, cron: 'not cron' }")), &[]).unwrap_err();
+        assert_eq!(err.errors.len(), 3 * jobs.len(), "{}", err);
+        // A cron expression whose only year has passed
+        let never = load("job_never", Some(&with_jobs("{ cron: '0 0 0 1 1 * 2000' }")), &[]).unwrap_err();
+        assert_eq!(never.errors.len(), jobs.len(), "{}", never);
+
+        let text = format!("{}\n{}", err, never);
+        for name in jobs {
+            assert!(text.contains(&format!("jobs.{}.interval must be greater than 0", name)));
+            assert!(text.contains(&format!("jobs.{}.timeout must be greater than 0", name)));
+            assert!(text.contains(&format!("jobs.{}.cron 'not cron' is invalid", name)));
+            assert!(text.contains(&format!("jobs.{}.cron '0 0 0 1 1 * 2000' never fires", name)));
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Error type shared by the RocksDB access, the calls to the AAS server, onboarding and
+//! the handlers. Handlers answer it with the HTTP status matching its kind and an AAS
+//! `Result` body.
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use chrono::Utc;
+use serde_json::json;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum AppError {
+    /// A document, submodel or element does not exist.
+    NotFound(String),
+    /// The AAS server answered with an error status.
+    Upstream { status: u16, message: String },
+    /// The AAS server could not be reached.
+    Unreachable(String),
+    /// The AAS server did not answer in time.
+    Timeout(String),
+    /// Reading or writing RocksDB failed.
+    Storage(String),
+    /// The request is malformed, e.g. an undecodable identifier or idShortPath.
+    BadRequest(String),
+    /// The request is invalid.
+    Validation(String),
+    /// Values of a patch that do not fit the stored submodel, one message per path.
+    InvalidValues(Vec<String>),
+    /// The request conflicts with the stored data, e.g. a user that already exists.
+    Conflict(String),
+    /// The request carries no valid credentials.
+    Unauthorized(String),
+    /// The credentials do not grant the request.
+    Forbidden(String),
+    /// The request cannot be served yet, e.g. before the first onboarding.
+    Unavailable { message: String, retry_after: Option<u64> },
+    Internal(String),
+}
+
+impl AppError {
+    /// Error of a response of the AAS server that was not expected.
+    pub fn upstream(status: reqwest::StatusCode, message: impl Into<String>) -> Self {
+        AppError::Upstream { status: status.as_u16(), message: message.into() }
+    }
+
+    /// Prefixes the message with `context`, keeping the kind of the error.
+    pub fn context(self, context: &str) -> Self {
+        let prefix = |message: String| format!("{}: {}", context, message);
+        match self {
+            AppError::NotFound(message) => AppError::NotFound(prefix(message)),
+            AppError::Upstream { status, message } => AppError::Upstream { status, message: prefix(message) },
+            AppError::Unreachable(message) => AppError::Unreachable(prefix(message)),
+            AppError::Timeout(message) => AppError::Timeout(prefix(message)),
+            AppError::Storage(message) => AppError::Storage(prefix(message)),
+            AppError::BadRequest(message) => AppError::BadRequest(prefix(message)),
+            AppError::Validation(message) => AppError::Validation(prefix(message)),
+            AppError::InvalidValues(messages) => AppError::InvalidValues(messages.into_iter().map(prefix).collect()),
+            AppError::Conflict(message) => AppError::Conflict(prefix(message)),
+            AppError::Unauthorized(message) => AppError::Unauthorized(prefix(message)),
+            AppError::Forbidden(message) => AppError::Forbidden(prefix(message)),
+            AppError::Unavailable { message, retry_after } => {
+                AppError::Unavailable { message: prefix(message), retry_after }
+            }
+            AppError::Internal(message) => AppError::Internal(prefix(message)),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Upstream { message, .. }
+            | AppError::Unreachable(message)
+            | AppError::Timeout(message)
+            | AppError::Storage(message)
+            | AppError::BadRequest(message)
+            | AppError::Validation(message)
+            | AppError::Conflict(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::Unavailable { message, .. }
+            | AppError::Internal(message) => message,
+            AppError::InvalidValues(_) => "Invalid values",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Upstream { status, message } => {
+                write!(f, "AAS server answered {}: {}", status, message)
+            }
+            AppError::InvalidValues(messages) => write!(f, "Invalid values: {}", messages.join("; ")),
+            _ => f.write_str(self.message()),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        let url = e.url().map(|url| url.to_string()).unwrap_or_default();
+        if e.is_timeout() {
+            AppError::Timeout(format!("Request to {} timed out", url))
+        } else if let Some(status) = e.status() {
+            AppError::upstream(status, e.to_string())
+        } else {
+            AppError::Unreachable(format!("Failed to send request to {}: {}", url, e))
+        }
+    }
+}
+
+impl From<rocksdb::Error> for AppError {
+    fn from(e: rocksdb::Error) -> Self {
+        AppError::Storage(format!("RocksDB error: {}", e))
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Upstream { status: 409, .. } | AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Upstream { .. } | AppError::Unreachable(_) => StatusCode::BAD_GATEWAY,
+            AppError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Validation(_) | AppError::InvalidValues(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Unavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Storage(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Answers with an AAS `Result` holding the error message, or one message per value for
+    /// `InvalidValues`.
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        let mut response = HttpResponse::build(status);
+        match self {
+            AppError::Unavailable { retry_after: Some(seconds), .. } => {
+                response.insert_header(("Retry-After", seconds.to_string()));
+            }
+            AppError::Unauthorized(_) => {
+                response.insert_header(("WWW-Authenticate", "Bearer"));
+            }
+            _ => (),
+        }
+        let texts = match self {
+            AppError::InvalidValues(messages) => messages.clone(),
+            _ => vec![self.to_string()],
+        };
+        let timestamp = Utc::now().to_rfc3339();
+        let messages: Vec<_> = texts
+            .into_iter()
+            .map(|text| {
+                json!({
+                    "messageType": "Error",
+                    "text": text,
+                    "code": status.as_u16().to_string(),
+                    "timestamp": timestamp,
+                })
+            })
+            .collect();
+        response.json(json!({ "messages": messages }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[actix_web::test]
+    async fn test_error_response_is_an_aas_result() {
+        let error = AppError::NotFound("Document with id 'x' not found".to_string()).context("Error getting submodel");
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body: serde_json::Value = serde_json::from_slice(&to_bytes(response.into_body()).await.unwrap()).unwrap();
+        let message = &body["messages"][0];
+        assert_eq!(message["messageType"], "Error");
+        assert_eq!(message["code"], "404");
+        assert_eq!(message["text"], "Error getting submodel: Document with id 'x' not found");
+    }
+
+    #[test]
+    fn test_upstream_status_codes() {
+        let conflict = AppError::upstream(reqwest::StatusCode::CONFLICT, "exists");
+        let server_error = AppError::upstream(reqwest::StatusCode::INTERNAL_SERVER_ERROR, "boom");
+        assert_eq!(conflict.status_code(), StatusCode::CONFLICT);
+        assert_eq!(server_error.status_code(), StatusCode::BAD_GATEWAY);
+        assert_eq!(AppError::Timeout("slow".to_string()).status_code(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(AppError::Validation("bad".to_string()).status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_values_list_every_path() {
+        let error = AppError::InvalidValues(vec!["A: no such element".to_string(), "B: expected an object".to_string()]);
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body: serde_json::Value = serde_json::from_slice(&to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["messages"][1]["text"], "B: expected an object");
+    }
+}
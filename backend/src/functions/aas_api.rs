@@ -0,0 +1,315 @@
+//! Serializations of the AAS API v3 (Part 2) over the cached shell and submodels: the
+//! `content` and `level` modifiers and paged results.
+
+use rocksdb::DB;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::aas_interfaces::{self, aas_find_one};
+use super::aas_server::AasServerClient;
+use super::id_short_path::{self, PathSegment};
+use super::transform_value_submodel::{merge_value_to_submodel_element, submodel_element_to_value, submodel_to_submodel_value};
+use crate::config::{Config, PatchValidation};
+use crate::error::AppError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Content {
+    #[default]
+    Normal,
+    Value,
+    Metadata,
+    Reference,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    #[default]
+    Deep,
+    Core,
+}
+
+/// Query parameters selecting the serialization.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Modifiers {
+    pub content: Content,
+    pub level: Level,
+}
+
+/// Query parameters filtering the submodels.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SubmodelFilter {
+    /// First key of the `semanticId`, base64url encoded in the AAS API.
+    #[serde(rename = "semanticId")]
+    pub semantic_id: Option<String>,
+}
+
+/// Query parameters of paged results.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Paging {
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+/// `PagedResult` with `limit` items starting at `cursor`, the offset of the first item.
+pub fn paged(items: Vec<Value>, paging: &Paging) -> Result<Value, AppError> {
+    let start = match &paging.cursor {
+        Some(cursor) => cursor
+            .parse::<usize>()
+            .map_err(|_| AppError::BadRequest(format!("Invalid cursor '{}'", cursor)))?,
+        None => 0,
+    };
+    let end = match paging.limit {
+        Some(0) => return Err(AppError::BadRequest("limit must be greater than 0".to_string())),
+        Some(limit) => start.saturating_add(limit).min(items.len()),
+        None => items.len(),
+    };
+
+    let mut paging_metadata = Map::new();
+    if end < items.len() {
+        paging_metadata.insert("cursor".to_string(), json!(end.to_string()));
+    }
+    let result: Vec<Value> = items.into_iter().skip(start).take(end.saturating_sub(start)).collect();
+    Ok(json!({ "paging_metadata": paging_metadata, "result": result }))
+}
+
+/// The cached shell, `NotFound` unless it has the identifier `aas_id`.
+pub async fn find_shell(rocksdb: Arc<Mutex<DB>>, aas_id_short: &str, aas_id: &str) -> Result<Value, AppError> {
+    let shell = aas_find_one(aas_id_short.to_string(), rocksdb).await?;
+    if shell["id"] != aas_id {
+        return Err(AppError::NotFound(format!("Shell '{}' not found", aas_id)));
+    }
+    Ok(shell)
+}
+
+/// The idShort and the cached document of the submodel with the identifier `submodel_id`.
+pub async fn find_submodel(
+    rocksdb: Arc<Mutex<DB>>,
+    aas_id_short: &str,
+    submodel_id: &str,
+) -> Result<(String, Value), AppError> {
+    let dictionary = aas_find_one(format!("{}:submodels_dictionary", aas_id_short), rocksdb.clone()).await?;
+    let submodel_id_short = dictionary
+        .as_object()
+        .and_then(|dictionary| dictionary.iter().find(|(_, id)| id.as_str() == Some(submodel_id)))
+        .map(|(id_short, _)| id_short.clone())
+        .ok_or_else(|| AppError::NotFound(format!("Submodel '{}' not found", submodel_id)))?;
+    let submodel = aas_find_one(format!("{}:{}", aas_id_short, submodel_id_short), rocksdb).await?;
+    Ok((submodel_id_short, submodel))
+}
+
+/// Serializes a submodel as selected by `modifiers`.
+pub fn submodel(mut submodel: Value, modifiers: &Modifiers) -> Result<Value, AppError> {
+    if modifiers.level == Level::Core {
+        for element in submodel["submodelElements"].as_array_mut().into_iter().flatten() {
+            remove_children(element);
+        }
+    }
+
+    match modifiers.content {
+        Content::Normal => Ok(submodel),
+        Content::Value => Ok(submodel_to_submodel_value(submodel)),
+        Content::Metadata => {
+            if let Some(submodel) = submodel.as_object_mut() {
+                submodel.remove("submodelElements");
+            }
+            Ok(submodel)
+        }
+        Content::Reference => Ok(reference(&submodel["id"], &[])),
+    }
+}
+
+/// Serializes the element at `path` of `submodel` as selected by `modifiers`.
+pub fn submodel_element(submodel: &Value, path: &[PathSegment], modifiers: &Modifiers) -> Result<Value, AppError> {
+    let mut element = id_short_path::find_element(submodel, path)?.clone();
+    if modifiers.level == Level::Core {
+        if let Some(key) = element["modelType"].as_str().and_then(id_short_path::children_key) {
+            for child in element[key].as_array_mut().into_iter().flatten() {
+                remove_children(child);
+            }
+        }
+    }
+
+    match modifiers.content {
+        Content::Normal => Ok(element),
+        Content::Value => Ok(element_value(&element)),
+        Content::Metadata => {
+            let model_type = element["modelType"].as_str().unwrap_or_default().to_string();
+            if let Some(element) = element.as_object_mut() {
+                for key in value_keys(&model_type) {
+                    element.remove(*key);
+                }
+            }
+            Ok(element)
+        }
+        Content::Reference => {
+            let elements = submodel["submodelElements"].as_array().map(Vec::as_slice).unwrap_or_default();
+            let trail = id_short_path::resolve_trail(elements, path).unwrap_or_default();
+            let keys: Vec<Value> = trail
+                .iter()
+                .zip(path)
+                .map(|(element, segment)| {
+                    let value = match segment {
+                        PathSegment::IdShort(id_short) => id_short.clone(),
+                        PathSegment::Index(index) => index.to_string(),
+                    };
+                    json!({ "type": element["modelType"], "value": value })
+                })
+                .collect();
+            Ok(reference(&submodel["id"], &keys))
+        }
+    }
+}
+
+/// Value-only serialization of a single element.
+pub fn element_value(element: &Value) -> Value {
+    submodel_element_to_value(element).unwrap_or(Value::Null)
+}
+
+/// Fails with the offending paths of a PATCH payload, or only logs them for
+/// `PatchValidation::Lenient`.
+pub fn validate_patch(validation: PatchValidation, invalid: Vec<String>) -> Result<(), AppError> {
+    if invalid.is_empty() {
+        return Ok(());
+    }
+    match validation {
+        PatchValidation::Strict => Err(AppError::InvalidValues(invalid)),
+        PatchValidation::Lenient => {
+            eprintln!("Patch does not fit the submodel, merging what fits: {}", invalid.join("; "));
+            Ok(())
+        }
+    }
+}
+
+/// Patch in the value-only format of a whole submodel that sets the element at `path` to
+/// `value`. It carries the complete value of the enclosing top-level element, so that it
+/// also reaches into `SubmodelElementList`s, which are written by position.
+pub fn nested_patch(
+    submodel: &Value,
+    path: &[PathSegment],
+    value: &Value,
+    validation: PatchValidation,
+) -> Result<Value, AppError> {
+    let mut submodel = submodel.clone();
+    let element = id_short_path::find_element_mut(&mut submodel, path)?;
+    validate_patch(validation, merge_value_to_submodel_element(element, value, &id_short_path::format(path)))?;
+    let top_level = &path[..1];
+    let id_short = id_short_path::format(top_level);
+    Ok(json!({ id_short: element_value(id_short_path::find_element(&submodel, top_level)?) }))
+}
+
+/// Sets the value of the element at `path` in the cached submodel and pushes it to the
+/// AAS server, if there is one.
+pub async fn patch_element_value(
+    server: &dyn AasServerClient,
+    config: &Config,
+    rocksdb: Arc<Mutex<DB>>,
+    submodel_id_short: &str,
+    path: &[PathSegment],
+    value: Value,
+) -> Result<(), AppError> {
+    let submodel = aas_interfaces::get_submodel_database(rocksdb.clone(), &config.aas_id_short, submodel_id_short).await?;
+    let patch = nested_patch(&submodel, path, &value, config.patch_validation)?;
+
+    aas_interfaces::patch_submodel_database(rocksdb.clone(), &config.aas_id_short, submodel_id_short, &patch)
+        .await
+        .map_err(|e| e.context("Error patching submodel in database"))?;
+    if !config.has_aas_server() {
+        return Ok(());
+    }
+    aas_interfaces::patch_submodel_server(
+        server,
+        rocksdb,
+        &config.aas_id_short,
+        submodel_id_short,
+        config.push_mode,
+        &patch,
+    )
+    .await
+    .map_err(|e| e.context("Error patching submodel to server"))?;
+    Ok(())
+}
+
+fn reference(submodel_id: &Value, element_keys: &[Value]) -> Value {
+    let mut keys = vec![json!({ "type": "Submodel", "value": submodel_id })];
+    keys.extend_from_slice(element_keys);
+    json!({ "type": "ModelReference", "keys": keys })
+}
+
+/// Drops the child elements of an element, for `level=core`.
+fn remove_children(element: &mut Value) {
+    if let Some(key) = element["modelType"].as_str().and_then(id_short_path::children_key) {
+        if let Some(element) = element.as_object_mut() {
+            element.remove(key);
+        }
+    }
+}
+
+/// Attributes of an element of `model_type` left out of its metadata.
+fn value_keys(model_type: &str) -> &'static [&'static str] {
+    match model_type {
+        "Property" | "MultiLanguageProperty" | "ReferenceElement" => &["value", "valueId"],
+        "File" | "Blob" | "SubmodelElementCollection" | "SubmodelElementList" => &["value"],
+        "Range" => &["min", "max"],
+        "RelationshipElement" => &["first", "second"],
+        "AnnotatedRelationshipElement" => &["first", "second", "annotations"],
+        "Entity" => &["statements", "globalAssetId", "specificAssetIds"],
+        "BasicEventElement" => &["observed"],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modifiers_and_paging() {
+        let submodel = json!({
+            "modelType": "Submodel",
+            "id": "urn:sm:1",
+            "idShort": "SystemInformation",
+            "submodelElements": [{
+                "modelType": "SubmodelElementCollection",
+                "idShort": "Hardware",
+                "value": [{
+                    "modelType": "SubmodelElementCollection",
+                    "idShort": "Processor",
+                    "value": [{ "modelType": "Property", "idShort": "CpuCores", "valueType": "xs:integer", "value": "4" }]
+                }]
+            }]
+        });
+        let modifiers = |content, level| Modifiers { content, level };
+        let path = id_short_path::parse("Hardware.Processor").unwrap();
+
+        let value = super::submodel(submodel.clone(), &modifiers(Content::Value, Level::Deep)).unwrap();
+        assert_eq!(value, json!({ "Hardware": { "Processor": { "CpuCores": 4 } } }));
+        let core = super::submodel(submodel.clone(), &modifiers(Content::Normal, Level::Core)).unwrap();
+        assert!(core["submodelElements"][0].get("value").is_none());
+        let metadata = submodel_element(&submodel, &path, &modifiers(Content::Metadata, Level::Deep)).unwrap();
+        assert!(metadata.get("value").is_none());
+        assert_eq!(metadata["idShort"], "Processor");
+        let reference = submodel_element(&submodel, &path, &modifiers(Content::Reference, Level::Deep)).unwrap();
+        assert_eq!(reference["keys"][2], json!({ "type": "SubmodelElementCollection", "value": "Processor" }));
+        assert_eq!(
+            nested_patch(&submodel, &path, &json!({ "CpuCores": "8" }), PatchValidation::Strict).unwrap(),
+            json!({ "Hardware": { "Processor": { "CpuCores": 8 } } })
+        );
+        assert!(nested_patch(&submodel, &path, &json!({ "CpuCores": 4.5 }), PatchValidation::Strict).is_err());
+        assert!(nested_patch(&submodel, &path, &json!({ "CpuCores": 4.5 }), PatchValidation::Lenient).is_ok());
+
+        let items: Vec<Value> = (0..5).map(|i| json!(i)).collect();
+        let first = paged(items.clone(), &Paging { limit: Some(2), cursor: None }).unwrap();
+        assert_eq!(first["result"], json!([0, 1]));
+        let cursor = first["paging_metadata"]["cursor"].as_str().unwrap().to_string();
+        let last = paged(items, &Paging { limit: Some(3), cursor: Some(cursor) }).unwrap();
+        assert_eq!(last["result"], json!([2, 3, 4]));
+        assert!(last["paging_metadata"].get("cursor").is_none());
+    }
+}
@@ -1,33 +1,35 @@
-use anyhow::Context;
 use rocksdb::DB;
-use serde_json::Value;
+use serde_json::{Map, Value};
 use tokio::sync::Mutex;
 use std::sync::Arc;
-use reqwest;
-use reqwest::Error as ReqwestError;
 
 
 // use serde::{Serialize, Deserialize};
 
-use crate::functions::transform_value_submodel::merge_submodel_value_to_submodel;
+use crate::config::{Config, PushMode, WellKnownSubmodel};
+use crate::error::AppError;
+use crate::functions::aas_server::{AasServerClient, ValueSupport};
+use crate::functions::transform_value_submodel::{merge_submodel_value_to_submodel, submodel_to_submodel_value};
+use crate::metrics;
 
 // Find one document in RocksDB based on the _id
 pub async fn aas_find_one(
     _id: String, // composite ID for the submodel using AAS ID and submodel ID
     rocksdb: Arc<Mutex<DB>>, // RocksDB database instance
-) -> Result<Value, String> {
+) -> Result<Value, AppError> {
     let db = rocksdb.lock().await;
 
     // Fetch the value from RocksDB based on the key (_id)
+    metrics::record_rocksdb_read();
     match db.get(_id.clone()) {
         Ok(Some(data)) => {
             // Deserialize the stored data into a `serde_json::Value` object
             let value: Value = serde_json::from_slice(&data)
-                .map_err(|e| format!("Error deserializing document: {}", e))?;
+                .map_err(|e| AppError::Storage(format!("Error deserializing document: {}", e)))?;
             Ok(value)
         }
-        Ok(None) => Err(format!("Document with id '{}' not found", _id)),
-        Err(e) => Err(format!("Error finding document: {}", e)),
+        Ok(None) => Err(AppError::NotFound(format!("Document with id '{}' not found", _id))),
+        Err(e) => Err(AppError::Storage(format!("Error finding document: {}", e))),
     }
 }
 
@@ -37,16 +39,17 @@ pub async fn aas_update_one(
     rocksdb: Arc<Mutex<DB>>, // RocksDB database instance
     new_document: Value, // Document to be upserted
     _upsert: bool, // Ignored since RocksDB overwrites by default
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let db = rocksdb.lock().await;
 
     // Serialize the JSON document into bytes
     let serialized_doc = serde_json::to_vec(&new_document)
-        .map_err(|e| format!("Error serializing document: {}", e))?;
+        .map_err(|e| AppError::Storage(format!("Error serializing document: {}", e)))?;
 
     // Insert or update the document in RocksDB
+    metrics::record_rocksdb_write();
     db.put(_id.clone(), serialized_doc)
-        .map_err(|e| format!("Error upserting document: {}", e))?;
+        .map_err(|e| AppError::Storage(format!("Error upserting document: {}", e)))?;
 
     Ok(format!("Document updated successfully with id: {}", _id))
 }
@@ -55,7 +58,7 @@ pub async fn get_submodel_database(
     rocksdb: Arc<Mutex<DB>>, // Arc and Mutex for thread-safe shared access to the RocksDB instance
     aas_id_short: &str,    // Short ID for the AAS (Asset Administration Shell)
     submodel_id_short: &str,  // Short ID for the submodel
-) -> Result<Value, String> {
+) -> Result<Value, AppError> {
     // Create a composite ID for the submodel using AAS ID and submodel ID
     let _id_submodel = format!("{}:{}", aas_id_short, submodel_id_short);
 
@@ -65,7 +68,7 @@ pub async fn get_submodel_database(
     // Handle success or error in fetching the submodel
     let aas_submodel = match aas_submodel_result {
         Ok(aas_submodel) => aas_submodel,
-        Err(e) => return Err(format!("Error getting submodel: {}", e)),
+        Err(e) => return Err(e.context("Error getting submodel")),
     };
 
     Ok(aas_submodel)
@@ -76,7 +79,7 @@ pub async fn patch_submodel_database(
     aas_id_short: &str,    // Short ID for the AAS (Asset Administration Shell)
     submodel_id_short: &str,  // Short ID for the submodel
     submodel_value: &Value  // JSON data to be patched into the submodel
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     // Create a composite ID for the submodel using AAS ID and submodel ID
     let _id_submodel = format!("{}:{}", aas_id_short, submodel_id_short);
 
@@ -97,197 +100,371 @@ pub async fn patch_submodel_database(
 }
 
 
-// Merge two documents recursively
-fn merge_documents(old_doc: &Value, new_doc: &Value) -> Value {
-    let mut merged_doc = old_doc.clone(); // Start with the old document
-
-    // Iterate over the keys in the new document and update or insert into the old document
-    if let Value::Object(ref old_map) = old_doc {
-        if let Value::Object(ref new_map) = new_doc {
-            for (key, new_value) in new_map {
-                // If both old and new values are objects, recursively merge them
-                if let Some(old_value) = old_map.get(key) {
-                    if let (Value::Object(_), Value::Object(_)) = (old_value, new_value) {
-                        let merged_subdoc = merge_documents(old_value, new_value);
-                        merged_doc[key] = merged_subdoc;
-                    } else {
-                        merged_doc[key] = new_value.clone(); // Overwrite if not both objects
-                    }
-                } else {
-                    merged_doc[key] = new_value.clone(); // Add new key-value pairs
-                }
-            }
-        }
-    }
-    merged_doc
-}
-
-
 pub async fn patch_submodel_server(
+    server: &dyn AasServerClient, // AAS server in its dialect, see `AppState::aas_server`
     rocksdb: Arc<Mutex<DB>>, // RocksDB instance for thread-safe shared access
     aas_id_short: &str,    // Short ID for the AAS (Asset Administration Shell)
     submodel_id_short: &str,  // Short ID for the submodel
-    aasx_server_url: &str,    // Base URL of the AASX server
+    push_mode: PushMode,      // PATCH of the element values or PUT of the whole submodel
     submodel_value: &Value  // JSON data to be patched into the submodel
-) -> Result<String, String> {
+) -> Result<String, AppError> {
+    let timer = metrics::PUSH_DURATION.with_label_values(&[submodel_id_short]).start_timer();
+    let result = push_submodel_server(server, rocksdb, aas_id_short, submodel_id_short, push_mode, submodel_value).await;
+    timer.observe_duration();
+    metrics::PUSH_TOTAL
+        .with_label_values(&[submodel_id_short, metrics::result_label(&result)])
+        .inc();
+    result
+}
+
+async fn push_submodel_server(
+    server: &dyn AasServerClient,
+    rocksdb: Arc<Mutex<DB>>,
+    aas_id_short: &str,
+    submodel_id_short: &str,
+    push_mode: PushMode,
+    submodel_value: &Value
+) -> Result<String, AppError> {
     // Create a composite ID for the submodel using AAS ID and submodel ID
     let _id_submodel = format!("{}:{}", aas_id_short, submodel_id_short);
 
     // Retrieve the existing submodel document from the RocksDB database
     let existing_submodel = aas_find_one(_id_submodel.clone(), rocksdb.clone()).await
-        .map_err(|e| format!("Error getting submodel: {}", e))?;
+        .map_err(|e| e.context("Error getting submodel"))?;
 
     // Merge the existing submodel document with the patch document
     let merged_doc = merge_submodel_value_to_submodel(existing_submodel, submodel_value.clone());
 
-    // Retrieve the submodels dictionary from RocksDB
-    let submodels_dictionary = aas_find_one(format!("{}:submodels_dictionary", aas_id_short), rocksdb.clone()).await
-        .map_err(|e| format!("Error getting submodels dictionary: {}", e))?;
-
-    // Extract the submodel UID from the dictionary
-    let submodel_uid = submodels_dictionary.get(submodel_id_short)
-        .and_then(|val| val.as_str())
-        .ok_or_else(|| "Submodel not found in dictionary".to_string())?;
-
-    // Create a new HTTP client
-    let client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)  // This allows SSL certificates to be ignored (only for testing purposes!)
-        .timeout(std::time::Duration::from_secs(10))  // You can add a timeout for the request
-        .build()
-        .expect("Failed to build client");
-    // Construct the URL for the submodel value endpoint
-    let url = format!(
-        "{}submodels/{}",
-        aasx_server_url,
-        base64::encode_config(submodel_uid, base64::URL_SAFE_NO_PAD),
-    );
-
-    // Send a PATCH request to the submodel value endpoint
-    // let response = client.put(&url)
-    //     .json(&merged_doc)
-    //     .send()
-    //     .await
-    //     .map_err(|e| format!("Error sending put request: {}", e))?;
-
-    let response = client.put(&url)
-        .json(&merged_doc)
-        .send()
-        .await
-        .map_err(|e| {
-            let pretty_body = serde_json::to_string_pretty(&merged_doc).unwrap_or_else(|_| "Failed to serialize JSON".to_string());
-
-            if e.is_timeout() {
-                format!("Request to {} timed out", url)
-            } else if e.is_status() {
-                format!(
-                    "Received error response {:?} from {}. \nRequest body: {}",
-                    e.status(), url, pretty_body
-                )
-            } else if e.is_request() {
-                format!(
-                    "Failed to send request to {}: {}. \nRequest body: {}",
-                    url, e, pretty_body
-                )
-            } else {
-                format!(
-                    "An unexpected error occurred while sending PUT request to {}: {}. \nRequest body: {}",
-                    url, e, pretty_body
-                )
-            }
-        })?;
+    let url = submodel_url(rocksdb.clone(), aas_id_short, submodel_id_short, server).await?;
 
-    // Check the response status code and return appropriate message
-    match response.status() {
-        reqwest::StatusCode::NO_CONTENT => Ok("Submodel putted successfully".into()),  // Return success message if status is 204 No Content
-        _ => Err(response.text().await.unwrap_or_else(|_| "Unknown error".into())),  // Return error message if status is not 204
+    if push_mode != PushMode::Put && server.value_capabilities().get(&url) != ValueSupport::None {
+        let changed = changed_values(&merged_doc, submodel_value);
+        if patch_value_server(server, &url, push_mode, &changed).await? {
+            return Ok("Submodel patched successfully".into());
+        }
     }
+
+    server.put_submodel(&url, &merged_doc).await?;
+    Ok("Submodel putted successfully".into())
 }
 
+/// Value-only serialization of the top-level elements of `submodel` named in `patch`, as
+/// stored after the merge.
+fn changed_values(submodel: &Value, patch: &Value) -> Map<String, Value> {
+    let values = submodel_to_submodel_value(submodel.clone());
+    patch
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(id_short, _)| values.get(id_short).map(|value| (id_short.clone(), value.clone())))
+        .collect()
+}
+
+/// PATCHes the `$value` of each changed element, or of the submodel with all of them if
+/// the server offers that. `false` if the server has no `$value` of elements either, then
+/// nothing was written and the submodel is to be PUT.
+///
+/// A server without `$value` of submodels answers 404, 405 or 501 to it. To the `$value` of
+/// an element it answers 405 or 501 if it has none, 404 only means the element is missing.
+async fn patch_value_server(
+    server: &dyn AasServerClient,
+    url: &str,
+    push_mode: PushMode,
+    changed: &Map<String, Value>,
+) -> Result<bool, AppError> {
+    let capabilities = server.value_capabilities();
+    if push_mode == PushMode::Submodel && capabilities.get(url) == ValueSupport::Submodel {
+        let value_url = format!("{}/$value", url);
+        match server.patch_value(&value_url, "patch_submodel_value", &Value::Object(changed.clone())).await {
+            Err(AppError::Upstream { status: status @ (404 | 405 | 501), .. }) => {
+                eprintln!(
+                    "AAS server answered {} to the $value PATCH of {}, patching its elements from now on",
+                    status, url
+                );
+                capabilities.lower(url, ValueSupport::Element);
+            }
+            result => return result.map(|()| true),
+        }
+    }
+    for (index, (id_short, value)) in changed.iter().enumerate() {
+        let value_url = format!("{}/submodel-elements/{}/$value", url, id_short);
+        match server.patch_value(&value_url, "patch_submodel_element_value", value).await {
+            Ok(()) => (),
+            // Only before anything was written, a PUT would overwrite the elements patched so far
+            Err(AppError::Upstream { status: status @ (405 | 501), .. }) if index == 0 => {
+                eprintln!(
+                    "AAS server answered {} to the $value PATCH of {}, pushing with PUT from now on",
+                    status, value_url
+                );
+                capabilities.lower(url, ValueSupport::None);
+                return Ok(false);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
 
 // Fetch a single submodel from the AASX server
 pub async fn fetch_single_submodel_from_server(
-    aasx_server_url: &str,
+    server: &dyn AasServerClient,
     aas_id_short: &str,
     submodel_id_short: &str,
     rocksdb: Arc<Mutex<DB>>,
-) -> Result<(), String> {
-    
-    let submodels_dictionary = aas_find_one(format!("{}:submodels_dictionary", aas_id_short), rocksdb.clone()).await;
-    
-    let submodel_uid = match submodels_dictionary {
-        Ok(submodels_dictionary) => {
-            match submodels_dictionary.get(submodel_id_short) {
-                Some(Value::String(submodel_uid_str)) => submodel_uid_str.to_owned(), // Convert to String
-                _ => return Err("Submodel not found in dictionary".into()), // Handle error
-            }
-        },
-        Err(e) => return Err(format!("Error getting submodels dictionary: {}", e)),
-    };
+) -> Result<(), AppError> {
+    let timer = metrics::POLL_DURATION.with_label_values(&[submodel_id_short]).start_timer();
+    let result = get_submodel_server(server, aas_id_short, submodel_id_short, rocksdb).await;
+    timer.observe_duration();
+    metrics::POLL_TOTAL
+        .with_label_values(&[submodel_id_short, metrics::result_label(&result)])
+        .inc();
+    result
+}
 
-    let client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)  // This allows SSL certificates to be ignored (only for testing purposes!)
-        .timeout(std::time::Duration::from_secs(10))  // You can add a timeout for the request
-        .build()
-        .expect("Failed to build client");
-    let submodel_url: String = format!(
-        "{}submodels/{}",
-        aasx_server_url,
-        base64::encode_config(submodel_uid, base64::URL_SAFE_NO_PAD),
-    );
-
-    let response = client
-        .get(&submodel_url)
-        .send()
+async fn get_submodel_server(
+    server: &dyn AasServerClient,
+    aas_id_short: &str,
+    submodel_id_short: &str,
+    rocksdb: Arc<Mutex<DB>>,
+) -> Result<(), AppError> {
+    let submodel_url = submodel_url(rocksdb.clone(), aas_id_short, submodel_id_short, server).await?;
+    let document = server
+        .get_submodel(&submodel_url)
         .await
-        .map_err(|e| 
-            if e.is_timeout() {
-                format!("Request to {} timed out", submodel_url)
-            } else if e.is_status() {
-                format!("Received error response {:?} from {}", e.status(), submodel_url)
-            } else if e.is_request() {
-                format!("Failed to send request to {}: {}", submodel_url, e)
-            } else {
-                format!("An unexpected error occurred while sending GET request to {}: {}", submodel_url, e)
-            }
-        )?;
-       
+        .map_err(|e| e.context(&format!("Error fetching submodel {}", submodel_url)))?;
 
-    if response.status().is_success() {
-        let body_value: Value = response.json().await
-            .with_context(|| "Failed to parse JSON response")
-            .map_err(|e| format!("Error parsing JSON response: {}", e))?;
+    let serialized = serde_json::to_vec(&document).map_err(|e| AppError::Internal(e.to_string()))?;
 
-        let bson_value = serde_json::to_value(&body_value)
-            .with_context(|| "Failed to convert JSON to BSON")
-            .map_err(|e| format!("Error converting JSON to BSON: {}", e))?;
+    let db = rocksdb.lock().await;
+    metrics::record_rocksdb_write();
+    db.put(
+        format!("{}:{}", aas_id_short, submodel_id_short),
+        serialized
+    ).map_err(|e| AppError::from(e).context("Failed to replace submodel in RocksDB"))?;
 
-        if let Value::Object(document) = bson_value {
-            let db = rocksdb.lock().await;
-            db.put(
-                format!("{}:{}", aas_id_short, submodel_id_short),
-                serde_json::to_vec(&document).expect("Failed to serialize JSON")
-            ).expect("Failed to replace submodel in RocksDB");
+    println!("Successfully replaced submodel: {}", submodel_id_short);
+    Ok(())
+}
 
-            println!("Successfully replaced submodel: {}", submodel_id_short)
-        } else {
-            return Err("Response body is not a BSON document".into())
+/// URL of a submodel at the AAS server: the endpoint found in the registry during
+/// onboarding, otherwise `submodels/{base64 id}` below the API of the server. Not found if
+/// neither is known, i.e. only a registry is configured.
+pub async fn submodel_url(
+    rocksdb: Arc<Mutex<DB>>,
+    aas_id_short: &str,
+    submodel_id_short: &str,
+    server: &dyn AasServerClient,
+) -> Result<String, AppError> {
+    if let Ok(endpoints) = aas_find_one(format!("{}:submodel_endpoints", aas_id_short), rocksdb.clone()).await {
+        if let Some(url) = endpoints.get(submodel_id_short).and_then(Value::as_str) {
+            return Ok(url.to_string());
         }
+    }
+
+    let submodels_dictionary = aas_find_one(format!("{}:submodels_dictionary", aas_id_short), rocksdb).await
+        .map_err(|e| e.context("Error getting submodels dictionary"))?;
+    let submodel_uid = submodels_dictionary.get(submodel_id_short)
+        .and_then(Value::as_str)
+        .ok_or_else(|| AppError::NotFound(format!("Submodel {} not found in dictionary", submodel_id_short)))?;
+    if server.base_url().is_empty() {
+        return Err(AppError::NotFound(format!(
+            "Submodel {} is not in the registry and AASX_SERVER is not set", submodel_id_short
+        )));
+    }
+
+    Ok(server.submodel_url(submodel_uid))
+}
 
-    } else {
-        return Err(format!("Error fetching submodel: {:?}", response))
+/// First key of the `semanticId` of a submodel or element.
+pub fn semantic_id(value: &Value) -> Option<&str> {
+    value["semanticId"]["keys"].as_array()?.first()?["value"].as_str()
+}
+
+/// Indexes the stored submodels by their semanticId, next to the dictionary of their
+/// idShorts. Submodels without one are only found by idShort.
+pub async fn index_semantic_ids(rocksdb: Arc<Mutex<DB>>, aas_id_short: &str) -> Result<(), AppError> {
+    let dictionary = aas_find_one(format!("{}:submodels_dictionary", aas_id_short), rocksdb.clone()).await?;
+
+    let mut index = Map::new();
+    for submodel_id_short in dictionary.as_object().into_iter().flat_map(|dictionary| dictionary.keys()) {
+        let submodel = aas_find_one(format!("{}:{}", aas_id_short, submodel_id_short), rocksdb.clone()).await?;
+        if let Some(semantic_id) = semantic_id(&submodel) {
+            index.insert(semantic_id.to_string(), Value::String(submodel_id_short.clone()));
+        }
     }
+
+    aas_update_one(format!("{}:submodels_semantic_ids", aas_id_short), rocksdb, Value::Object(index), true).await?;
     Ok(())
 }
 
-pub async fn read_managed_device(
+/// idShort of the stored submodel with the semanticId `semantic_id`.
+pub async fn find_by_semantic_id(
     rocksdb: Arc<Mutex<DB>>,
     aas_id_short: &str,
-) -> Result<Value, String> {
-    let table_id = format!("{}:{}", aas_id_short, "ManagedDevice");
+    semantic_id: &str,
+) -> Result<String, AppError> {
+    let index = match aas_find_one(format!("{}:submodels_semantic_ids", aas_id_short), rocksdb).await {
+        Ok(index) => index,
+        Err(AppError::NotFound(_)) => Value::Null,
+        Err(e) => return Err(e),
+    };
+    index
+        .get(semantic_id)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| AppError::NotFound(format!("No submodel with semanticId '{}'", semantic_id)))
+}
+
+/// idShort of a submodel the client relies on: the one with its semanticId if there is
+/// such a submodel, otherwise the configured idShort.
+pub async fn resolve_submodel(rocksdb: Arc<Mutex<DB>>, config: &Config, submodel: &WellKnownSubmodel) -> String {
+    if let Some(semantic_id) = &submodel.semantic_id {
+        match find_by_semantic_id(rocksdb, &config.aas_id_short, semantic_id).await {
+            Ok(id_short) => return id_short,
+            Err(AppError::NotFound(_)) => (),
+            Err(e) => eprintln!("Failed to look up submodel {}: {}", semantic_id, e),
+        }
+    }
+    submodel.id_short.clone()
+}
+
+pub async fn read_managed_device(
+    rocksdb: Arc<Mutex<DB>>,
+    config: &Config,
+) -> Result<Value, AppError> {
+    let submodel_id_short = resolve_submodel(rocksdb.clone(), config, &config.submodels.managed_device).await;
+    let table_id = format!("{}:{}", config.aas_id_short, submodel_id_short);
     
     let managed_device = aas_find_one(table_id, rocksdb.clone()).await;
     match managed_device {
         Ok(managed_device) => Ok(managed_device),
-        Err(e) => Err(format!("Failed to find managed device: {}", e)),
+        Err(e) => Err(e.context("Failed to find managed device")),
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerDialect;
+    use crate::functions::aas_server;
+    use crate::test_utils::{aas_client, MockServer, TempDb};
+    use actix_web::{web, HttpRequest, HttpResponse};
+    use serde_json::json;
+
+    type Requests = web::Data<std::sync::Mutex<Vec<(String, String, Value)>>>;
+
+    /// Records the request. The submodel "new" is served by a server without `$value` of
+    /// submodels and without the element LastUpdate, "old" by one without any `$value`.
+    async fn record(req: HttpRequest, body: web::Json<Value>, requests: Requests) -> HttpResponse {
+        let path = req.path().to_string();
+        requests.lock().unwrap().push((req.method().to_string(), path.clone(), body.into_inner()));
+        if path == "/submodels/new/$value" || path.ends_with("/LastUpdate/$value") {
+            HttpResponse::NotFound().finish()
+        } else if path.starts_with("/submodels/old/") {
+            HttpResponse::MethodNotAllowed().finish()
+        } else {
+            HttpResponse::NoContent().finish()
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_patches_element_values_and_falls_back_to_put() {
+        let requests: Requests = web::Data::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let configure = move |app: &mut web::ServiceConfig| {
+            app.app_data(recorded.clone()).default_service(web::to(record));
+        };
+        let new_server = MockServer::start(configure.clone());
+        let old_server = MockServer::start(configure);
+
+        let db = TempDb::open();
+        let rocksdb = db.rocksdb();
+        let submodel = json!({
+            "modelType": "Submodel",
+            "id": "urn:sm:1",
+            "submodelElements": [
+                { "modelType": "Property", "idShort": "BoardingStatus", "valueType": "xs:string", "value": "OFFBOARDED" },
+                { "modelType": "Property", "idShort": "LastUpdate", "valueType": "xs:dateTime", "value": "2024-01-01T00:00:00Z" }
+            ]
+        });
+        {
+            let db = rocksdb.lock().await;
+            for id_short in ["New", "Old"] {
+                db.put(format!("Test:{}", id_short), submodel.to_string()).unwrap();
+            }
+            let endpoints = json!({
+                "New": format!("{}submodels/new", new_server.url()),
+                "Old": format!("{}submodels/old", old_server.url())
+            });
+            db.put("Test:submodel_endpoints", endpoints.to_string()).unwrap();
+        }
+        let server = aas_server::for_dialect(ServerDialect::Basyx, aas_client(), new_server.url().to_string());
+        let patch = json!({ "BoardingStatus": "ONBOARDED" });
+        let push = |submodel: &'static str, push_mode: PushMode, patch: Value| {
+            let server = server.clone();
+            let rocksdb = rocksdb.clone();
+            async move { patch_submodel_server(server.as_ref(), rocksdb, "Test", submodel, push_mode, &patch).await }
+        };
+
+        // Without $value of the submodel the elements are patched, also in later pushes
+        push("New", PushMode::Submodel, patch.clone()).await.unwrap();
+        push("New", PushMode::Submodel, patch.clone()).await.unwrap();
+        // An element missing on the server is an error and no reason to PUT the submodel
+        let missing = push("New", PushMode::Element, json!({ "LastUpdate": "2024-06-01T00:00:00Z" })).await;
+        assert!(matches!(missing, Err(AppError::Upstream { status: 404, .. })));
+        push("New", PushMode::Element, patch.clone()).await.unwrap();
+        // Without any $value the submodel is PUT, the second push goes straight to PUT
+        push("Old", PushMode::Element, patch.clone()).await.unwrap();
+        push("Old", PushMode::Element, patch.clone()).await.unwrap();
+        // Which does not affect the other server
+        push("New", PushMode::Element, patch.clone()).await.unwrap();
+
+        let requests = requests.lock().unwrap().clone();
+        // Only the changed element is sent, in the value-only serialization
+        assert_eq!(requests[0].2, json!({ "BoardingStatus": "ONBOARDED" }));
+        assert_eq!(requests[1].2, json!("ONBOARDED"));
+        let requests: Vec<(String, String)> =
+            requests.iter().map(|(method, path, _)| (method.clone(), path.clone())).collect();
+        let expected = [
+            ("PATCH", "/submodels/new/$value"),
+            ("PATCH", "/submodels/new/submodel-elements/BoardingStatus/$value"),
+            ("PATCH", "/submodels/new/submodel-elements/BoardingStatus/$value"),
+            ("PATCH", "/submodels/new/submodel-elements/LastUpdate/$value"),
+            ("PATCH", "/submodels/new/submodel-elements/BoardingStatus/$value"),
+            ("PATCH", "/submodels/old/submodel-elements/BoardingStatus/$value"),
+            ("PUT", "/submodels/old"),
+            ("PUT", "/submodels/old"),
+            ("PATCH", "/submodels/new/submodel-elements/BoardingStatus/$value"),
+        ];
+        assert_eq!(requests, expected.map(|(method, path)| (method.to_string(), path.to_string())));
+    }
+
+    #[actix_web::test]
+    async fn test_resolves_submodels_by_semantic_id() {
+        let db = TempDb::open();
+        let rocksdb = db.rocksdb();
+        {
+            let db = rocksdb.lock().await;
+            let semantic_id = json!({ "type": "ExternalReference", "keys": [{ "type": "GlobalReference", "value": "urn:managed-device" }] });
+            db.put("Test:submodels_dictionary", json!({ "Device": "urn:sm:1", "Nameplate": "urn:sm:2" }).to_string()).unwrap();
+            db.put("Test:Device", json!({ "idShort": "Device", "semanticId": semantic_id }).to_string()).unwrap();
+            db.put("Test:Nameplate", json!({ "idShort": "Nameplate" }).to_string()).unwrap();
+        }
+        let mut config = Config { aas_id_short: "Test".to_string(), ..Default::default() };
+        config.submodels.managed_device.semantic_id = Some("urn:managed-device".to_string());
+        config.submodels.system_information.semantic_id = Some("urn:system-information".to_string());
+
+        // Before the index is built the idShorts are used
+        assert_eq!(resolve_submodel(rocksdb.clone(), &config, &config.submodels.managed_device).await, "ManagedDevice");
+
+        index_semantic_ids(rocksdb.clone(), "Test").await.unwrap();
+        assert_eq!(resolve_submodel(rocksdb.clone(), &config, &config.submodels.managed_device).await, "Device");
+        assert_eq!(
+            resolve_submodel(rocksdb.clone(), &config, &config.submodels.system_information).await,
+            "SystemInformation"
+        );
+        assert!(matches!(
+            find_by_semantic_id(rocksdb.clone(), "Test", "urn:system-information").await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,47 @@
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use super::{paged_or_plain, AasServerClient, ValueCapabilities, ValueSupport};
+use crate::config::ServerDialect;
+use crate::error::AppError;
+use crate::http_client::AasClient;
+
+/// Eclipse AASX Server: answers a PUT with 200 or 204, lists without paging in older
+/// versions and has no `$value` of whole submodels, so those pushes go element by element.
+pub struct AasxServer {
+    http: AasClient,
+    base_url: String,
+    value_capabilities: ValueCapabilities,
+}
+
+impl AasxServer {
+    pub fn new(http: AasClient, base_url: String) -> Self {
+        AasxServer { http, base_url, value_capabilities: ValueCapabilities::new(ValueSupport::Element) }
+    }
+}
+
+impl AasServerClient for AasxServer {
+    fn dialect(&self) -> ServerDialect {
+        ServerDialect::AasxServer
+    }
+
+    fn http(&self) -> &AasClient {
+        &self.http
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn value_capabilities(&self) -> &ValueCapabilities {
+        &self.value_capabilities
+    }
+
+    fn put_succeeded(&self, status: StatusCode) -> bool {
+        matches!(status, StatusCode::OK | StatusCode::NO_CONTENT)
+    }
+
+    fn page(&self, body: Value) -> Result<(Vec<Value>, Option<String>), AppError> {
+        paged_or_plain(body)
+    }
+}
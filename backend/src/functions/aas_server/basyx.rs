@@ -0,0 +1,35 @@
+use super::{AasServerClient, ValueCapabilities, ValueSupport};
+use crate::config::ServerDialect;
+use crate::http_client::AasClient;
+
+/// Eclipse BaSyx follows the AAS API as specified: 204 to a PUT, paged results and
+/// `$value` of submodels and elements.
+pub struct Basyx {
+    http: AasClient,
+    base_url: String,
+    value_capabilities: ValueCapabilities,
+}
+
+impl Basyx {
+    pub fn new(http: AasClient, base_url: String) -> Self {
+        Basyx { http, base_url, value_capabilities: ValueCapabilities::new(ValueSupport::Submodel) }
+    }
+}
+
+impl AasServerClient for Basyx {
+    fn dialect(&self) -> ServerDialect {
+        ServerDialect::Basyx
+    }
+
+    fn http(&self) -> &AasClient {
+        &self.http
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn value_capabilities(&self) -> &ValueCapabilities {
+        &self.value_capabilities
+    }
+}
@@ -0,0 +1,41 @@
+use super::{AasServerClient, ValueCapabilities, ValueSupport};
+use crate::config::ServerDialect;
+use crate::http_client::AasClient;
+
+/// FA³ST Service serves the AAS API below `/api/v3.0` (`/api/v3.1` in newer versions) and
+/// otherwise follows the specification.
+pub struct Faaast {
+    http: AasClient,
+    base_url: String,
+    value_capabilities: ValueCapabilities,
+}
+
+impl Faaast {
+    /// `base_url` is the server itself or already the API below it.
+    pub fn new(http: AasClient, base_url: String) -> Self {
+        let base_url = if base_url.contains("/api/v3.") || base_url.is_empty() {
+            base_url
+        } else {
+            format!("{}api/v3.0/", base_url)
+        };
+        Faaast { http, base_url, value_capabilities: ValueCapabilities::new(ValueSupport::Submodel) }
+    }
+}
+
+impl AasServerClient for Faaast {
+    fn dialect(&self) -> ServerDialect {
+        ServerDialect::Faaast
+    }
+
+    fn http(&self) -> &AasClient {
+        &self.http
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn value_capabilities(&self) -> &ValueCapabilities {
+        &self.value_capabilities
+    }
+}
@@ -0,0 +1,47 @@
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use super::{paged_or_plain, AasServerClient, ValueCapabilities, ValueSupport};
+use crate::config::ServerDialect;
+use crate::error::AppError;
+use crate::http_client::AasClient;
+
+/// Servers not recognized as one of the others: any successful status is accepted for a
+/// PUT and paged results may be plain arrays.
+pub struct Generic {
+    http: AasClient,
+    base_url: String,
+    value_capabilities: ValueCapabilities,
+}
+
+impl Generic {
+    pub fn new(http: AasClient, base_url: String) -> Self {
+        Generic { http, base_url, value_capabilities: ValueCapabilities::new(ValueSupport::Submodel) }
+    }
+}
+
+impl AasServerClient for Generic {
+    fn dialect(&self) -> ServerDialect {
+        ServerDialect::Generic
+    }
+
+    fn http(&self) -> &AasClient {
+        &self.http
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn value_capabilities(&self) -> &ValueCapabilities {
+        &self.value_capabilities
+    }
+
+    fn put_succeeded(&self, status: StatusCode) -> bool {
+        status.is_success()
+    }
+
+    fn page(&self, body: Value) -> Result<(Vec<Value>, Option<String>), AppError> {
+        paged_or_plain(body)
+    }
+}
@@ -0,0 +1,343 @@
+//! Requests to the AAS server. Eclipse BaSyx, Eclipse AASX Server and FA³ST all implement
+//! the AAS API v3 but differ in details: the status answered to a PUT, paged results,
+//! `$value` support and the path the API is served at. `AasServerClient` implements the
+//! requests following the specification, each dialect overrides where its server deviates.
+
+mod aasx_server;
+mod basyx;
+mod faaast;
+mod generic;
+
+use async_trait::async_trait;
+use reqwest::header::SERVER;
+use reqwest::{Response, StatusCode, Url};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::discovery::encode_id;
+use crate::config::{Config, ServerDialect};
+use crate::error::AppError;
+use crate::http_client::AasClient;
+use crate::metrics;
+
+pub use aasx_server::AasxServer;
+pub use basyx::Basyx;
+pub use faaast::Faaast;
+pub use generic::Generic;
+
+/// `$value` PATCHes a server accepts, from the least to the most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ValueSupport {
+    /// None, submodels are pushed with a PUT of the whole document.
+    None,
+    /// Only `.../submodel-elements/{idShortPath}/$value`.
+    Element,
+    /// Also `.../$value` of the whole submodel.
+    Submodel,
+}
+
+/// `$value` support of the servers a client talks to: at first that of the dialect, lowered
+/// for a server once it rejects a PATCH. Submodels found in a registry may be served by
+/// other servers than `AASX_SERVER`, so it is kept per origin of the submodel URLs.
+pub struct ValueCapabilities {
+    dialect: ValueSupport,
+    servers: std::sync::Mutex<HashMap<String, ValueSupport>>,
+}
+
+impl ValueCapabilities {
+    pub fn new(dialect: ValueSupport) -> Self {
+        ValueCapabilities { dialect, servers: std::sync::Mutex::new(HashMap::new()) }
+    }
+
+    /// Support of the server of `url`.
+    pub fn get(&self, url: &str) -> ValueSupport {
+        self.servers.lock().unwrap().get(&origin(url)).copied().unwrap_or(self.dialect)
+    }
+
+    /// Lowers the support of the server of `url` to at most `support`.
+    pub fn lower(&self, url: &str, support: ValueSupport) {
+        let support = support.min(self.get(url));
+        self.servers.lock().unwrap().insert(origin(url), support);
+    }
+}
+
+/// Scheme, host and port of `url`.
+fn origin(url: &str) -> String {
+    Url::parse(url).map(|url| url.origin().ascii_serialization()).unwrap_or_else(|_| url.to_string())
+}
+
+#[async_trait]
+pub trait AasServerClient: Send + Sync {
+    fn dialect(&self) -> ServerDialect;
+
+    /// Client with the pooled connections and the credentials of the server.
+    fn http(&self) -> &AasClient;
+
+    /// Base URL of the API, ending with '/', e.g. `http://server:8081/api/v3.0/`.
+    fn base_url(&self) -> &str;
+
+    fn shell_url(&self, aas_id: &str) -> String {
+        format!("{}shells/{}", self.base_url(), encode_id(aas_id))
+    }
+
+    fn submodel_url(&self, submodel_id: &str) -> String {
+        format!("{}submodels/{}", self.base_url(), encode_id(submodel_id))
+    }
+
+    /// Whether a PUT answered with `status` stored the submodel. The AAS API answers 204.
+    fn put_succeeded(&self, status: StatusCode) -> bool {
+        status == StatusCode::NO_CONTENT
+    }
+
+    /// `$value` support, see `ValueCapabilities`.
+    fn value_capabilities(&self) -> &ValueCapabilities;
+
+    /// Items of a page of a paged result and the cursor of the next page.
+    fn page(&self, body: Value) -> Result<(Vec<Value>, Option<String>), AppError> {
+        paged_result(body)
+    }
+
+    async fn get_json(&self, url: &str, operation: &str) -> Result<Value, AppError> {
+        self.http().get_json(resource_url(url), operation).await
+    }
+
+    async fn get_shell(&self, shell_url: &str) -> Result<Value, AppError> {
+        self.get_json(shell_url, "get_shell").await
+    }
+
+    async fn get_submodel(&self, submodel_url: &str) -> Result<Value, AppError> {
+        match self.get_json(submodel_url, "get_submodel").await? {
+            submodel @ Value::Object(_) => Ok(submodel),
+            _ => Err(AppError::upstream(StatusCode::OK, format!("Submodel {} is not a JSON object", submodel_url))),
+        }
+    }
+
+    /// References of the submodels of a shell, from the shell itself or, for servers that
+    /// leave them out, from `/submodel-refs` page by page.
+    async fn get_submodel_refs(&self, shell_url: &str, shell: &Value) -> Result<Vec<Value>, AppError> {
+        if let Some(references) = shell["submodels"].as_array() {
+            return Ok(references.clone());
+        }
+        let url = format!("{}/submodel-refs", resource_url(shell_url));
+        let mut references = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page_url = match &cursor {
+                Some(cursor) => Url::parse_with_params(&url, [("cursor", cursor)])
+                    .map_err(|e| AppError::Internal(format!("Invalid URL {}: {}", url, e)))?
+                    .to_string(),
+                None => url.clone(),
+            };
+            let (items, next) = self.page(self.get_json(&page_url, "get_submodel_refs").await?)?;
+            references.extend(items);
+            match next {
+                Some(next) if !next.is_empty() => cursor = Some(next),
+                _ => return Ok(references),
+            }
+        }
+    }
+
+    async fn put_submodel(&self, submodel_url: &str, submodel: &Value) -> Result<(), AppError> {
+        let http = self.http();
+        let response = http.send(http.put(resource_url(submodel_url)).json(submodel)).await;
+        metrics::record_server_response("put_submodel", response.as_ref().ok().map(|r| r.status()));
+        let response = response?;
+
+        let status = response.status();
+        if self.put_succeeded(status) {
+            return Ok(());
+        }
+        Err(AppError::upstream(status, response.text().await.unwrap_or_else(|_| "Unknown error".into())))
+    }
+
+    /// PATCHes a `$value` endpoint, of the submodel or of one of its elements.
+    async fn patch_value(&self, value_url: &str, operation: &str, value: &Value) -> Result<(), AppError> {
+        let http = self.http();
+        let response = http.send(http.patch(value_url).json(value)).await;
+        metrics::record_server_response(operation, response.as_ref().ok().map(|r| r.status()));
+        let response = response?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(AppError::upstream(status, format!("Error patching {}: {}", value_url, body)))
+    }
+
+    /// The thumbnail of the shell, `None` if the server has none.
+    async fn get_thumbnail(&self, shell_url: &str) -> Result<Option<Response>, AppError> {
+        let http = self.http();
+        let url = format!("{}/asset-information/thumbnail", resource_url(shell_url));
+        let response = http.send(http.get(&url)).await;
+        metrics::record_server_response("get_thumbnail", response.as_ref().ok().map(|r| r.status()));
+        let response = response?;
+        Ok(response.status().is_success().then_some(response))
+    }
+}
+
+/// Resources are addressed without a trailing slash, which several servers answer with 404.
+fn resource_url(url: &str) -> &str {
+    url.trim_end_matches('/')
+}
+
+/// Items and cursor of a `PagedResult`: `{"result": [...], "paging_metadata": {"cursor"}}`.
+fn paged_result(body: Value) -> Result<(Vec<Value>, Option<String>), AppError> {
+    let cursor = body["paging_metadata"]["cursor"].as_str().map(str::to_string);
+    match body {
+        Value::Object(mut page) => match page.remove("result") {
+            Some(Value::Array(items)) => Ok((items, cursor)),
+            _ => Err(AppError::upstream(StatusCode::OK, "Paged result without a result array")),
+        },
+        _ => Err(AppError::upstream(StatusCode::OK, "Expected a paged result")),
+    }
+}
+
+/// A paged result, or the plain array older servers answer.
+fn paged_or_plain(body: Value) -> Result<(Vec<Value>, Option<String>), AppError> {
+    match body {
+        Value::Array(items) => Ok((items, None)),
+        body => paged_result(body),
+    }
+}
+
+/// Client for `base_url` in `dialect`, the generic one for `auto`.
+pub fn for_dialect(dialect: ServerDialect, http: AasClient, base_url: String) -> Arc<dyn AasServerClient> {
+    match dialect {
+        ServerDialect::Basyx => Arc::new(Basyx::new(http, base_url)),
+        ServerDialect::AasxServer => Arc::new(AasxServer::new(http, base_url)),
+        ServerDialect::Faaast => Arc::new(Faaast::new(http, base_url)),
+        ServerDialect::Generic | ServerDialect::Auto => Arc::new(Generic::new(http, base_url)),
+    }
+}
+
+/// Client for `AASX_SERVER` in the configured dialect, or the one detected from the server.
+pub async fn connect(http: &AasClient, config: &Config) -> Result<Arc<dyn AasServerClient>, AppError> {
+    let base_url = config.aasx_server.clone();
+    let (dialect, base_url) = match config.aas_server_dialect {
+        ServerDialect::Auto if !base_url.is_empty() => detect(http, base_url).await?,
+        dialect => (dialect, base_url),
+    };
+    Ok(for_dialect(dialect, http.clone(), base_url))
+}
+
+/// Finds the `/description` of the server at `base_url` or below `api/v3.1/` or
+/// `api/v3.0/`. The AASX Server runs on Kestrel. FA³ST serves the API below such a path,
+/// which tells nothing if `AASX_SERVER` already names it. BaSyx runs on Spring Boot, which
+/// answers unknown paths with its own error document. Anything else, including a server
+/// without `/description`, is generic.
+async fn detect(http: &AasClient, base_url: String) -> Result<(ServerDialect, String), AppError> {
+    for prefix in ["", "api/v3.1/", "api/v3.0/"] {
+        let api_url = format!("{}{}", base_url, prefix);
+        let response = http.send(http.get(&format!("{}description", api_url))).await;
+        metrics::record_server_response("get_description", response.as_ref().ok().map(|r| r.status()));
+        let response = response?;
+        if !response.status().is_success() {
+            continue;
+        }
+
+        let server = response.headers().get(SERVER).and_then(|server| server.to_str().ok()).unwrap_or_default();
+        let dialect = if server.contains("Kestrel") {
+            ServerDialect::AasxServer
+        } else if !prefix.is_empty() {
+            ServerDialect::Faaast
+        } else if is_spring_boot(http, &api_url).await? {
+            ServerDialect::Basyx
+        } else {
+            ServerDialect::Generic
+        };
+        return Ok((dialect, api_url));
+    }
+    Ok((ServerDialect::Generic, base_url))
+}
+
+/// Whether the server answers an unknown path with the error document of Spring Boot,
+/// `{"timestamp", "status", "error", "path"}`.
+async fn is_spring_boot(http: &AasClient, api_url: &str) -> Result<bool, AppError> {
+    let response = http.send(http.get(&format!("{}dialect-probe", api_url))).await;
+    metrics::record_server_response("get_dialect_probe", response.as_ref().ok().map(|r| r.status()));
+    let response = response?;
+    if response.status() != StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+    let body: Value = response.json().await.unwrap_or_default();
+    Ok(["timestamp", "status", "error", "path"].iter().all(|field| body.get(field).is_some()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{aas_client, MockServer};
+    use actix_web::{web, HttpResponse};
+    use serde_json::json;
+
+    #[actix_web::test]
+    async fn test_detects_api_path_and_reads_paged_submodel_refs() {
+        let mock = MockServer::start(|app| {
+            app.route("/api/v3.0/description", web::get().to(|| async {
+                    HttpResponse::Ok().json(json!({ "profiles": [] }))
+                }))
+                .route("/api/v3.0/shells/{id}/submodel-refs", web::get().to(|query: web::Query<Vec<(String, String)>>| async move {
+                    let reference = |id: &str| json!({ "type": "ModelReference", "keys": [{ "type": "Submodel", "value": id }] });
+                    match query.first() {
+                        None => HttpResponse::Ok().json(json!({ "paging_metadata": { "cursor": "a b" }, "result": [reference("urn:sm:1")] })),
+                        Some((_, cursor)) => {
+                            assert_eq!(cursor, "a b");
+                            HttpResponse::Ok().json(json!({ "paging_metadata": {}, "result": [reference("urn:sm:2")] }))
+                        }
+                    }
+                }));
+        });
+        let base = mock.url().to_string();
+
+        let http = aas_client();
+        let config = Config { aasx_server: base.clone(), ..Default::default() };
+        let server = connect(&http, &config).await.unwrap();
+        assert_eq!(server.dialect(), ServerDialect::Faaast);
+        assert_eq!(server.base_url(), format!("{}api/v3.0/", base));
+
+        let shell_url = format!("{}/", server.shell_url("urn:aas:1"));
+        let references = server.get_submodel_refs(&shell_url, &json!({ "id": "urn:aas:1" })).await.unwrap();
+        let ids: Vec<&str> = references.iter().filter_map(|reference| reference["keys"][0]["value"].as_str()).collect();
+        assert_eq!(ids, ["urn:sm:1", "urn:sm:2"]);
+    }
+
+    #[actix_web::test]
+    async fn test_detects_basyx_and_aasx_server() {
+        let description = || async { HttpResponse::Ok().json(json!({ "profiles": [] })) };
+        let basyx = MockServer::start(move |app| {
+            app.route("/description", web::get().to(description)).default_service(web::to(|| async {
+                HttpResponse::NotFound().json(json!({
+                    "timestamp": "2024-01-01T00:00:00.000+00:00", "status": 404, "error": "Not Found", "path": "/dialect-probe"
+                }))
+            }));
+        });
+        let aasx_server = MockServer::start(|app| {
+            app.route("/description", web::get().to(|| async {
+                HttpResponse::Ok().insert_header(("Server", "Kestrel")).json(json!({ "profiles": [] }))
+            }));
+        });
+        let api_path = MockServer::start(move |app| {
+            app.route("/api/v3.0/description", web::get().to(description));
+        });
+
+        let http = aas_client();
+        let detected = |aasx_server: String| {
+            let http = http.clone();
+            async move {
+                let config = Config { aasx_server, ..Default::default() };
+                let server = connect(&http, &config).await.unwrap();
+                (server.dialect(), server.base_url().to_string())
+            }
+        };
+        assert_eq!(detected(basyx.url().to_string()).await, (ServerDialect::Basyx, basyx.url().to_string()));
+        assert_eq!(
+            detected(aasx_server.url().to_string()).await,
+            (ServerDialect::AasxServer, aasx_server.url().to_string())
+        );
+        // The API path configured in AASX_SERVER is no sign of FA³ST and not added twice
+        let configured = format!("{}api/v3.0/", api_path.url());
+        assert_eq!(detected(configured.clone()).await, (ServerDialect::Generic, configured));
+    }
+}
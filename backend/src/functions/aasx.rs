@@ -0,0 +1,589 @@
+//! AASX packages (AAS Part 5): onboarding from a package where there is no AAS server and
+//! exporting the local state as one.
+
+use rocksdb::DB;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeSet;
+use std::io::{Cursor, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::aas_interfaces::{self, aas_find_one};
+use super::concept_descriptions;
+use super::id_short_path;
+use super::onboarding::extract_submodels_id;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::metrics;
+
+const ORIGIN_RELATIONSHIP: &str = "http://admin-shell.io/aasx/relationships/aasx-origin";
+const SPEC_RELATIONSHIP: &str = "http://admin-shell.io/aasx/relationships/aas-spec";
+const SUPPLEMENTARY_RELATIONSHIP: &str = "http://admin-shell.io/aasx/relationships/aas-suppl";
+const THUMBNAIL_RELATIONSHIP: &str = "http://schemas.openxmlformats.org/package/2006/relationships/metadata/thumbnail";
+
+/// Supplementary files of an imported package, stored by their path in the package.
+const FILES_DIR: &str = "./static/aasx_files/";
+const IMAGES_DIR: &str = "./static/asset_images/";
+/// Image types a thumbnail is stored as, in the order `stored_thumbnail` looks for them.
+const THUMBNAIL_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "svg", "gif"];
+
+/// Parts of an AASX package.
+#[derive(Debug, Default)]
+pub struct Package {
+    /// The AAS environment with `assetAdministrationShells`, `submodels` and `conceptDescriptions`.
+    pub environment: Value,
+    /// Path in the package and content of the thumbnail.
+    pub thumbnail: Option<(String, Vec<u8>)>,
+    /// Supplementary files by their path in the package, e.g. `/aasx/files/manual.pdf`.
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+/// Reads a package, with the environment given as JSON or XML.
+pub fn read_package(bytes: &[u8]) -> Result<Package, AppError> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(|e| invalid_package(e.to_string()))?;
+
+    let root = relationships(&mut archive, "/")?;
+    let origin = target(&root, ORIGIN_RELATIONSHIP).ok_or_else(|| invalid_package("no aasx-origin".to_string()))?;
+    let spec = target(&relationships(&mut archive, &origin)?, SPEC_RELATIONSHIP)
+        .ok_or_else(|| invalid_package("no aas-spec".to_string()))?;
+
+    let content = read_part(&mut archive, &spec)?;
+    let environment = if spec.to_lowercase().ends_with(".xml") {
+        let xml = String::from_utf8(content).map_err(|e| invalid_package(format!("{}: {}", spec, e)))?;
+        xml_environment(&xml)?
+    } else {
+        serde_json::from_slice(&content).map_err(|e| invalid_package(format!("{}: {}", spec, e)))?
+    };
+
+    let thumbnail = match target(&root, THUMBNAIL_RELATIONSHIP) {
+        Some(path) => Some((path.clone(), read_part(&mut archive, &path)?)),
+        None => None,
+    };
+    let mut files = Vec::new();
+    for (kind, path) in relationships(&mut archive, &spec)? {
+        if kind == SUPPLEMENTARY_RELATIONSHIP {
+            files.push((path.clone(), read_part(&mut archive, &path)?));
+        }
+    }
+
+    Ok(Package { environment, thumbnail, files })
+}
+
+/// Writes a package with a JSON environment.
+pub fn write_package(package: &Package) -> Result<Vec<u8>, AppError> {
+    let spec = "/aasx/environment.json";
+    let environment = serde_json::to_vec_pretty(&package.environment).map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let mut root = vec![(ORIGIN_RELATIONSHIP, "/aasx/aasx-origin")];
+    if let Some((path, _)) = &package.thumbnail {
+        root.push((THUMBNAIL_RELATIONSHIP, path));
+    }
+    let supplementary: Vec<_> = package.files.iter().map(|(path, _)| (SUPPLEMENTARY_RELATIONSHIP, path.as_str())).collect();
+
+    let mut parts: Vec<(&str, Vec<u8>)> = vec![
+        ("/_rels/.rels", relationships_xml(&root).into_bytes()),
+        ("/aasx/aasx-origin", b"Intentionally empty.".to_vec()),
+        ("/aasx/_rels/aasx-origin.rels", relationships_xml(&[(SPEC_RELATIONSHIP, spec)]).into_bytes()),
+        (spec, environment),
+    ];
+    if !supplementary.is_empty() {
+        parts.push(("/aasx/_rels/environment.json.rels", relationships_xml(&supplementary).into_bytes()));
+    }
+    parts.extend(package.thumbnail.iter().chain(&package.files).map(|(path, content)| (path.as_str(), content.clone())));
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let content_types = content_types_xml(parts.iter().map(|(path, _)| *path));
+    for (path, content) in std::iter::once(("/[Content_Types].xml", content_types.into_bytes())).chain(parts) {
+        writer.start_file(path.trim_start_matches('/'), options).map_err(|e| AppError::Internal(e.to_string()))?;
+        writer.write_all(&content).map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+    let cursor = writer.finish().map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(cursor.into_inner())
+}
+
+/// Seeds RocksDB, the submodels dictionary, the thumbnail in `static/asset_images` and the
+/// supplementary files from the package at `aasx.import_path`, as onboarding from the AAS
+/// server does.
+pub async fn import_package(config: &Config, rocksdb: Arc<Mutex<DB>>) -> Result<(), AppError> {
+    let path = config.aasx.import_path.as_deref().unwrap_or_default();
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| AppError::NotFound(format!("Failed to read AASX package {}: {}", path, e)))?;
+    let package = read_package(&bytes)?;
+    let aas_id_short = config.aas_id_short.as_str();
+    let shell = select_shell(&package.environment, config)?;
+
+    let submodels = package.environment["submodels"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let mut dictionary = Map::new();
+    {
+        let db = rocksdb.lock().await;
+        metrics::record_rocksdb_write();
+        db.put(aas_id_short, serde_json::to_vec(shell).map_err(|e| AppError::Internal(e.to_string()))?)?;
+
+        for submodel_id in extract_submodels_id(shell)? {
+            let submodel = submodels.iter().find(|submodel| submodel["id"] == submodel_id.as_str());
+            let Some((submodel, id_short)) = submodel.and_then(|submodel| Some((submodel, submodel["idShort"].as_str()?))) else {
+                eprintln!("Submodel {} is not in the AASX package", submodel_id);
+                continue;
+            };
+            metrics::record_rocksdb_write();
+            db.put(
+                format!("{}:{}", aas_id_short, id_short),
+                serde_json::to_vec(submodel).map_err(|e| AppError::Internal(e.to_string()))?,
+            )?;
+            dictionary.insert(id_short.to_string(), Value::String(submodel_id));
+        }
+
+        metrics::record_rocksdb_write();
+        db.put(
+            format!("{}:submodels_dictionary", aas_id_short),
+            serde_json::to_vec(&dictionary).map_err(|e| AppError::Internal(e.to_string()))?,
+        )?;
+        // Submodels are read from AASX_SERVER if there is one, not from earlier endpoints
+        metrics::record_rocksdb_write();
+        db.put(format!("{}:submodel_endpoints", aas_id_short), b"{}")?;
+    }
+
+    aas_interfaces::index_semantic_ids(rocksdb.clone(), aas_id_short).await?;
+
+    let concept_descriptions = package.environment["conceptDescriptions"].as_array().map(Vec::as_slice).unwrap_or_default();
+    concept_descriptions::store(rocksdb, aas_id_short, concept_descriptions).await?;
+
+    if let Some((path, image)) = &package.thumbnail {
+        match extension(path).filter(|extension| THUMBNAIL_EXTENSIONS.contains(&extension.as_str())) {
+            Some(extension) => {
+                // A thumbnail of another type from an earlier import would be found first
+                for other in THUMBNAIL_EXTENSIONS {
+                    let _ = tokio::fs::remove_file(thumbnail_path(aas_id_short, other)).await;
+                }
+                write_file(&thumbnail_path(aas_id_short, &extension), image).await?;
+            }
+            None => eprintln!("Skipping thumbnail {} that is not a known image type", path),
+        }
+    }
+    for (path, content) in &package.files {
+        match local_path(path) {
+            Some(local_path) => write_file(&local_path, content).await?,
+            None => eprintln!("Skipping supplementary file {} outside the package", path),
+        }
+    }
+
+    println!("Imported shell {} with {} submodels from {}", shell["id"], dictionary.len(), path);
+    Ok(())
+}
+
+/// The cached shell, submodels and ConceptDescriptions as a package, with the thumbnail and
+/// the supplementary files their `File` elements point to.
+pub async fn export_package(config: &Config, rocksdb: Arc<Mutex<DB>>) -> Result<Vec<u8>, AppError> {
+    let aas_id_short = config.aas_id_short.as_str();
+    let shell = aas_find_one(aas_id_short.to_string(), rocksdb.clone()).await?;
+    let dictionary = aas_find_one(format!("{}:submodels_dictionary", aas_id_short), rocksdb.clone()).await?;
+
+    let mut submodels = Vec::new();
+    for submodel_id_short in dictionary.as_object().into_iter().flat_map(|dictionary| dictionary.keys()) {
+        submodels.push(aas_find_one(format!("{}:{}", aas_id_short, submodel_id_short), rocksdb.clone()).await?);
+    }
+
+    let mut file_paths = BTreeSet::new();
+    for submodel in &submodels {
+        collect_file_paths(submodel["submodelElements"].as_array().map(Vec::as_slice).unwrap_or_default(), &mut file_paths);
+    }
+    let mut files = Vec::new();
+    for path in file_paths {
+        if let Some(content) = local_path(&path).and_then(|local_path| std::fs::read(local_path).ok()) {
+            files.push((path, content));
+        }
+    }
+
+    let thumbnail = stored_thumbnail(aas_id_short).await.map(|(image_type, image)| {
+        // The default thumbnail path, unless the stored image is of another type
+        let path = shell["assetInformation"]["defaultThumbnail"]["path"]
+            .as_str()
+            .filter(|path| path.starts_with('/') && extension(path).as_deref() == Some(image_type))
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("/aasx/thumbnail.{}", image_type));
+        (path, image)
+    });
+
+    let concept_descriptions = concept_descriptions::cached(rocksdb, aas_id_short).await?;
+    let environment = json!({
+        "assetAdministrationShells": [shell],
+        "submodels": submodels,
+        "conceptDescriptions": concept_descriptions.into_iter().map(|(_, concept_description)| concept_description).collect::<Vec<_>>(),
+    });
+    write_package(&Package { environment, thumbnail, files })
+}
+
+/// Extension and content of the thumbnail stored by `import_package`, if there is one.
+pub async fn stored_thumbnail(aas_id_short: &str) -> Option<(&'static str, Vec<u8>)> {
+    for extension in THUMBNAIL_EXTENSIONS {
+        if let Ok(image) = tokio::fs::read(thumbnail_path(aas_id_short, extension)).await {
+            return Some((extension, image));
+        }
+    }
+    None
+}
+
+fn thumbnail_path(aas_id_short: &str, extension: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}.{}", IMAGES_DIR, aas_id_short, extension))
+}
+
+/// The lowercased extension of the last segment of a part name.
+fn extension(path: &str) -> Option<String> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.rsplit_once('.').map(|(_, extension)| extension.to_lowercase())
+}
+
+/// The content type of a part or file with the extension.
+pub fn content_type(extension: &str) -> &'static str {
+    match extension {
+        "rels" => "application/vnd.openxmlformats-package.relationships+xml",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "svg" => "image/svg+xml",
+        "gif" => "image/gif",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The shell with `AAS_IDENTIFIER`, or the one of `AAS_ASSET_IDENTIFIER`, or the only one.
+fn select_shell<'a>(environment: &'a Value, config: &Config) -> Result<&'a Value, AppError> {
+    let shells = environment["assetAdministrationShells"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let asset_identifier = config.aas_asset_identifier.as_deref();
+    let shell = if !config.aas_identifier.is_empty() {
+        shells.iter().find(|shell| shell["id"] == config.aas_identifier.as_str())
+    } else if let Some(asset_identifier) = asset_identifier {
+        shells.iter().find(|shell| shell["assetInformation"]["globalAssetId"] == asset_identifier)
+    } else {
+        shells.first().filter(|_| shells.len() == 1)
+    };
+    let shell = shell.ok_or_else(|| {
+        let wanted = if config.aas_identifier.is_empty() { asset_identifier.unwrap_or("a single shell") } else { &config.aas_identifier };
+        AppError::Validation(format!("The AASX package has no shell for {}", wanted))
+    })?;
+
+    // Same check as onboarding from the AAS server
+    if let Some(asset_identifier) = asset_identifier {
+        let global_asset_id = shell["assetInformation"]["globalAssetId"].as_str().unwrap_or_default();
+        if global_asset_id != asset_identifier {
+            return Err(AppError::Validation(format!(
+                "Shell {} describes asset '{}' but AAS_ASSET_IDENTIFIER is '{}'",
+                shell["id"], global_asset_id, asset_identifier
+            )));
+        }
+    }
+    Ok(shell)
+}
+
+/// Package paths of the `File` elements below `elements`.
+fn collect_file_paths(elements: &[Value], paths: &mut BTreeSet<String>) {
+    for element in elements {
+        if element["modelType"] == "File" {
+            if let Some(path) = element["value"].as_str().filter(|path| path.starts_with('/')) {
+                paths.insert(path.to_string());
+            }
+        }
+        if let Some(children) = id_short_path::children(element) {
+            collect_file_paths(children, paths);
+        }
+    }
+}
+
+/// Local copy of the supplementary file at `path` in the package, `None` for paths leaving
+/// the package.
+fn local_path(path: &str) -> Option<PathBuf> {
+    let relative = Path::new(path.trim_start_matches('/'));
+    let inside = relative.components().all(|component| matches!(component, Component::Normal(_)));
+    (inside && !path.is_empty()).then(|| Path::new(FILES_DIR).join(relative))
+}
+
+async fn write_file(path: &Path, content: &[u8]) -> Result<(), AppError> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to create {}: {}", dir.display(), e)))?;
+    }
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+fn invalid_package(message: String) -> AppError {
+    AppError::Validation(format!("Invalid AASX package: {}", message))
+}
+
+fn read_part(archive: &mut ZipArchive<Cursor<&[u8]>>, path: &str) -> Result<Vec<u8>, AppError> {
+    let mut part = archive
+        .by_name(path.trim_start_matches('/'))
+        .map_err(|e| invalid_package(format!("{}: {}", path, e)))?;
+    let mut content = Vec::new();
+    part.read_to_end(&mut content).map_err(|e| invalid_package(format!("{}: {}", path, e)))?;
+    Ok(content)
+}
+
+/// Types and absolute targets of the relationships of the part at `source`, `/` for the
+/// package itself.
+fn relationships(archive: &mut ZipArchive<Cursor<&[u8]>>, source: &str) -> Result<Vec<(String, String)>, AppError> {
+    let (dir, name) = source.rsplit_once('/').unwrap_or(("", source));
+    let rels_path = format!("{}/_rels/{}.rels", dir, name);
+    if archive.by_name(rels_path.trim_start_matches('/')).is_err() {
+        return Ok(Vec::new());
+    }
+    let xml = String::from_utf8(read_part(archive, &rels_path)?).map_err(|e| invalid_package(e.to_string()))?;
+    let document = roxmltree::Document::parse(&xml).map_err(|e| invalid_package(format!("{}: {}", rels_path, e)))?;
+
+    Ok(document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "Relationship" && node.attribute("TargetMode") != Some("External"))
+        .filter_map(|node| Some((node.attribute("Type")?.to_string(), resolve(dir, node.attribute("Target")?))))
+        .collect())
+}
+
+fn target(relationships: &[(String, String)], kind: &str) -> Option<String> {
+    relationships.iter().find(|(found, _)| found == kind).map(|(_, target)| target.clone())
+}
+
+/// Absolute path of a relationship target, relative ones are resolved against `dir`.
+fn resolve(dir: &str, target: &str) -> String {
+    let joined = if target.starts_with('/') { target.to_string() } else { format!("{}/{}", dir, target) };
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in joined.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+fn relationships_xml(relationships: &[(&str, &str)]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\n",
+    );
+    for (index, (kind, target)) in relationships.iter().enumerate() {
+        xml.push_str(&format!(
+            "  <Relationship Type=\"{}\" Target=\"{}\" Id=\"R{}\" />\n",
+            kind,
+            escape_xml(target),
+            index + 1
+        ));
+    }
+    xml.push_str("</Relationships>\n");
+    xml
+}
+
+fn content_types_xml<'a>(paths: impl Iterator<Item = &'a str>) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\n",
+    );
+    let mut extensions = BTreeSet::new();
+    for path in paths {
+        match extension(path) {
+            Some(extension) => {
+                extensions.insert(extension);
+            }
+            None => xml.push_str(&format!("  <Override PartName=\"{}\" ContentType=\"text/plain\" />\n", escape_xml(path))),
+        }
+    }
+    for extension in extensions {
+        xml.push_str(&format!(
+            "  <Default Extension=\"{}\" ContentType=\"{}\" />\n",
+            escape_xml(&extension),
+            content_type(&extension)
+        ));
+    }
+    xml.push_str("</Types>\n");
+    xml
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Properties that hold a list in the XML serialization. `value` is one for collections,
+/// lists and multi-language properties.
+const XML_LISTS: &[&str] = &[
+    "assetAdministrationShells", "submodels", "conceptDescriptions", "extensions", "displayName", "description",
+    "embeddedDataSpecifications", "supplementalSemanticIds", "qualifiers", "specificAssetIds", "submodelElements",
+    "keys", "refersTo", "isCaseOf", "statements", "annotations", "inputVariables", "outputVariables",
+    "inoutputVariables", "preferredName", "shortName", "definition", "valueReferencePairs",
+];
+
+/// Classes that carry a `modelType` in the JSON serialization.
+const XML_CLASSES: &[&str] = &[
+    "assetAdministrationShell", "submodel", "conceptDescription", "property", "multiLanguageProperty", "range",
+    "file", "blob", "referenceElement", "relationshipElement", "annotatedRelationshipElement",
+    "submodelElementCollection", "submodelElementList", "entity", "basicEventElement", "operation", "capability",
+    "dataSpecificationIec61360",
+];
+
+/// Converts an XML environment (`https://admin-shell.io/aas/3/0`) to its JSON serialization.
+pub fn xml_environment(xml: &str) -> Result<Value, AppError> {
+    let document = roxmltree::Document::parse(xml).map_err(|e| invalid_package(format!("environment: {}", e)))?;
+    Ok(xml_object(document.root_element()))
+}
+
+fn xml_object(node: roxmltree::Node) -> Value {
+    let owner = node.tag_name().name();
+    let mut object = Map::new();
+    if XML_CLASSES.contains(&owner) {
+        let mut chars = owner.chars();
+        let model_type: String = chars.next().into_iter().flat_map(char::to_uppercase).chain(chars).collect();
+        object.insert("modelType".to_string(), json!(model_type));
+    }
+
+    for child in node.children().filter(roxmltree::Node::is_element) {
+        let name = child.tag_name().name();
+        let mut elements = child.children().filter(roxmltree::Node::is_element).peekable();
+        let holds_elements = matches!(owner, "multiLanguageProperty" | "submodelElementCollection" | "submodelElementList");
+        let value = if XML_LISTS.contains(&name) || (name == "value" && holds_elements) {
+            Value::Array(elements.map(xml_object).collect())
+        } else if elements.peek().is_none() {
+            let text = child.text().unwrap_or_default().trim();
+            if name == "orderRelevant" { json!(text == "true") } else { json!(text) }
+        } else if name == "dataSpecificationContent" || (name == "value" && owner == "operationVariable") {
+            // A single element that is not wrapped in JSON
+            elements.next().map(xml_object).unwrap_or_default()
+        } else {
+            xml_object(child)
+        };
+        object.insert(name.to_string(), value);
+    }
+    Value::Object(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDb;
+
+    #[test]
+    fn test_package_round_trip_and_xml_environment() {
+        let environment = json!({
+            "assetAdministrationShells": [{ "modelType": "AssetAdministrationShell", "id": "urn:aas:1" }],
+            "submodels": [],
+            "conceptDescriptions": []
+        });
+        let package = Package {
+            environment: environment.clone(),
+            thumbnail: Some(("/aasx/thumbnail.png".to_string(), b"png".to_vec())),
+            files: vec![("/aasx/files/manual.pdf".to_string(), b"pdf".to_vec())],
+        };
+        let read = read_package(&write_package(&package).unwrap()).unwrap();
+        assert_eq!(read.environment, environment);
+        assert_eq!(read.thumbnail, package.thumbnail);
+        assert_eq!(read.files, package.files);
+        assert_eq!(resolve("/aasx/xml", "../files/a.pdf"), "/aasx/files/a.pdf");
+        assert_eq!(local_path("/../etc/passwd"), None);
+
+        let xml = r#"<environment xmlns="https://admin-shell.io/aas/3/0">
+          <submodels><submodel>
+            <idShort>Nameplate</idShort><id>urn:sm:1</id>
+            <submodelElements>
+              <multiLanguageProperty><idShort>ManufacturerName</idShort>
+                <value><langStringTextType><language>en</language><text>ACME</text></langStringTextType></value>
+              </multiLanguageProperty>
+              <submodelElementList><idShort>Ports</idShort><orderRelevant>true</orderRelevant>
+                <value><property><valueType>xs:int</valueType><value>80</value></property></value>
+              </submodelElementList>
+              <referenceElement><idShort>Link</idShort>
+                <value><type>ExternalReference</type><keys><key><type>GlobalReference</type><value>urn:x</value></key></keys></value>
+              </referenceElement>
+            </submodelElements>
+          </submodel></submodels>
+        </environment>"#;
+        let submodel = &xml_environment(xml).unwrap()["submodels"][0];
+        assert_eq!(submodel["modelType"], "Submodel");
+        let elements = &submodel["submodelElements"];
+        assert_eq!(elements[0]["value"], json!([{ "language": "en", "text": "ACME" }]));
+        assert_eq!(elements[1]["orderRelevant"], true);
+        assert_eq!(elements[1]["value"][0], json!({ "modelType": "Property", "valueType": "xs:int", "value": "80" }));
+        assert_eq!(elements[2]["value"]["keys"][0]["value"], "urn:x");
+    }
+
+    #[actix_web::test]
+    async fn test_import_package_seeds_rocksdb() {
+        let reference = |id: &str| json!({ "type": "ModelReference", "keys": [{ "type": "Submodel", "value": id }] });
+        let shell = json!({
+            "modelType": "AssetAdministrationShell",
+            "id": "urn:aas:1",
+            "submodels": [reference("urn:sm:1"), reference("urn:sm:missing")]
+        });
+        let submodel = json!({ "modelType": "Submodel", "id": "urn:sm:1", "idShort": "Network", "submodelElements": [] });
+        let concept_description = json!({ "modelType": "ConceptDescription", "id": "urn:cd:1" });
+        let package = Package {
+            environment: json!({
+                "assetAdministrationShells": [shell.clone(), { "modelType": "AssetAdministrationShell", "id": "urn:aas:2" }],
+                "submodels": [submodel.clone()],
+                "conceptDescriptions": [concept_description.clone()]
+            }),
+            thumbnail: None,
+            files: Vec::new(),
+        };
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), write_package(&package).unwrap()).unwrap();
+        let db = TempDb::open();
+        let mut config = Config { aas_id_short: "Test".to_string(), aas_identifier: "urn:aas:1".to_string(), ..Default::default() };
+        config.aasx.import_path = Some(file.path().to_string_lossy().into_owned());
+
+        db.rocksdb().lock().await.put("Test:submodel_endpoints", json!({ "Network": "http://old/submodels/x" }).to_string()).unwrap();
+
+        import_package(&config, db.rocksdb()).await.unwrap();
+        // The configured shell, with the submodels that are in the package
+        assert_eq!(aas_find_one("Test".to_string(), db.rocksdb()).await.unwrap(), shell);
+        assert_eq!(aas_find_one("Test:Network".to_string(), db.rocksdb()).await.unwrap(), submodel);
+        assert_eq!(aas_find_one("Test:submodels_dictionary".to_string(), db.rocksdb()).await.unwrap(), json!({ "Network": "urn:sm:1" }));
+        // Endpoints of an earlier onboarding are dropped
+        assert_eq!(aas_find_one("Test:submodel_endpoints".to_string(), db.rocksdb()).await.unwrap(), json!({}));
+        assert_eq!(concept_descriptions::find(db.rocksdb(), "Test", "urn:cd:1").await.unwrap(), concept_description);
+
+        config.aas_identifier = "urn:aas:3".to_string();
+        assert!(matches!(import_package(&config, db.rocksdb()).await, Err(AppError::Validation(_))));
+    }
+
+    #[actix_web::test]
+    async fn test_thumbnail_keeps_its_image_type() {
+        let aas_id_short = "AasxThumbnailTest";
+        let shell = json!({ "modelType": "AssetAdministrationShell", "id": "urn:aas:1", "submodels": [] });
+        let import = |thumbnail: (&str, &[u8])| {
+            let package = Package {
+                environment: json!({ "assetAdministrationShells": [shell.clone()], "submodels": [] }),
+                thumbnail: Some((thumbnail.0.to_string(), thumbnail.1.to_vec())),
+                files: Vec::new(),
+            };
+            let file = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(file.path(), write_package(&package).unwrap()).unwrap();
+            file
+        };
+        let db = TempDb::open();
+        let mut config = Config { aas_id_short: aas_id_short.to_string(), ..Default::default() };
+
+        let file = import(("/aasx/thumbnail.png", b"png"));
+        config.aasx.import_path = Some(file.path().to_string_lossy().into_owned());
+        import_package(&config, db.rocksdb()).await.unwrap();
+        let file = import(("/aasx/Photo.JPG", b"jpeg"));
+        config.aasx.import_path = Some(file.path().to_string_lossy().into_owned());
+        import_package(&config, db.rocksdb()).await.unwrap();
+
+        let stored = stored_thumbnail(aas_id_short).await;
+        let exported = read_package(&export_package(&config, db.rocksdb()).await.unwrap()).unwrap().thumbnail;
+        for extension in THUMBNAIL_EXTENSIONS {
+            let _ = std::fs::remove_file(thumbnail_path(aas_id_short, extension));
+        }
+        // The PNG of the first import is replaced, not served instead of the JPEG
+        assert_eq!(stored, Some(("jpg", b"jpeg".to_vec())));
+        assert_eq!(content_type("jpg"), "image/jpeg");
+        assert_eq!(exported, Some(("/aasx/thumbnail.jpg".to_string(), b"jpeg".to_vec())));
+    }
+}
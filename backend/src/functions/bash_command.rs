@@ -3,8 +3,16 @@ use anyhow:: anyhow;
 
 /// Executes a bash script and returns its output as a String.
 pub async fn run_bash_script(script_path: &str) -> anyhow::Result<String> {
+    run_bash_script_with_env(script_path, &[]).await
+}
+
+/// Executes a bash script with additional environment variables and returns its output.
+/// The script is killed if the returned future is dropped, e.g. by a timeout.
+pub async fn run_bash_script_with_env(script_path: &str, envs: &[(String, String)]) -> anyhow::Result<String> {
     let output = Command::new("bash")
         .arg(script_path)
+        .envs(envs.iter().map(|(key, value)| (key, value)))
+        .kill_on_drop(true)
         .output()
         .await
         .map_err(|e| anyhow!("Failed to execute script: {}", e))?;
@@ -16,4 +24,4 @@ pub async fn run_bash_script(script_path: &str) -> anyhow::Result<String> {
         let stderr = String::from_utf8_lossy(&output.stderr);
         Err(anyhow!("Script execution failed: {}", stderr))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,241 @@
+//! ConceptDescriptions of the semanticIds in the stored submodels, fetched from the AAS
+//! server (or read from an imported AASX package) and cached in RocksDB, so that values
+//! can be shown with their names, units and definitions.
+
+use rocksdb::DB;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::aas_interfaces::{aas_find_one, aas_update_one, semantic_id};
+use super::discovery::encode_id;
+use super::id_short_path;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::http_client::AasClient;
+
+fn cache_key(aas_id_short: &str) -> String {
+    format!("{}:concept_descriptions", aas_id_short)
+}
+
+pub fn is_enabled(config: &Config) -> bool {
+    config.concept_descriptions.url.is_some()
+}
+
+/// Cached ConceptDescriptions by identifier, empty before the first fetch.
+pub async fn cached(rocksdb: Arc<Mutex<DB>>, aas_id_short: &str) -> Result<Map<String, Value>, AppError> {
+    match aas_find_one(cache_key(aas_id_short), rocksdb).await {
+        Ok(Value::Object(concept_descriptions)) => Ok(concept_descriptions),
+        Ok(_) | Err(AppError::NotFound(_)) => Ok(Map::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// The cached ConceptDescription `id`.
+pub async fn find(rocksdb: Arc<Mutex<DB>>, aas_id_short: &str, id: &str) -> Result<Value, AppError> {
+    cached(rocksdb, aas_id_short)
+        .await?
+        .remove(id)
+        .ok_or_else(|| AppError::NotFound(format!("ConceptDescription '{}' not found", id)))
+}
+
+/// Adds ConceptDescriptions to the cache, e.g. the ones of an imported AASX package.
+pub async fn store(rocksdb: Arc<Mutex<DB>>, aas_id_short: &str, concept_descriptions: &[Value]) -> Result<(), AppError> {
+    let mut cache = cached(rocksdb.clone(), aas_id_short).await?;
+    for concept_description in concept_descriptions {
+        if let Some(id) = concept_description["id"].as_str() {
+            cache.insert(id.to_string(), concept_description.clone());
+        }
+    }
+    aas_update_one(cache_key(aas_id_short), rocksdb, Value::Object(cache), true).await?;
+    Ok(())
+}
+
+/// Fetches the ConceptDescriptions referenced by the stored submodels from
+/// `concept_descriptions.url` and caches them. Ones that cannot be fetched keep their
+/// cached version. Returns the number of cached ConceptDescriptions.
+pub async fn refresh(client: &AasClient, config: &Config, rocksdb: Arc<Mutex<DB>>) -> Result<usize, AppError> {
+    let Some(server) = &config.concept_descriptions.url else {
+        return Ok(0);
+    };
+    let aas_id_short = config.aas_id_short.as_str();
+    let dictionary = aas_find_one(format!("{}:submodels_dictionary", aas_id_short), rocksdb.clone()).await?;
+
+    let mut ids = BTreeSet::new();
+    for submodel_id_short in dictionary.as_object().into_iter().flat_map(|dictionary| dictionary.keys()) {
+        let submodel = aas_find_one(format!("{}:{}", aas_id_short, submodel_id_short), rocksdb.clone()).await?;
+        collect_semantic_ids(submodel["submodelElements"].as_array().map(Vec::as_slice).unwrap_or_default(), &mut ids);
+    }
+
+    let mut cache = cached(rocksdb.clone(), aas_id_short).await?;
+    let mut missing = 0;
+    for id in ids {
+        let url = format!("{}concept-descriptions/{}", server, encode_id(&id));
+        match client.get_json(&url, "get_concept_description").await {
+            Ok(concept_description) => {
+                cache.insert(id, concept_description);
+            }
+            // Semantic IDs often point to dictionaries the server does not mirror
+            Err(AppError::NotFound(_)) => missing += 1,
+            Err(e) => eprintln!("Failed to fetch ConceptDescription {}: {}", id, e),
+        }
+    }
+    if missing > 0 {
+        println!("{} referenced ConceptDescriptions are not available at {}", missing, server);
+    }
+
+    let count = cache.len();
+    aas_update_one(cache_key(aas_id_short), rocksdb, Value::Object(cache), true).await?;
+    Ok(count)
+}
+
+fn collect_semantic_ids(elements: &[Value], ids: &mut BTreeSet<String>) {
+    for element in elements {
+        if let Some(id) = semantic_id(element) {
+            ids.insert(id.to_string());
+        }
+        if let Some(children) = id_short_path::children(element) {
+            collect_semantic_ids(children, ids);
+        }
+    }
+}
+
+/// `{"value", "annotations"}` with the annotations of `elements` and their children by
+/// idShortPath. Each element is given with its own idShortPath.
+pub fn annotate<'a>(
+    value: Value,
+    elements: impl IntoIterator<Item = (String, &'a Value)>,
+    concept_descriptions: &Map<String, Value>,
+) -> Value {
+    let mut annotations = Map::new();
+    for (path, element) in elements {
+        collect_annotations(element, path, concept_descriptions, &mut annotations);
+    }
+    json!({ "value": value, "annotations": annotations })
+}
+
+fn collect_annotations(element: &Value, path: String, concept_descriptions: &Map<String, Value>, annotations: &mut Map<String, Value>) {
+    if let Some(annotation) = semantic_id(element).and_then(|id| concept_descriptions.get(id)).and_then(annotation) {
+        annotations.insert(path.clone(), annotation);
+    }
+    let is_list = element["modelType"] == "SubmodelElementList";
+    for (index, child) in id_short_path::children(element).into_iter().flatten().enumerate() {
+        let child_path = if is_list {
+            format!("{}[{}]", path, index)
+        } else {
+            format!("{}.{}", path, child["idShort"].as_str().unwrap_or_default())
+        };
+        collect_annotations(child, child_path, concept_descriptions, annotations);
+    }
+}
+
+/// Names, unit and definition from the IEC 61360 data specification of a ConceptDescription.
+/// Multi-language texts are given in English, or the first language there is.
+pub fn annotation(concept_description: &Value) -> Option<Value> {
+    let content = concept_description["embeddedDataSpecifications"]
+        .as_array()?
+        .iter()
+        .map(|specification| &specification["dataSpecificationContent"])
+        .find(|content| content["modelType"] == "DataSpecificationIec61360")?;
+
+    let mut annotation = Map::new();
+    for key in ["preferredName", "shortName", "definition"] {
+        let texts = content[key].as_array().map(Vec::as_slice).unwrap_or_default();
+        let text = texts.iter().find(|text| text["language"] == "en").or(texts.first());
+        if let Some(text) = text.and_then(|text| text["text"].as_str()) {
+            annotation.insert(key.to_string(), json!(text));
+        }
+    }
+    for key in ["unit", "symbol"] {
+        if let Some(text) = content[key].as_str().filter(|text| !text.is_empty()) {
+            annotation.insert(key.to_string(), json!(text));
+        }
+    }
+    (!annotation.is_empty()).then_some(Value::Object(annotation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{aas_client, MockServer, TempDb};
+    use actix_web::{web, HttpResponse};
+
+    #[test]
+    fn test_annotations_by_id_short_path() {
+        let semantic_id = |id: &str| json!({ "type": "ExternalReference", "keys": [{ "type": "GlobalReference", "value": id }] });
+        let temperature = json!({
+            "modelType": "ConceptDescription",
+            "id": "0173-1#02-AAA001#001",
+            "embeddedDataSpecifications": [{
+                "dataSpecificationContent": {
+                    "modelType": "DataSpecificationIec61360",
+                    "preferredName": [{ "language": "de", "text": "CPU-Temperatur" }, { "language": "en", "text": "CPU temperature" }],
+                    "unit": "°C",
+                    "symbol": ""
+                }
+            }]
+        });
+        let concept_descriptions = Map::from_iter([("0173-1#02-AAA001#001".to_string(), temperature)]);
+        let hardware = json!({
+            "modelType": "SubmodelElementCollection",
+            "idShort": "Hardware",
+            "value": [
+                { "modelType": "Property", "idShort": "CpuTemperature", "semanticId": semantic_id("0173-1#02-AAA001#001") },
+                { "modelType": "SubmodelElementList", "idShort": "Sensors", "value": [
+                    { "modelType": "Property", "semanticId": semantic_id("0173-1#02-AAA001#001") },
+                    { "modelType": "Property", "semanticId": semantic_id("urn:unknown") }
+                ]}
+            ]
+        });
+
+        let mut ids = BTreeSet::new();
+        collect_semantic_ids(std::slice::from_ref(&hardware), &mut ids);
+        assert_eq!(ids.into_iter().collect::<Vec<_>>(), ["0173-1#02-AAA001#001", "urn:unknown"]);
+
+        let annotated = annotate(json!(null), [("Hardware".to_string(), &hardware)], &concept_descriptions);
+        let expected = json!({ "preferredName": "CPU temperature", "unit": "°C" });
+        assert_eq!(annotated["annotations"], json!({ "Hardware.CpuTemperature": expected, "Hardware.Sensors[0]": expected }));
+    }
+
+    #[actix_web::test]
+    async fn test_refresh_caches_referenced_concept_descriptions() {
+        let mock = MockServer::start(|app| {
+            app.route("/concept-descriptions/{id}", web::get().to(|id: web::Path<String>| async move {
+                if id.as_str() == encode_id("urn:cd:1") {
+                    HttpResponse::Ok().json(json!({ "modelType": "ConceptDescription", "id": "urn:cd:1" }))
+                } else {
+                    HttpResponse::NotFound().finish()
+                }
+            }));
+        });
+        let db = TempDb::open();
+        let semantic_id = |id: &str| json!({ "type": "ExternalReference", "keys": [{ "type": "GlobalReference", "value": id }] });
+        let submodel = json!({
+            "modelType": "Submodel",
+            "idShort": "Network",
+            "submodelElements": [
+                { "modelType": "Property", "idShort": "Address", "semanticId": semantic_id("urn:cd:1") },
+                { "modelType": "Property", "idShort": "Port", "semanticId": semantic_id("urn:cd:2") }
+            ]
+        });
+        {
+            let db = db.rocksdb();
+            let db = db.lock().await;
+            db.put("Test:submodels_dictionary", json!({ "Network": "urn:sm:1" }).to_string()).unwrap();
+            db.put("Test:Network", submodel.to_string()).unwrap();
+        }
+        let old = json!({ "modelType": "ConceptDescription", "id": "urn:cd:old" });
+        store(db.rocksdb(), "Test", std::slice::from_ref(&old)).await.unwrap();
+
+        let mut config = Config { aas_id_short: "Test".to_string(), ..Default::default() };
+        // Nothing is fetched without a server
+        assert_eq!(refresh(&aas_client(), &config, db.rocksdb()).await.unwrap(), 0);
+        config.concept_descriptions.url = Some(mock.url().to_string());
+        // urn:cd:2 is not at the server, the cached ones are kept
+        assert_eq!(refresh(&aas_client(), &config, db.rocksdb()).await.unwrap(), 2);
+        assert_eq!(find(db.rocksdb(), "Test", "urn:cd:1").await.unwrap()["id"], "urn:cd:1");
+        assert_eq!(find(db.rocksdb(), "Test", "urn:cd:old").await.unwrap(), old);
+        assert!(matches!(find(db.rocksdb(), "Test", "urn:cd:2").await, Err(AppError::NotFound(_))));
+    }
+}
@@ -0,0 +1,224 @@
+//! Locating the shell and its submodels, either through an AAS Discovery service and AAS
+//! Registry or directly at `AASX_SERVER`.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use super::aas_server::AasServerClient;
+use super::registration;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::http_client::AasClient;
+
+/// Where the shell and its submodels are served.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShellEndpoints {
+    pub aas_id: String,
+    /// URL of the shell itself, e.g. `http://server/shells/{base64}`.
+    pub shell_url: String,
+    /// URLs of the submodels listed in the shell descriptor, by submodel ID.
+    pub submodel_urls: HashMap<String, String>,
+}
+
+impl ShellEndpoints {
+    /// URL of a submodel, falling back to `AASX_SERVER` if the registry does not describe it.
+    /// `None` if neither knows the submodel, i.e. only a registry is configured.
+    pub fn submodel_url(&self, server: &dyn AasServerClient, submodel_id: &str) -> Option<String> {
+        match self.submodel_urls.get(submodel_id) {
+            Some(url) => Some(url.clone()),
+            None if server.base_url().is_empty() => None,
+            None => Some(server.submodel_url(submodel_id)),
+        }
+    }
+}
+
+pub fn encode_id(id: &str) -> String {
+    base64::encode_config(id, base64::URL_SAFE_NO_PAD)
+}
+
+/// Decodes an identifier from a URL path, accepting padded and unpadded base64url.
+pub fn decode_id(encoded: &str) -> Result<String, AppError> {
+    base64::decode_config(encoded.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+        .ok()
+        .and_then(|id| String::from_utf8(id).ok())
+        .ok_or_else(|| AppError::BadRequest(format!("'{}' is no base64url encoded identifier", encoded)))
+}
+
+/// Finds the shell ID (through the discovery service if configured) and the endpoints of
+/// the shell and its submodels (through the registry if configured).
+pub async fn resolve_shell(server: &dyn AasServerClient, config: &Config) -> Result<ShellEndpoints, AppError> {
+    let client = server.http();
+    let discovery = &config.discovery;
+    let aas_id = match (&discovery.discovery_url, &config.aas_asset_identifier) {
+        (Some(discovery_url), Some(asset_id)) => lookup_shell_id(client, discovery_url, asset_id, config).await?,
+        _ => config.aas_identifier.clone(),
+    };
+
+    let Some(registry_url) = &discovery.registry_url else {
+        return Ok(ShellEndpoints {
+            shell_url: server.shell_url(&aas_id),
+            aas_id,
+            submodel_urls: HashMap::new(),
+        });
+    };
+
+    let descriptor_url = format!("{}shell-descriptors/{}", registry_url, encode_id(&aas_id));
+    let descriptor = client.get_json(&descriptor_url, "get_shell_descriptor").await?;
+    let shell_url = endpoint_href(&descriptor, "AAS", config)
+        .ok_or_else(|| AppError::NotFound(format!("Shell descriptor {} has no AAS endpoint", aas_id)))?;
+
+    let mut submodel_urls = HashMap::new();
+    for submodel_descriptor in descriptor["submodelDescriptors"].as_array().into_iter().flatten() {
+        if let (Some(id), Some(href)) = (submodel_descriptor["id"].as_str(), endpoint_href(submodel_descriptor, "SUBMODEL", config)) {
+            submodel_urls.insert(id.to_string(), href);
+        }
+    }
+
+    Ok(ShellEndpoints { aas_id, shell_url, submodel_urls })
+}
+
+/// Completes the submodel endpoints from the submodel registry, for shell descriptors
+/// that do not embed their submodel descriptors.
+pub async fn resolve_submodels(
+    client: &AasClient,
+    config: &Config,
+    endpoints: &mut ShellEndpoints,
+    submodel_ids: &[String],
+) -> Result<(), AppError> {
+    let Some(registry_url) = &config.discovery.submodel_registry_url else {
+        return Ok(());
+    };
+    for submodel_id in submodel_ids {
+        if endpoints.submodel_urls.contains_key(submodel_id) {
+            continue;
+        }
+        let descriptor_url = format!("{}submodel-descriptors/{}", registry_url, encode_id(submodel_id));
+        let descriptor = client.get_json(&descriptor_url, "get_submodel_descriptor").await?;
+        if let Some(href) = endpoint_href(&descriptor, "SUBMODEL", config) {
+            endpoints.submodel_urls.insert(submodel_id.clone(), href);
+        }
+    }
+    Ok(())
+}
+
+/// Asks the discovery service for the shells of the asset. With several shells the
+/// configured `AAS_IDENTIFIER` has to be one of them.
+async fn lookup_shell_id(client: &AasClient, discovery_url: &str, asset_id: &str, config: &Config) -> Result<String, AppError> {
+    let asset_link = json!({ "name": "globalAssetId", "value": asset_id });
+    let url = format!("{}lookup/shells?assetIds={}", discovery_url, encode_id(&asset_link.to_string()));
+    let found = client.get_json(&url, "lookup_shells").await?;
+
+    // Discovery services of AAS 3.0 answer a paged result, older ones a plain array
+    let ids: Vec<&str> = found
+        .get("result")
+        .unwrap_or(&found)
+        .as_array()
+        .map(|ids| ids.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    match ids.as_slice() {
+        [] => Err(AppError::NotFound(format!("Discovery service knows no shell of asset '{}'", asset_id))),
+        [id] => Ok(id.to_string()),
+        _ if ids.contains(&config.aas_identifier.as_str()) => Ok(config.aas_identifier.clone()),
+        _ => Err(AppError::Validation(format!(
+            "Asset '{}' has {} shells ({}), set AAS_IDENTIFIER to choose one",
+            asset_id,
+            ids.len(),
+            ids.join(", ")
+        ))),
+    }
+}
+
+/// `href` of the first endpoint of a descriptor whose interface starts with `interface`,
+/// e.g. `AAS-3.0` or `SUBMODEL-3.0`. The endpoints this device registered itself are
+/// skipped, the data is fetched from the AAS server.
+fn endpoint_href(descriptor: &Value, interface: &str, config: &Config) -> Option<String> {
+    descriptor["endpoints"]
+        .as_array()?
+        .iter()
+        .filter(|endpoint| endpoint["interface"].as_str().is_some_and(|name| name.starts_with(interface)))
+        .filter_map(|endpoint| endpoint["protocolInformation"]["href"].as_str())
+        .find(|href| !registration::is_own_endpoint(config, href))
+        .map(|href| href.trim_end_matches('/').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerDialect;
+    use crate::functions::aas_server;
+    use crate::test_utils::{aas_client, MockServer};
+    use actix_web::{web, HttpRequest, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_resolves_shell_through_discovery_and_registry() {
+        let asset_link = json!({ "name": "globalAssetId", "value": "urn:asset:1" }).to_string();
+        let mock = MockServer::start(move |app| {
+            let asset_link = asset_link.clone();
+            app.route("/lookup/shells", web::get().to(move |req: HttpRequest| {
+                    let expected = format!("assetIds={}", encode_id(&asset_link));
+                    async move {
+                        assert_eq!(req.query_string(), expected);
+                        HttpResponse::Ok().json(json!({ "paging_metadata": {}, "result": ["urn:aas:1"] }))
+                    }
+                }))
+                .route("/shell-descriptors/{id}", web::get().to(|id: web::Path<String>| async move {
+                    assert_eq!(id.as_str(), encode_id("urn:aas:1"));
+                    HttpResponse::Ok().json(json!({
+                        "id": "urn:aas:1",
+                        "endpoints": [{ "interface": "AAS-3.0", "protocolInformation": { "href": "http://edge:8081/shells/abc" } }],
+                        "submodelDescriptors": [{
+                            "id": "urn:sm:1",
+                            "endpoints": [{ "interface": "SUBMODEL-3.0", "protocolInformation": { "href": "http://other:8081/submodels/def/" } }]
+                        }]
+                    }))
+                }));
+        });
+
+        let mut config = Config {
+            aas_asset_identifier: Some("urn:asset:1".to_string()),
+            aasx_server: "http://fallback/".to_string(),
+            ..Default::default()
+        };
+        config.discovery.discovery_url = Some(mock.url().to_string());
+        config.discovery.registry_url = Some(mock.url().to_string());
+        let server = aas_server::for_dialect(ServerDialect::Generic, aas_client(), config.aasx_server.clone());
+
+        let endpoints = resolve_shell(server.as_ref(), &config).await.unwrap();
+        assert_eq!(endpoints.aas_id, "urn:aas:1");
+        assert_eq!(endpoints.shell_url, "http://edge:8081/shells/abc");
+        assert_eq!(endpoints.submodel_url(server.as_ref(), "urn:sm:1").unwrap(), "http://other:8081/submodels/def");
+        // Submodels missing in the registry are read from AASX_SERVER
+        assert_eq!(
+            endpoints.submodel_url(server.as_ref(), "urn:sm:2").unwrap(),
+            format!("http://fallback/submodels/{}", encode_id("urn:sm:2"))
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_registry_without_aas_server() {
+        let mock = MockServer::start(|app| {
+            app.route("/shell-descriptors/{id}", web::get().to(|| async {
+                HttpResponse::Ok().json(json!({
+                    "id": "urn:aas:1",
+                    "endpoints": [{ "interface": "AAS-3.0", "protocolInformation": { "href": "http://edge:8081/shells/abc" } }],
+                    "submodelDescriptors": [{
+                        "id": "urn:sm:1",
+                        "endpoints": [{ "interface": "SUBMODEL-3.0", "protocolInformation": { "href": "http://edge:8081/submodels/def" } }]
+                    }]
+                }))
+            }));
+        });
+
+        let mut config = Config { aas_identifier: "urn:aas:1".to_string(), aasx_server: String::new(), ..Default::default() };
+        config.discovery.registry_url = Some(mock.url().to_string());
+        assert!(config.has_aas_server());
+        let server = aas_server::for_dialect(ServerDialect::Generic, aas_client(), config.aasx_server.clone());
+
+        let endpoints = resolve_shell(server.as_ref(), &config).await.unwrap();
+        assert_eq!(endpoints.shell_url, "http://edge:8081/shells/abc");
+        assert_eq!(endpoints.submodel_url(server.as_ref(), "urn:sm:1").unwrap(), "http://edge:8081/submodels/def");
+        // No relative URL for submodels the registry does not describe
+        assert_eq!(endpoints.submodel_url(server.as_ref(), "urn:sm:2"), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! idShortPaths like `Hardware.Processor.CpuUsage` or `Interfaces[2].Address`, resolved
+//! against the JSON of a stored submodel.
+
+use serde_json::Value;
+
+use crate::error::AppError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// Child of a collection, entity or annotated relationship.
+    IdShort(String),
+    /// Item of a `SubmodelElementList`.
+    Index(usize),
+}
+
+/// Splits an idShortPath into its segments. It has to start with an idShort.
+pub fn parse(path: &str) -> Result<Vec<PathSegment>, AppError> {
+    let invalid = || AppError::BadRequest(format!("Invalid idShortPath '{}'", path));
+    let mut segments = Vec::new();
+
+    for part in path.split('.') {
+        let (id_short, mut indices) = match part.find('[') {
+            Some(bracket) => part.split_at(bracket),
+            None => (part, ""),
+        };
+        if id_short.is_empty() || !id_short.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(invalid());
+        }
+        segments.push(PathSegment::IdShort(id_short.to_string()));
+
+        while !indices.is_empty() {
+            let close = indices.find(']').ok_or_else(invalid)?;
+            let index = indices[1..close].parse().map_err(|_| invalid())?;
+            segments.push(PathSegment::Index(index));
+            indices = &indices[close + 1..];
+            if !indices.is_empty() && !indices.starts_with('[') {
+                return Err(invalid());
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Formats segments back into an idShortPath.
+pub fn format(segments: &[PathSegment]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        match segment {
+            PathSegment::IdShort(id_short) if path.is_empty() => path.push_str(id_short),
+            PathSegment::IdShort(id_short) => {
+                path.push('.');
+                path.push_str(id_short);
+            }
+            PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+        }
+    }
+    path
+}
+
+/// Key of the child elements of an element of `model_type`, if it has any.
+pub fn children_key(model_type: &str) -> Option<&'static str> {
+    match model_type {
+        "SubmodelElementCollection" | "SubmodelElementList" => Some("value"),
+        "Entity" => Some("statements"),
+        "AnnotatedRelationshipElement" => Some("annotations"),
+        _ => None,
+    }
+}
+
+pub fn children(element: &Value) -> Option<&Vec<Value>> {
+    let key = children_key(element["modelType"].as_str()?)?;
+    element.get(key)?.as_array()
+}
+
+/// The elements along `path`, starting at the top-level element in `elements`.
+pub fn resolve_trail<'a>(elements: &'a [Value], path: &[PathSegment]) -> Option<Vec<&'a Value>> {
+    let mut trail: Vec<&Value> = Vec::with_capacity(path.len());
+
+    for segment in path {
+        let candidates: &[Value] = match trail.last() {
+            Some(parent) => children(parent)?,
+            None => elements,
+        };
+        let is_list = trail.last().is_some_and(|parent| parent["modelType"] == "SubmodelElementList");
+        let next = match segment {
+            PathSegment::IdShort(id_short) if !is_list => candidates.iter().find(|element| element["idShort"] == id_short.as_str()),
+            PathSegment::Index(index) if is_list => candidates.get(*index),
+            _ => None,
+        }?;
+        trail.push(next);
+    }
+
+    Some(trail)
+}
+
+/// The element at `path` below the top-level `elements`.
+pub fn resolve<'a>(elements: &'a [Value], path: &[PathSegment]) -> Option<&'a Value> {
+    resolve_trail(elements, path)?.pop()
+}
+
+/// The element at `path` in `submodel`, `NotFound` if there is none.
+pub fn find_element<'a>(submodel: &'a Value, path: &[PathSegment]) -> Result<&'a Value, AppError> {
+    let elements = submodel["submodelElements"].as_array().map(Vec::as_slice).unwrap_or_default();
+    resolve(elements, path).ok_or_else(|| AppError::NotFound(format!("Submodel element '{}' not found", format(path))))
+}
+
+/// The element at `path` in `submodel` for writing, `NotFound` if there is none.
+pub fn find_element_mut<'a>(submodel: &'a mut Value, path: &[PathSegment]) -> Result<&'a mut Value, AppError> {
+    let not_found = || AppError::NotFound(format!("Submodel element '{}' not found", format(path)));
+    let (first, rest) = path.split_first().ok_or_else(not_found)?;
+    let top_level = match first {
+        PathSegment::IdShort(id_short) => submodel
+            .get_mut("submodelElements")
+            .and_then(Value::as_array_mut)
+            .and_then(|elements| elements.iter_mut().find(|element| element["idShort"] == id_short.as_str())),
+        PathSegment::Index(_) => None,
+    };
+    top_level.and_then(|element| descend_mut(element, rest)).ok_or_else(not_found)
+}
+
+fn descend_mut<'a>(element: &'a mut Value, path: &[PathSegment]) -> Option<&'a mut Value> {
+    let Some((segment, rest)) = path.split_first() else { return Some(element) };
+    let is_list = element["modelType"] == "SubmodelElementList";
+    let key = children_key(element["modelType"].as_str()?)?;
+    let candidates = element.get_mut(key)?.as_array_mut()?;
+    let next = match segment {
+        PathSegment::IdShort(id_short) if !is_list => candidates.iter_mut().find(|element| element["idShort"] == id_short.as_str()),
+        PathSegment::Index(index) if is_list => candidates.get_mut(*index),
+        _ => None,
+    }?;
+    descend_mut(next, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_and_resolve() {
+        let path = parse("Interfaces[1].Address").unwrap();
+        assert_eq!(
+            path,
+            vec![
+                PathSegment::IdShort("Interfaces".to_string()),
+                PathSegment::Index(1),
+                PathSegment::IdShort("Address".to_string()),
+            ]
+        );
+        assert_eq!(format(&path), "Interfaces[1].Address");
+        for invalid in ["", "A..B", "[0]", "A[x]", "A[0]B", "A[0"] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+
+        let submodel = json!({ "submodelElements": [{
+            "modelType": "SubmodelElementList", "idShort": "Interfaces", "value": [
+                { "modelType": "SubmodelElementCollection", "value": [{ "modelType": "Property", "idShort": "Address", "value": "a" }] },
+                { "modelType": "SubmodelElementCollection", "value": [{ "modelType": "Property", "idShort": "Address", "value": "b" }] }
+            ]
+        }]});
+        assert_eq!(find_element(&submodel, &path).unwrap()["value"], "b");
+        assert!(find_element(&submodel, &parse("Interfaces[2].Address").unwrap()).is_err());
+        // Lists are indexed, not addressed by idShort
+        assert!(find_element(&submodel, &parse("Interfaces.Address").unwrap()).is_err());
+
+        let mut submodel = submodel;
+        find_element_mut(&mut submodel, &path).unwrap()["value"] = "c".into();
+        assert_eq!(find_element(&submodel, &path).unwrap()["value"], "c");
+    }
+}
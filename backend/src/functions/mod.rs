@@ -1,5 +1,18 @@
+pub mod aas_api;
 pub mod aas_interfaces;
+pub mod aas_server;
+pub mod aasx;
+pub mod concept_descriptions;
 pub mod onboarding;
+pub mod registration;
 pub mod bash_command;
+pub mod discovery;
+pub mod id_short_path;
+pub mod operations;
+pub mod scheduler;
 pub mod scheduler_task;
-pub mod transform_value_submodel;
\ No newline at end of file
+pub mod shutdown;
+pub mod transform_value_submodel;
+pub mod value_type;
+pub mod timeseries;
+pub mod users;
@@ -1,138 +1,102 @@
 use serde_json;
-use reqwest;
-use base64;
-use anyhow;
-use anyhow::Context;
 use tokio;
 use futures;
 use chrono::Utc;
-use actix_web::Error;
+use reqwest::StatusCode;
 use rocksdb::DB;
 use serde_json::Value;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use super::aas_interfaces;
+use actix_web::web;
+
+use super::{aas_interfaces, aasx, concept_descriptions, discovery, registration, scheduler_task};
+use super::aas_server::AasServerClient;
+use super::scheduler::SchedulerHandle;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::metrics;
+use crate::state::AppState;
+use crate::state::onboarding::{Onboarding, OnboardingState};
 
 async fn fetch_single_submodel(
-    aasx_server_url: &str,
+    server: &dyn AasServerClient,
+    submodel_url: &str,
     aas_id_short: &str,
     submodel_uid: &str,
     rocksdb: Arc<Mutex<DB>>,
     submodels_dictionary: Arc<Mutex<serde_json::Map<String, Value>>>,
-    _onboarding: bool,
-) -> Result<(), actix_web::Error> {
-    let submodel_url = format!(
-        "{}submodels/{}",
-        aasx_server_url,
-        base64::encode_config(submodel_uid, base64::URL_SAFE_NO_PAD)
-    );
-
-    // let client = reqwest::Client::new();
-    let client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)  // This allows SSL certificates to be ignored (only for testing purposes!)
-        .timeout(std::time::Duration::from_secs(10))  // You can add a timeout for the request
-        .build()
-        .expect("Failed to build client");
-
-    let response = client
-        .get(&submodel_url)
-        .send()
+    submodel_endpoints: Arc<Mutex<serde_json::Map<String, Value>>>,
+) -> Result<(), AppError> {
+
+    let response_body_submodel = server
+        .get_submodel(submodel_url)
         .await
-        .with_context(|| format!("Failed to send request to fetch submodel id short from URL: {}", submodel_url))
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(|e| e.context(&format!("Failed to fetch submodel {}", submodel_url)))?;
 
-    if response.status().is_success(){
-        let response_body_submodel: Value = response
-            .json()
-            .await
-            .with_context(|| "Failed to parse response body as JSON")
-            .map_err(actix_web::error::ErrorInternalServerError)?;
-
-        let submodel_id_short = response_body_submodel["idShort"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Failed to extract idShort from response body"))
-            .map_err(actix_web::error::ErrorInternalServerError)?;
-
-        // Serialize the submodel value to store in RocksDB
-        let serialized_submodel = serde_json::to_vec(&response_body_submodel)
-            .map_err(actix_web::error::ErrorInternalServerError)?;
-
-        // Store the submodel in RocksDB
-        {
-            let mut db = rocksdb.lock().await;
-            db.put(format!("{}:{}", aas_id_short, submodel_id_short), serialized_submodel)
-                .map_err(actix_web::error::ErrorInternalServerError)?;
-        }
+    let submodel_id_short = response_body_submodel["idShort"]
+        .as_str()
+        .ok_or_else(|| AppError::upstream(StatusCode::OK, "Failed to extract idShort from response body"))?;
 
-        println!("Successfully replaced submodel: {}", submodel_id_short);
+    // Serialize the submodel value to store in RocksDB
+    let serialized_submodel = serde_json::to_vec(&response_body_submodel)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
 
-        // Update the submodels dictionary
-        {
-            let mut dictionary = submodels_dictionary.lock().await;
-            dictionary.insert(
-                submodel_id_short.to_string(),
-                Value::String(submodel_uid.to_string()),
-            );
-        }
-    } else {
-        // Handle unsuccessful responses
-        if !response.status().is_success() {
-            let status_code = response.status();
-            let response_body = response.text().await.unwrap_or_default();
-
-            println!(
-                "Failed to fetch URL {}. Status code: {}. Response body: {}",
-                submodel_url, status_code, response_body
-            );
-            return Err(actix_web::error::ErrorInternalServerError(format!(
-                "Failed to fetch URL {}. Status code: {}. Response body: {}",
-                submodel_url, status_code, response_body
-            )));
-        } else {
-            let status_code = response.status();
-            let response_body = response.text().await.unwrap_or_default();
+    // Store the submodel in RocksDB
+    {
+        let db = rocksdb.lock().await;
+        metrics::record_rocksdb_write();
+        db.put(format!("{}:{}", aas_id_short, submodel_id_short), serialized_submodel)?;
+    }
 
-            println!(
-                "Failed to fetch URL {}. Status code: {}. Response body: {}",
-                submodel_url, status_code, response_body
-            );
-            return Err(actix_web::error::ErrorInternalServerError(format!(
-                "Failed to fetch URL {}. Status code: {}. Response body: {}",
-                submodel_url, status_code, response_body
-            )));
-        }
+    println!("Successfully replaced submodel: {}", submodel_id_short);
+
+    // Update the submodels dictionary
+    {
+        let mut dictionary = submodels_dictionary.lock().await;
+        dictionary.insert(
+            submodel_id_short.to_string(),
+            Value::String(submodel_uid.to_string()),
+        );
     }
+    submodel_endpoints
+        .lock()
+        .await
+        .insert(submodel_id_short.to_string(), Value::String(submodel_url.to_string()));
 
     Ok(())
 }
 
 
+/// Fetches the submodels, given as pairs of submodel ID and URL, and stores them along
+/// with the dictionary of their idShorts and their endpoints.
 async fn fetch_all_submodels(
-    aasx_server_url: &str,
+    server: &Arc<dyn AasServerClient>,
     aas_id_short: &str,
-    submodel_uids: Vec<String>,
+    submodels: Vec<(String, String)>,
     rocksdb: Arc<Mutex<DB>>,
-    onboarding: bool,
-) -> Result<(), actix_web::Error> {
+) -> Result<(), AppError> {
     let submodels_dictionary = Arc::new(Mutex::new(serde_json::Map::new()));
+    let submodel_endpoints = Arc::new(Mutex::new(serde_json::Map::new()));
 
-    let fetch_tasks: Vec<_> = submodel_uids
+    let fetch_tasks: Vec<_> = submodels
         .into_iter()
-        .map(|submodel_uid| {
+        .map(|(submodel_uid, submodel_url)| {
+            let server = Arc::clone(server);
             let rocksdb_clone = Arc::clone(&rocksdb);
             let submodels_dictionary_clone = Arc::clone(&submodels_dictionary);
-            let aasx_server_url_clone = aasx_server_url.to_string();
+            let submodel_endpoints_clone = Arc::clone(&submodel_endpoints);
             let aas_id_short_clone = aas_id_short.to_string();
 
             tokio::spawn(async move {
                 if let Err(e) = fetch_single_submodel(
-                    &aasx_server_url_clone,
+                    server.as_ref(),
+                    &submodel_url,
                     &aas_id_short_clone,
                     &submodel_uid,
                     rocksdb_clone,
                     submodels_dictionary_clone,
-                    onboarding,
+                    submodel_endpoints_clone,
                 )
                 .await
                 {
@@ -148,166 +112,223 @@ async fn fetch_all_submodels(
     {
         let dictionary = submodels_dictionary.lock().await;
         let serialized_dictionary = serde_json::to_vec(&*dictionary)
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+            .map_err(|e| AppError::Internal(e.to_string()))?;
 
-        let mut db = rocksdb.lock().await;
+        let db = rocksdb.lock().await;
+        metrics::record_rocksdb_write();
         db.put(
             format!("{}:submodels_dictionary", aas_id_short),
             serialized_dictionary,
-        )
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        )?;
+
+        let endpoints = submodel_endpoints.lock().await;
+        let serialized_endpoints = serde_json::to_vec(&*endpoints)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        metrics::record_rocksdb_write();
+        db.put(format!("{}:submodel_endpoints", aas_id_short), serialized_endpoints)?;
     }
 
-    Ok(())
+    aas_interfaces::index_semantic_ids(rocksdb, aas_id_short).await
 }
 
 pub async fn edge_device_onboarding(
-    aasx_server: &str,
-    aas_uid: &str,
-    aas_id_short: &str,
+    server: &Arc<dyn AasServerClient>,
+    config: &Config,
     rocksdb: Arc<Mutex<DB>>,
-) -> Result<(), actix_web::Error> {
-    let url: String = format!(
-        "{}shells/{}",
-        aasx_server,
-        base64::encode_config(aas_uid, base64::URL_SAFE_NO_PAD)
-    );
+    onboarding: &Onboarding,
+) -> Result<(), AppError> {
+    let aas_id_short = config.aas_id_short.as_str();
+    let asset_identifier = config.aas_asset_identifier.as_deref();
+
+    // Offline onboarding from an AASX package instead of the AAS server
+    if config.aasx.import_path.is_some() {
+        onboarding.set_state(OnboardingState::FetchingSubmodels).await;
+        aasx::import_package(config, rocksdb.clone()).await?;
+        return onboarding_managed_device(server.as_ref(), config, rocksdb).await;
+    }
+
+    // Through the discovery service and registry if configured, otherwise at AASX_SERVER
+    let mut endpoints = discovery::resolve_shell(server.as_ref(), config).await?;
+    let aas_uid = endpoints.aas_id.clone();
+    let url = endpoints.shell_url.clone();
 
     println!("Fetching URL: {}", url);
 
     // Request Shell information from the Server
-    // let client: reqwest::Client = reqwest::Client::new();
-    let client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)  // This allows SSL certificates to be ignored (only for testing purposes!)
-        .timeout(std::time::Duration::from_secs(10))  // You can add a timeout for the request
-        .build()
-        .expect("Failed to build client");
-    let response: reqwest::Response = client
-        .get(&url)
-        .send()
+    let mut insert_data = server
+        .get_shell(&url)
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-
-    if response.status().is_success() {
-        let insert_data: Value = response
-            .json()
-            .await
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(|e| e.context(&format!("Failed to fetch URL {}", url)))?;
+
+    // Make sure the shell describes this device before storing anything
+    if let Some(asset_identifier) = asset_identifier {
+        let global_asset_id = insert_data["assetInformation"]["globalAssetId"].as_str().unwrap_or_default();
+        if global_asset_id != asset_identifier {
+            return Err(AppError::Validation(format!(
+                "Shell {} describes asset '{}' but AAS_ASSET_IDENTIFIER is '{}'",
+                aas_uid, global_asset_id, asset_identifier
+            )));
+        }
+    }
 
-        // Extract submodels ID
-        let submodels_id: Vec<String> = extract_submodels_id(&insert_data)?;
+    // Servers may leave the submodel references out of the shell
+    let references = server.get_submodel_refs(&url, &insert_data).await?;
+    insert_data["submodels"] = Value::Array(references);
 
-        // Store the shell data in RocksDB
-        {
-            let serialized_data = serde_json::to_vec(&insert_data)
-                .map_err(actix_web::error::ErrorInternalServerError)?;
+    // Extract submodels ID
+    let submodels_id: Vec<String> = extract_submodels_id(&insert_data)?;
+    discovery::resolve_submodels(server.http(), config, &mut endpoints, &submodels_id).await?;
+    let submodels: Vec<(String, String)> = submodels_id
+        .into_iter()
+        .filter_map(|submodel_id| match endpoints.submodel_url(server.as_ref(), &submodel_id) {
+            Some(submodel_url) => Some((submodel_id, submodel_url)),
+            None => {
+                eprintln!("Submodel {} is not in the registry and AASX_SERVER is not set, skipping it", submodel_id);
+                None
+            }
+        })
+        .collect();
 
-            let mut db = rocksdb.lock().await;
-            db.put(aas_id_short, serialized_data)
-                .map_err(actix_web::error::ErrorInternalServerError)?;
-        }
+    // Store the shell data in RocksDB
+    {
+        let serialized_data = serde_json::to_vec(&insert_data)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
 
-        fetch_all_submodels(
-            aasx_server,
-            aas_id_short,
-            submodels_id,
-            rocksdb.clone(),
-            true,
-        )
-        .await?;
-    } else {
-        println!("Failed to fetch URL. Status code: {}", response.status());
-        return Err(actix_web::error::ErrorInternalServerError(format!(
-            "Failed to fetch URL. Status code: {}",
-            response.status()
-        )));
+        let db = rocksdb.lock().await;
+        metrics::record_rocksdb_write();
+        db.put(aas_id_short, serialized_data)?;
     }
 
-    collecting_thumbnail_image(aas_id_short, aasx_server, aas_uid).await?;
+    onboarding.set_state(OnboardingState::FetchingSubmodels).await;
+
+    fetch_all_submodels(
+        server,
+        aas_id_short,
+        submodels,
+        rocksdb.clone(),
+    )
+    .await?;
+
+    collecting_thumbnail_image(server.as_ref(), aas_id_short, &endpoints.shell_url).await?;
 
-    onboarding_managed_device(aas_id_short, aasx_server, rocksdb.clone()).await?;
+    onboarding_managed_device(server.as_ref(), config, rocksdb.clone()).await?;
 
     Ok(())
 }
 
+/// Onboards the device, retrying until it succeeds, and then starts the scheduler.
+/// Runs next to the HTTP server, which reports the progress through `AppState.onboarding`.
+/// Returns the handle of the started scheduler, `None` if it could not be started.
+pub async fn onboarding_loop(app_state: web::Data<AppState>) -> Option<SchedulerHandle> {
+    let config = &app_state.config;
+
+    loop {
+        app_state.onboarding.start_attempt().await;
+
+        let server = app_state.aas_server().await;
+        let result = edge_device_onboarding(
+            &server,
+            config,
+            app_state.rocksdb.clone(),
+            &app_state.onboarding,
+        )
+        .await;
+        metrics::ONBOARDING_ATTEMPTS.with_label_values(&[metrics::result_label(&result)]).inc();
+
+        match result {
+            Ok(_) => {
+                app_state.onboarding.succeed().await;
+                println!("Device onboarded successfully!");
+                if registration::is_enabled(config) {
+                    if let Err(e) = registration::register(&app_state.http_client, config, app_state.rocksdb.clone()).await {
+                        eprintln!("Failed to register in the AAS registry: {}", e);
+                    }
+                }
+                if concept_descriptions::is_enabled(config) {
+                    match concept_descriptions::refresh(&app_state.http_client, config, app_state.rocksdb.clone()).await {
+                        Ok(count) => println!("Cached {} ConceptDescriptions", count),
+                        Err(e) => eprintln!("Failed to fetch the ConceptDescriptions: {}", e),
+                    }
+                }
+                break; // Exit loop on success
+            }
+            Err(err) => {
+                eprintln!(
+                    "Failed to onboard device: {}. \nRetrying in {} seconds...",
+                    err, config.onboarding_retry_interval
+                );
+                app_state.onboarding.fail(err.to_string()).await;
+                tokio::time::sleep(std::time::Duration::from_secs(config.onboarding_retry_interval)).await;
+            }
+        }
+    }
+
+    // only after is onboarded
+    match scheduler_task::submodels_scheduler(app_state.clone(), app_state.rocksdb.clone()).await {
+        Ok(scheduler) => Some(scheduler),
+        Err(e) => {
+            eprintln!("Failed to start the scheduler: {}", e);
+            None
+        }
+    }
+}
+
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 
 async fn collecting_thumbnail_image(
+    server: &dyn AasServerClient,
     aas_id_short: &str,   // A short ID for the AAS (Asset Administration Shell)
-    aasx_server_url: &str,   // Base URL of the AASX server
-    aas_uid: &str,           // UID of the AAS
-) -> Result<(), Error> {
-    // Construct the URL for the thumbnail image
-    let url = format!(
-        "{}shells/{}/asset-information/thumbnail",
-        aasx_server_url,
-        base64::encode_config(aas_uid, base64::URL_SAFE_NO_PAD) // Encode the AAS UID in a URL-safe manner
-    );
-
-    // Create a new HTTP client
-    // let client: reqwest::Client = reqwest::Client::new();
-    let client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)  // This allows SSL certificates to be ignored (only for testing purposes!)
-        .timeout(std::time::Duration::from_secs(10))  // You can add a timeout for the request
-        .build()
-        .expect("Failed to build client");
-
-    // Send a GET request to the constructed URL
-    let mut response = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?; // Map any request errors to Actix Web internal server errors
-
-    // Ensure the request was successful and returned a status code of 200 OK
-    if response.status().is_success() {
+    shell_url: &str,      // URL of the shell at the AAS server
+) -> Result<(), AppError> {
+    // Request the thumbnail of the shell, if the server has one
+    if let Some(mut response) = server.get_thumbnail(shell_url).await? {
         // Ensure the directory for storing images exists
         tokio::fs::create_dir_all("./static/asset_images/")
             .await
-            .map_err(actix_web::Error::from)?; // Convert any errors to Actix Web errors
+            .map_err(|e| AppError::Internal(format!("Failed to create ./static/asset_images/: {}", e)))?;
 
         // Create a file to store the downloaded image
         let image_name = format!("./static/asset_images/{}.png", aas_id_short);
-        let mut file = File::create(image_name)
+        let mut file = File::create(&image_name)
             .await
-            .map_err(actix_web::Error::from)?; // Convert any errors to Actix Web errors
+            .map_err(|e| AppError::Internal(format!("Failed to create {}: {}", image_name, e)))?;
 
         // Stream the bytes directly into the file
-        while let Some(chunk) = response
-            .chunk()
-            .await
-            .map_err(actix_web::error::ErrorInternalServerError)?
-        {
+        loop {
+            let Some(chunk) = response.chunk().await? else { break };
             file.write_all(&chunk)
                 .await
-                .map_err(actix_web::Error::from)?; // Write each chunk to the file, converting errors
+                .map_err(|e| AppError::Internal(format!("Failed to write {}: {}", image_name, e)))?; // Write each chunk to the file
         }
 
         println!("Successfully retrieved image");
         Ok(())
     } else {
-        // If the response status is not 200 OK, return a message
+        // The server has no thumbnail for the shell
         eprintln!("Failed to retrieve image");
         Ok(())
     }
 }
 
 async fn onboarding_managed_device(
-    aas_id_short: &str,
-    aasx_server_url: &str,
+    server: &dyn AasServerClient,
+    config: &Config,
     rocksdb: Arc<Mutex<DB>>,
-) -> Result<(), actix_web::Error> {
-    match aas_interfaces::read_managed_device(rocksdb.clone(), aas_id_short).await {
+) -> Result<(), AppError> {
+    let aas_id_short = config.aas_id_short.as_str();
+    match aas_interfaces::read_managed_device(rocksdb.clone(), config).await {
         Ok(_) => (),
         Err(e) => {
             eprintln!("Failed to read managed device: {}", e);
-            return Err(actix_web::error::ErrorInternalServerError(e));
+            return Err(e);
         }
     };
 
     let time_now = Utc::now();
-    let submodel_id_short = "ManagedDevice";
+    let submodel_id_short =
+        aas_interfaces::resolve_submodel(rocksdb.clone(), config, &config.submodels.managed_device).await;
+    let submodel_id_short = submodel_id_short.as_str();
     let json = serde_json::json!({
         "BoardingStatus": "ONBOARDED",
         "LastUpdate": time_now.to_rfc3339()
@@ -327,15 +348,20 @@ async fn onboarding_managed_device(
                 "Failed Onboarding because of error by patch submodel to database: {}",
                 e
             );
-            return Err(actix_web::error::ErrorInternalServerError(e));
+            return Err(e);
         }
     };
 
+    if !config.has_aas_server() {
+        return Ok(());
+    }
+
     match aas_interfaces::patch_submodel_server(
+        server,
         rocksdb.clone(),
         aas_id_short,
         submodel_id_short,
-        aasx_server_url,
+        config.push_mode,
         &json,
     )
     .await
@@ -346,14 +372,14 @@ async fn onboarding_managed_device(
                 "Failed Onboarding because of error by patch submodel to AAS Server: {}",
                 e
             );
-            return Err(actix_web::error::ErrorInternalServerError(e));
+            return Err(e);
         }
     };
 
     Ok(())
 }
 
-fn extract_submodels_id(data: &Value) -> Result<Vec<String>, actix_web::Error> {
+pub fn extract_submodels_id(data: &Value) -> Result<Vec<String>, AppError> {
     let mut filtered_values = Vec::new();
 
     // Access "submodels" field as an array
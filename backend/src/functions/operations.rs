@@ -0,0 +1,564 @@
+//! Invocation of `Operation` elements through the local actions configured in
+//! `operations.handlers`. Arguments are exchanged in the value-only representation, keyed
+//! by the idShort of the variables the Operation declares, and checked against them.
+//!
+//! Scripts get every input and inoutput argument as `AAS_INPUT_<idShort>` (strings as they
+//! are, other values as JSON) and all of them as a JSON object in `AAS_INPUTS`. They print
+//! a JSON object with the output arguments, or just the value if the Operation declares a
+//! single output variable.
+
+use actix_web::web;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+use super::id_short_path::{self, PathSegment};
+use super::transform_value_submodel::{merge_value_to_submodel_element, submodel_element_to_value, submodel_to_submodel_value};
+use super::{aas_interfaces, bash_command, scheduler_task};
+use crate::config::{BuiltinOperation, HttpMethod, OperationHandlerConfig};
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// Arguments by the idShort of their variable.
+type Arguments = Map<String, Value>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ExecutionState {
+    Running,
+    Completed,
+    Failed,
+    Timeout,
+}
+
+/// Value-only `OperationRequest`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OperationRequest {
+    pub input_arguments: Arguments,
+    pub inoutput_arguments: Arguments,
+}
+
+/// Value-only `OperationResult`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationResult {
+    pub execution_state: ExecutionState,
+    pub success: bool,
+    pub messages: Vec<Value>,
+    pub output_arguments: Arguments,
+    pub inoutput_arguments: Arguments,
+}
+
+impl OperationResult {
+    pub fn running() -> Self {
+        OperationResult {
+            execution_state: ExecutionState::Running,
+            success: false,
+            messages: Vec::new(),
+            output_arguments: Map::new(),
+            inoutput_arguments: Map::new(),
+        }
+    }
+
+    fn failed(execution_state: ExecutionState, text: impl ToString) -> Self {
+        let message = json!({
+            "messageType": "Error",
+            "text": text.to_string(),
+            "timestamp": Utc::now().to_rfc3339(),
+        });
+        OperationResult { execution_state, messages: vec![message], ..OperationResult::running() }
+    }
+
+    /// `BaseOperationResult` without the arguments, for `operation-status`.
+    pub fn status(&self) -> Value {
+        json!({ "executionState": self.execution_state, "success": self.success, "messages": self.messages })
+    }
+}
+
+/// An invocation checked against the Operation, ready to run.
+pub struct Invocation {
+    pub operation: String,
+    handler: OperationHandlerConfig,
+    element: Value,
+    /// Input and inoutput arguments by idShort.
+    arguments: Arguments,
+}
+
+/// Name of the Operation at `path` of a submodel, `{submodel idShort}/{idShortPath}`.
+pub fn operation_name(submodel_id_short: &str, path: &[PathSegment]) -> String {
+    format!("{}/{}", submodel_id_short, id_short_path::format(path))
+}
+
+/// Checks an invocation of the Operation at `path` of the submodel `submodel_id_short`.
+/// Arguments that do not fit the declared variables are answered with `InvalidValues`.
+pub async fn prepare(
+    app_data: &AppState,
+    submodel_id_short: &str,
+    path: &[PathSegment],
+    request: &OperationRequest,
+) -> Result<Invocation, AppError> {
+    let operation = operation_name(submodel_id_short, path);
+    let id_short_path = id_short_path::format(path);
+    let handler = app_data
+        .config
+        .operations
+        .handlers
+        .iter()
+        .find(|handler| handler.submodel == submodel_id_short && handler.path == id_short_path)
+        .ok_or_else(|| AppError::NotFound(format!("No action is configured for operation {}", operation)))?;
+
+    let submodel = aas_interfaces::get_submodel_database(app_data.rocksdb.clone(), &app_data.config.aas_id_short, submodel_id_short).await?;
+    let element = id_short_path::find_element(&submodel, path)?;
+    if element["modelType"] != "Operation" {
+        return Err(AppError::BadRequest(format!("{} is not an Operation", operation)));
+    }
+    let arguments = input_arguments(element, request).map_err(AppError::InvalidValues)?;
+
+    Ok(Invocation { operation, handler: handler.clone(), element: element.clone(), arguments })
+}
+
+/// Runs the action of an invocation, cancelled after the timeout of its handler.
+pub async fn run(app_data: &AppState, invocation: &Invocation) -> OperationResult {
+    let timeout = invocation.handler.timeout.unwrap_or(app_data.config.operations.timeout);
+    let outputs = match tokio::time::timeout(Duration::from_secs(timeout), run_action(app_data, invocation)).await {
+        Ok(outputs) => outputs,
+        Err(_) => {
+            let text = format!("Operation {} timed out after {} seconds", invocation.operation, timeout);
+            eprintln!("{}", text);
+            return OperationResult::failed(ExecutionState::Timeout, text);
+        }
+    };
+    let arguments = outputs.and_then(|outputs| {
+        output_arguments(&invocation.element, &invocation.arguments, &outputs).map_err(AppError::InvalidValues)
+    });
+
+    match arguments {
+        Ok((output_arguments, inoutput_arguments)) => OperationResult {
+            execution_state: ExecutionState::Completed,
+            success: true,
+            messages: Vec::new(),
+            output_arguments,
+            inoutput_arguments,
+        },
+        Err(e) => {
+            eprintln!("Operation {} failed: {}", invocation.operation, e);
+            OperationResult::failed(ExecutionState::Failed, e)
+        }
+    }
+}
+
+/// Runs the operation the AAS server requested through the `operations.command` property
+/// of `ManagedDevice`: `{submodel idShort}/{idShortPath}`, or a JSON object with
+/// `operation` and the arguments of an `OperationRequest`. Its result is written to
+/// `operations.command_result`.
+///
+/// The command is recorded as started before the property is cleared, so that it runs
+/// once even if clearing the property on the AAS server fails. The same command runs
+/// again only after the property was seen empty.
+pub async fn run_polled_command(app_data: web::Data<AppState>) -> Result<(), AppError> {
+    let Some(command_property) = app_data.config.operations.command.clone() else {
+        return Ok(());
+    };
+    let managed_device = aas_interfaces::read_managed_device(app_data.rocksdb.clone(), &app_data.config).await?;
+    let started = started_command(&app_data).await?;
+    let command = match submodel_to_submodel_value(managed_device).get(&command_property) {
+        Some(Value::String(command)) if !command.trim().is_empty() => command.trim().to_string(),
+        _ => {
+            if started.is_some() {
+                record_started_command(&app_data, None).await?;
+            }
+            return Ok(());
+        }
+    };
+
+    let clear = json!({ command_property: "" });
+    if started.as_deref() == Some(command.as_str()) {
+        // Started by an earlier poll, only the property is left to clear
+        return update_managed_device(&app_data, &clear).await;
+    }
+    record_started_command(&app_data, Some(&command)).await?;
+    if let Err(e) = update_managed_device(&app_data, &clear).await {
+        eprintln!("Failed to clear the command {}, retrying with the next poll: {}", command, e);
+    }
+    println!("Running operation requested by the AAS server: {}", command);
+
+    // Operations may take longer than the polling job is allowed to
+    tokio::spawn(async move {
+        let result = match parse_command(&command) {
+            Ok((submodel_id_short, path, request)) => match prepare(&app_data, &submodel_id_short, &path, &request).await {
+                Ok(invocation) => run(&app_data, &invocation).await,
+                Err(e) => OperationResult::failed(ExecutionState::Failed, e),
+            },
+            Err(e) => OperationResult::failed(ExecutionState::Failed, e),
+        };
+        let Some(result_property) = &app_data.config.operations.command_result else {
+            return;
+        };
+        let result = serde_json::to_string(&result).unwrap_or_default();
+        if let Err(e) = update_managed_device(&app_data, &json!({ result_property: result })).await {
+            eprintln!("Failed to write the result of operation {}: {}", command, e);
+        }
+    });
+    Ok(())
+}
+
+/// Key of the last command started, until the AAS server clears it.
+fn started_command_key(app_data: &AppState) -> String {
+    format!("{}:operations_command_started", app_data.config.aas_id_short)
+}
+
+async fn started_command(app_data: &AppState) -> Result<Option<String>, AppError> {
+    match aas_interfaces::aas_find_one(started_command_key(app_data), app_data.rocksdb.clone()).await {
+        Ok(Value::String(command)) => Ok(Some(command)),
+        Ok(_) | Err(AppError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+async fn record_started_command(app_data: &AppState, command: Option<&str>) -> Result<(), AppError> {
+    let command = command.map_or(Value::Null, |command| json!(command));
+    aas_interfaces::aas_update_one(started_command_key(app_data), app_data.rocksdb.clone(), command, true).await?;
+    Ok(())
+}
+
+fn parse_command(command: &str) -> Result<(String, Vec<PathSegment>, OperationRequest), AppError> {
+    let invalid = |reason: String| AppError::BadRequest(format!("Invalid command '{}': {}", command, reason));
+    let (operation, request) = if command.starts_with('{') {
+        let command: Value = serde_json::from_str(command).map_err(|e| invalid(e.to_string()))?;
+        let operation = command["operation"].as_str().unwrap_or_default().to_string();
+        (operation, serde_json::from_value(command).map_err(|e| invalid(e.to_string()))?)
+    } else {
+        (command.to_string(), OperationRequest::default())
+    };
+    let (submodel_id_short, path) = operation
+        .split_once('/')
+        .ok_or_else(|| invalid("expected {submodel idShort}/{idShortPath}".to_string()))?;
+    Ok((submodel_id_short.to_string(), id_short_path::parse(path)?, request))
+}
+
+/// Writes `value` to `ManagedDevice` in RocksDB and on the AAS server, if there is one.
+async fn update_managed_device(app_data: &AppState, value: &Value) -> Result<(), AppError> {
+    let config = &app_data.config;
+    let managed_device = aas_interfaces::resolve_submodel(app_data.rocksdb.clone(), config, &config.submodels.managed_device).await;
+    aas_interfaces::patch_submodel_database(app_data.rocksdb.clone(), &config.aas_id_short, &managed_device, value).await?;
+    if config.has_aas_server() {
+        aas_interfaces::patch_submodel_server(
+            app_data.aas_server().await.as_ref(),
+            app_data.rocksdb.clone(),
+            &config.aas_id_short,
+            &managed_device,
+            config.push_mode,
+            value,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Output arguments of the configured action, by idShort.
+async fn run_action(app_data: &AppState, invocation: &Invocation) -> Result<Arguments, AppError> {
+    let handler = &invocation.handler;
+    let arguments = &invocation.arguments;
+
+    if let Some(script) = &handler.script {
+        let mut envs = vec![("AAS_INPUTS".to_string(), Value::Object(arguments.clone()).to_string())];
+        envs.extend(arguments.iter().map(|(id_short, value)| (format!("AAS_INPUT_{}", id_short), argument_text(value))));
+        let output = bash_command::run_bash_script_with_env(script, &envs)
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        return Ok(script_outputs(&invocation.element, output.trim()));
+    }
+
+    if let Some(http) = &handler.http {
+        let client = &app_data.http_client;
+        let request = match http.method {
+            HttpMethod::Get => {
+                let query: Vec<(&String, String)> = arguments.iter().map(|(id_short, value)| (id_short, argument_text(value))).collect();
+                client.get(&http.url).query(&query)
+            }
+            HttpMethod::Post => client.post(&http.url).json(arguments),
+            HttpMethod::Put => client.put(&http.url).json(arguments),
+            HttpMethod::Patch => client.patch(&http.url).json(arguments),
+        };
+        // The action is no AAS server, it must not receive its credentials
+        let response = client.send_unauthenticated(request).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(AppError::upstream(status, format!("{} answered {}", http.url, status)));
+        }
+        let body = response.text().await?;
+        if body.trim().is_empty() {
+            return Ok(Map::new());
+        }
+        return serde_json::from_str(&body)
+            .map_err(|e| AppError::upstream(status, format!("Expected a JSON object from {}: {}", http.url, e)));
+    }
+
+    match handler.builtin {
+        Some(BuiltinOperation::Echo) => Ok(arguments.clone()),
+        Some(BuiltinOperation::SystemInformation) => {
+            let values = scheduler_task::run_script().await.map_err(|e| AppError::Internal(e.to_string()))?;
+            let config = &app_data.config;
+            let system_information =
+                aas_interfaces::resolve_submodel(app_data.rocksdb.clone(), config, &config.submodels.system_information).await;
+            aas_interfaces::patch_submodel_database(app_data.rocksdb.clone(), &config.aas_id_short, &system_information, &values)
+                .await?;
+            Ok(values.as_object().cloned().unwrap_or_default())
+        }
+        Some(BuiltinOperation::Offboard) => {
+            // Carried out by the next push, as if the AAS server had requested it
+            scheduler_task::update_boarding_status(app_data.rocksdb.clone(), app_data, "OFFBOARDING_REQUESTED", Utc::now())
+                .await
+                .map_err(|e| AppError::Internal(e.to_string()))?;
+            Ok(Map::new())
+        }
+        None => Err(AppError::Internal(format!("No action is configured for operation {}", invocation.operation))),
+    }
+}
+
+fn argument_text(value: &Value) -> String {
+    value.as_str().map_or_else(|| value.to_string(), str::to_string)
+}
+
+fn script_outputs(operation: &Value, stdout: &str) -> Arguments {
+    if let Ok(Value::Object(outputs)) = serde_json::from_str(stdout) {
+        return outputs;
+    }
+    let outputs: Vec<&str> = variables(operation, "outputVariables").map(|(id_short, _)| id_short).collect();
+    match outputs.as_slice() {
+        [id_short] if !stdout.is_empty() => Map::from_iter([(id_short.to_string(), json!(stdout))]),
+        _ => Map::new(),
+    }
+}
+
+/// Declared variables of `kind` (`inputVariables`, ...) with their idShort.
+fn variables<'a>(operation: &'a Value, kind: &str) -> impl Iterator<Item = (&'a str, &'a Value)> {
+    operation[kind]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|variable| Some((variable["value"]["idShort"].as_str()?, &variable["value"])))
+}
+
+/// Input and inoutput arguments of a request, typed by their variables. Variables left out
+/// keep the value they are declared with.
+fn input_arguments(operation: &Value, request: &OperationRequest) -> Result<Arguments, Vec<String>> {
+    let mut arguments = Map::new();
+    let mut invalid = Vec::new();
+
+    for (kind, given, name) in [
+        ("inputVariables", &request.input_arguments, "inputArguments"),
+        ("inoutputVariables", &request.inoutput_arguments, "inoutputArguments"),
+    ] {
+        for id_short in given.keys() {
+            if !variables(operation, kind).any(|(declared, _)| declared == id_short) {
+                invalid.push(format!("{}.{}: no such variable", name, id_short));
+            }
+        }
+        arguments.extend(typed_arguments(operation, kind, name, given, &Map::new(), &mut invalid));
+    }
+
+    if invalid.is_empty() {
+        Ok(arguments)
+    } else {
+        Err(invalid)
+    }
+}
+
+/// Output and inoutput arguments answered by an action, typed by their variables.
+/// Inoutput arguments it leaves out keep their input value.
+fn output_arguments(
+    operation: &Value,
+    arguments: &Arguments,
+    outputs: &Arguments,
+) -> Result<(Arguments, Arguments), Vec<String>> {
+    let mut invalid = Vec::new();
+    let output_arguments = typed_arguments(operation, "outputVariables", "outputArguments", outputs, &Map::new(), &mut invalid);
+    let inoutput_arguments = typed_arguments(operation, "inoutputVariables", "inoutputArguments", outputs, arguments, &mut invalid);
+
+    if invalid.is_empty() {
+        Ok((output_arguments, inoutput_arguments))
+    } else {
+        Err(invalid)
+    }
+}
+
+/// Values of the variables of `kind` from `values`, else from `fallback`, merged into the
+/// declared variables. Input variables missing in both keep their declared value, outputs
+/// are left out.
+fn typed_arguments(
+    operation: &Value,
+    kind: &str,
+    name: &str,
+    values: &Arguments,
+    fallback: &Arguments,
+    invalid: &mut Vec<String>,
+) -> Arguments {
+    let mut arguments = Map::new();
+    for (id_short, variable) in variables(operation, kind) {
+        let mut variable = variable.clone();
+        match values.get(id_short).or_else(|| fallback.get(id_short)) {
+            Some(value) => invalid.extend(merge_value_to_submodel_element(&mut variable, value, &format!("{}.{}", name, id_short))),
+            None if kind == "outputVariables" => continue,
+            None => (),
+        }
+        arguments.insert(id_short.to_string(), submodel_element_to_value(&variable).unwrap_or(Value::Null));
+    }
+    arguments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AuthMethod, Config, HttpOperationConfig, ServerDialect};
+    use crate::test_utils::{app_state, MockServer, TempDb};
+    use actix_web::{HttpRequest, HttpResponse};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_arguments_follow_declared_variables() {
+        let variable = |id_short: &str, value_type: &str, value: &str| {
+            json!({ "value": { "modelType": "Property", "idShort": id_short, "valueType": value_type, "value": value } })
+        };
+        let operation = json!({
+            "modelType": "Operation",
+            "idShort": "Restart",
+            "inputVariables": [variable("Delay", "xs:int", "5"), variable("Force", "xs:boolean", "false")],
+            "outputVariables": [variable("Uptime", "xs:double", "")],
+            "inoutputVariables": [variable("Counter", "xs:int", "0")]
+        });
+
+        let request: OperationRequest = serde_json::from_value(json!({
+            "inputArguments": { "Force": "1" },
+            "inoutputArguments": { "Counter": 2 }
+        }))
+        .unwrap();
+        let arguments = input_arguments(&operation, &request).unwrap();
+        assert_eq!(Value::Object(arguments.clone()), json!({ "Delay": 5, "Force": true, "Counter": 2 }));
+
+        let request: OperationRequest = serde_json::from_value(json!({ "inputArguments": { "Delay": "soon", "Mode": 1 } })).unwrap();
+        let invalid = input_arguments(&operation, &request).unwrap_err();
+        assert_eq!(invalid.len(), 2);
+        assert!(invalid[0].starts_with("inputArguments.Mode"));
+        assert!(invalid[1].starts_with("inputArguments.Delay"));
+
+        let outputs = script_outputs(&operation, "12.5");
+        let (output, inoutput) = output_arguments(&operation, &arguments, &outputs).unwrap();
+        assert_eq!(Value::Object(output), json!({ "Uptime": 12.5 }));
+        assert_eq!(Value::Object(inoutput), json!({ "Counter": 2 }));
+        assert!(output_arguments(&operation, &arguments, &script_outputs(&operation, "{\"Uptime\": \"n/a\"}")).is_err());
+
+        let (submodel, path, request) = parse_command("{\"operation\": \"Maintenance/Restart\", \"inputArguments\": {\"Delay\": 1}}").unwrap();
+        assert_eq!(operation_name(&submodel, &path), "Maintenance/Restart");
+        assert_eq!(request.input_arguments["Delay"], 1);
+        assert!(parse_command("Restart").is_err());
+    }
+
+    #[derive(Default)]
+    struct Calls {
+        clears: AtomicUsize,
+        actions: AtomicUsize,
+    }
+
+    /// AAS server failing the first clear of the command, and an action counting its calls.
+    /// Only the AAS server gets its credentials.
+    async fn mock(req: HttpRequest, calls: web::Data<Calls>) -> HttpResponse {
+        let authorization = req.headers().get("authorization").and_then(|value| value.to_str().ok());
+        if req.path() == "/action" {
+            assert_eq!(authorization, None);
+            calls.actions.fetch_add(1, Ordering::SeqCst);
+            return HttpResponse::Ok().json(json!({ "Result": "done" }));
+        }
+        assert_eq!(authorization, Some("Bearer server-secret"));
+        if req.path().ends_with("/Command/$value") && calls.clears.fetch_add(1, Ordering::SeqCst) == 0 {
+            return HttpResponse::InternalServerError().finish();
+        }
+        HttpResponse::NoContent().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_polled_command_runs_once_even_if_clearing_it_fails() {
+        let calls = web::Data::new(Calls::default());
+        let calls_state = calls.clone();
+        let server = MockServer::start(move |app| {
+            app.app_data(calls_state.clone()).default_service(web::to(mock));
+        });
+
+        let mut config = Config {
+            aas_id_short: "Test".to_string(),
+            aasx_server: server.url().to_string(),
+            aas_server_dialect: ServerDialect::Basyx,
+            ..Default::default()
+        };
+        config.server_auth.method = AuthMethod::Bearer;
+        config.server_auth.token = Some("server-secret".to_string());
+        config.operations.command = Some("Command".to_string());
+        config.operations.command_result = Some("CommandResult".to_string());
+        config.operations.handlers.push(OperationHandlerConfig {
+            submodel: "Maintenance".to_string(),
+            path: "Restart".to_string(),
+            http: Some(HttpOperationConfig { url: format!("{}action", server.url()), method: HttpMethod::Post }),
+            ..Default::default()
+        });
+        let db = TempDb::open();
+        let app_data = app_state(config, &db, true);
+
+        let property = |id_short: &str, value: &str| {
+            json!({ "modelType": "Property", "idShort": id_short, "valueType": "xs:string", "value": value })
+        };
+        let managed_device = |command: &str| {
+            json!({ "idShort": "ManagedDevice", "submodelElements": [property("Command", command), property("CommandResult", "")] })
+        };
+        let output = json!({ "value": property("Result", "") });
+        {
+            let rocksdb = db.rocksdb();
+            let db = rocksdb.lock().await;
+            db.put("Test:submodel_endpoints", json!({ "ManagedDevice": format!("{}submodels/md", server.url()) }).to_string()).unwrap();
+            db.put("Test:Maintenance", json!({
+                "idShort": "Maintenance",
+                "submodelElements": [{ "modelType": "Operation", "idShort": "Restart", "outputVariables": [output] }]
+            }).to_string()).unwrap();
+        }
+        // Each poll replaces ManagedDevice with the one of the AAS server
+        let poll = |command: &'static str| {
+            let app_data = app_data.clone();
+            let managed_device = managed_device(command);
+            async move {
+                app_data.rocksdb.lock().await.put("Test:ManagedDevice", managed_device.to_string()).unwrap();
+                run_polled_command(app_data).await
+            }
+        };
+        let result = || async {
+            for _ in 0..100 {
+                let managed_device = aas_interfaces::read_managed_device(app_data.rocksdb.clone(), &app_data.config).await.unwrap();
+                match submodel_to_submodel_value(managed_device)["CommandResult"].as_str() {
+                    Some("") | None => tokio::time::sleep(Duration::from_millis(10)).await,
+                    Some(result) => return serde_json::from_str::<Value>(result).unwrap(),
+                }
+            }
+            panic!("The command did not finish");
+        };
+
+        // Clearing the command on the AAS server fails, it runs nevertheless
+        poll("Maintenance/Restart").await.unwrap();
+        let finished = result().await;
+        assert_eq!(finished["executionState"], "Completed");
+        assert_eq!(finished["outputArguments"], json!({ "Result": "done" }));
+        // The next poll still sees it and only clears it
+        poll("Maintenance/Restart").await.unwrap();
+        assert_eq!(calls.clears.load(Ordering::SeqCst), 2);
+        poll("").await.unwrap();
+        assert_eq!(calls.actions.load(Ordering::SeqCst), 1);
+
+        // Requested again after it was cleared, it runs again
+        poll("Maintenance/Restart").await.unwrap();
+        for _ in 0..100 {
+            if calls.actions.load(Ordering::SeqCst) == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(calls.actions.load(Ordering::SeqCst), 2);
+    }
+}
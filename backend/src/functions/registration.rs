@@ -0,0 +1,302 @@
+//! Registration of the shell and submodels served by this device in an AAS Registry, so
+//! that other systems find the live data of the HTTP API.
+//!
+//! The descriptors may also carry the endpoints of the AAS server. Only the endpoints under
+//! `registration.public_url` belong to this device, everything else is left as found.
+
+use chrono::{SecondsFormat, Utc};
+use rocksdb::DB;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::aas_interfaces::aas_find_one;
+use super::discovery::encode_id;
+use crate::config::{Config, OffboardingAction};
+use crate::error::AppError;
+use crate::http_client::AasClient;
+use crate::metrics;
+
+const STATUS_EXTENSION: &str = "EdgeClientStatus";
+const HEARTBEAT_EXTENSION: &str = "EdgeClientHeartbeat";
+
+pub fn is_enabled(config: &Config) -> bool {
+    config.registration.public_url.is_some()
+}
+
+/// Whether `href` points at the HTTP API of this device.
+pub fn is_own_endpoint(config: &Config, href: &str) -> bool {
+    match &config.registration.public_url {
+        Some(public_url) => href.starts_with(public_url.as_str()) || href == public_url.trim_end_matches('/'),
+        None => false,
+    }
+}
+
+/// Creates or updates the shell descriptor and the submodel descriptors of the onboarded
+/// shell with the endpoints of this device. Called after onboarding and as heartbeat.
+pub async fn register(client: &AasClient, config: &Config, rocksdb: Arc<Mutex<DB>>) -> Result<(), AppError> {
+    let (public_url, registry_url) = targets(config)?;
+    let aas_id_short = config.aas_id_short.as_str();
+
+    let shell = aas_find_one(aas_id_short.to_string(), rocksdb.clone()).await?;
+    let aas_id = shell["id"]
+        .as_str()
+        .ok_or_else(|| AppError::Internal("The stored shell has no id".to_string()))?;
+    let dictionary = aas_find_one(format!("{}:submodels_dictionary", aas_id_short), rocksdb.clone()).await?;
+
+    let descriptor_url = format!("{}shell-descriptors/{}", registry_url, encode_id(aas_id));
+    let existing = fetch_descriptor(client, &descriptor_url).await?;
+    let registered = existing.is_some();
+    let mut descriptor = existing.unwrap_or_else(|| {
+        json!({
+            "id": aas_id,
+            "idShort": shell["idShort"],
+            "assetKind": shell["assetInformation"]["assetKind"],
+            "globalAssetId": shell["assetInformation"]["globalAssetId"],
+        })
+    });
+
+    let shell_href = format!("{}api/v3.0/shells/{}", public_url, encode_id(aas_id));
+    set_own_endpoint(config, &mut descriptor, "AAS-3.0", &shell_href);
+    set_extensions(&mut descriptor, "ONBOARDED");
+
+    let submodel_descriptors = descriptor_array(&mut descriptor, "submodelDescriptors");
+    for (submodel_id_short, submodel_id) in dictionary.as_object().into_iter().flatten() {
+        let Some(submodel_id) = submodel_id.as_str() else { continue };
+        let position = match submodel_descriptors.iter().position(|d| d["id"] == submodel_id) {
+            Some(position) => position,
+            None => {
+                let submodel = aas_find_one(format!("{}:{}", aas_id_short, submodel_id_short), rocksdb.clone())
+                    .await
+                    .unwrap_or_default();
+                let mut submodel_descriptor = json!({ "id": submodel_id, "idShort": submodel_id_short });
+                if !submodel["semanticId"].is_null() {
+                    submodel_descriptor["semanticId"] = submodel["semanticId"].clone();
+                }
+                submodel_descriptors.push(submodel_descriptor);
+                submodel_descriptors.len() - 1
+            }
+        };
+        let href = format!("{}api/v3.0/submodels/{}", public_url, encode_id(submodel_id));
+        set_own_endpoint(config, &mut submodel_descriptors[position], "SUBMODEL-3.0", &href);
+    }
+
+    if registered {
+        write_descriptor(client, client.put(&descriptor_url), "put_shell_descriptor", &descriptor).await
+    } else {
+        println!("Registering shell {} at {}", aas_id, registry_url);
+        let url = format!("{}shell-descriptors", registry_url);
+        write_descriptor(client, client.post(&url), "post_shell_descriptor", &descriptor).await
+    }
+}
+
+/// Withdraws the registration on offboarding, depending on `registration.on_offboarding`
+/// by removing the endpoints of this device or by marking them as offboarded.
+pub async fn deregister(client: &AasClient, config: &Config, rocksdb: Arc<Mutex<DB>>) -> Result<(), AppError> {
+    let (_, registry_url) = targets(config)?;
+    let shell = aas_find_one(config.aas_id_short.clone(), rocksdb).await?;
+    let aas_id = shell["id"]
+        .as_str()
+        .ok_or_else(|| AppError::Internal("The stored shell has no id".to_string()))?;
+
+    let descriptor_url = format!("{}shell-descriptors/{}", registry_url, encode_id(aas_id));
+    let Some(mut descriptor) = fetch_descriptor(client, &descriptor_url).await? else {
+        return Ok(());
+    };
+
+    if config.registration.on_offboarding == OffboardingAction::Mark {
+        set_extensions(&mut descriptor, "OFFBOARDED");
+        return write_descriptor(client, client.put(&descriptor_url), "put_shell_descriptor", &descriptor).await;
+    }
+
+    remove_own_endpoints(config, &mut descriptor);
+    remove_extensions(&mut descriptor);
+    let submodel_descriptors = descriptor_array(&mut descriptor, "submodelDescriptors");
+    for submodel_descriptor in submodel_descriptors.iter_mut() {
+        remove_own_endpoints(config, submodel_descriptor);
+    }
+    submodel_descriptors.retain(|d| d["endpoints"].as_array().is_some_and(|endpoints| !endpoints.is_empty()));
+
+    if descriptor["endpoints"].as_array().is_some_and(|endpoints| !endpoints.is_empty()) {
+        write_descriptor(client, client.put(&descriptor_url), "put_shell_descriptor", &descriptor).await
+    } else {
+        // Registered by this device alone
+        println!("Removing shell descriptor {} from {}", aas_id, registry_url);
+        let response = client.send(client.delete(&descriptor_url)).await;
+        metrics::record_server_response("delete_shell_descriptor", response.as_ref().ok().map(|r| r.status()));
+        check_response(response?, &descriptor_url).await
+    }
+}
+
+fn targets(config: &Config) -> Result<(&str, &str), AppError> {
+    let registration = &config.registration;
+    match (&registration.public_url, &registration.registry_url) {
+        (Some(public_url), Some(registry_url)) => Ok((public_url, registry_url)),
+        _ => Err(AppError::Validation("Registration is not configured".to_string())),
+    }
+}
+
+async fn fetch_descriptor(client: &AasClient, url: &str) -> Result<Option<Value>, AppError> {
+    match client.get_json(url, "get_shell_descriptor").await {
+        Ok(descriptor) => Ok(Some(descriptor)),
+        Err(AppError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+async fn write_descriptor(
+    client: &AasClient,
+    request: reqwest::RequestBuilder,
+    operation: &str,
+    descriptor: &Value,
+) -> Result<(), AppError> {
+    let response = client.send(request.json(descriptor)).await;
+    metrics::record_server_response(operation, response.as_ref().ok().map(|r| r.status()));
+    let response = response?;
+    let url = response.url().to_string();
+    check_response(response, &url).await
+}
+
+async fn check_response(response: reqwest::Response, url: &str) -> Result<(), AppError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(AppError::upstream(status, format!("Registry rejected {}: {}", url, body)))
+}
+
+/// The array `key` of a descriptor, created if missing.
+fn descriptor_array<'a>(descriptor: &'a mut Value, key: &str) -> &'a mut Vec<Value> {
+    if !descriptor[key].is_array() {
+        descriptor[key] = json!([]);
+    }
+    descriptor[key].as_array_mut().unwrap()
+}
+
+/// Replaces the endpoints of this device in a descriptor with one at `href`.
+fn set_own_endpoint(config: &Config, descriptor: &mut Value, interface: &str, href: &str) {
+    remove_own_endpoints(config, descriptor);
+    let scheme = href.split("://").next().unwrap_or("http").to_uppercase();
+    descriptor_array(descriptor, "endpoints").push(json!({
+        "interface": interface,
+        "protocolInformation": { "href": href, "endpointProtocol": scheme },
+    }));
+}
+
+fn remove_own_endpoints(config: &Config, descriptor: &mut Value) {
+    descriptor_array(descriptor, "endpoints").retain(|endpoint| {
+        endpoint["protocolInformation"]["href"]
+            .as_str()
+            .filter(|href| is_own_endpoint(config, href))
+            .is_none()
+    });
+}
+
+/// Sets the status of this device and the time of the heartbeat.
+fn set_extensions(descriptor: &mut Value, status: &str) {
+    remove_extensions(descriptor);
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let extensions = descriptor_array(descriptor, "extensions");
+    extensions.push(extension(STATUS_EXTENSION, "xs:string", status));
+    extensions.push(extension(HEARTBEAT_EXTENSION, "xs:dateTime", &now));
+}
+
+fn remove_extensions(descriptor: &mut Value) {
+    descriptor_array(descriptor, "extensions")
+        .retain(|extension| extension["name"] != STATUS_EXTENSION && extension["name"] != HEARTBEAT_EXTENSION);
+    if descriptor["extensions"].as_array().is_some_and(Vec::is_empty) {
+        if let Some(descriptor) = descriptor.as_object_mut() {
+            descriptor.remove("extensions");
+        }
+    }
+}
+
+fn extension(name: &str, value_type: &str, value: &str) -> Value {
+    json!({ "name": name, "valueType": value_type, "value": value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{aas_client, MockServer, TempDb};
+    use actix_web::{web, HttpResponse};
+
+    type Registry = web::Data<std::sync::Mutex<Option<Value>>>;
+
+    async fn get_descriptor(registry: Registry) -> HttpResponse {
+        match registry.lock().unwrap().clone() {
+            Some(descriptor) => HttpResponse::Ok().json(descriptor),
+            None => HttpResponse::NotFound().finish(),
+        }
+    }
+
+    async fn put_descriptor(registry: Registry, body: web::Json<Value>) -> HttpResponse {
+        *registry.lock().unwrap() = Some(body.into_inner());
+        HttpResponse::NoContent().finish()
+    }
+
+    async fn delete_descriptor(registry: Registry) -> HttpResponse {
+        *registry.lock().unwrap() = None;
+        HttpResponse::NoContent().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_register_and_deregister_keep_foreign_endpoints() {
+        let server_endpoint = json!({ "interface": "AAS-3.0", "protocolInformation": { "href": "http://server/shells/abc" } });
+        let registry: Registry = web::Data::new(std::sync::Mutex::new(Some(json!({
+            "id": "urn:aas:1",
+            "endpoints": [server_endpoint.clone()],
+        }))));
+        let registry_state = registry.clone();
+        let server = MockServer::start(move |app| {
+            app.app_data(registry_state.clone())
+                .route("/shell-descriptors/{id}", web::get().to(get_descriptor))
+                .route("/shell-descriptors/{id}", web::put().to(put_descriptor))
+                .route("/shell-descriptors/{id}", web::delete().to(delete_descriptor));
+        });
+
+        let db = TempDb::open();
+        let rocksdb = db.rocksdb();
+        {
+            let db = rocksdb.lock().await;
+            db.put("Test", json!({ "id": "urn:aas:1", "idShort": "Test" }).to_string()).unwrap();
+            db.put("Test:submodels_dictionary", json!({ "ManagedDevice": "urn:sm:1" }).to_string()).unwrap();
+        }
+
+        let mut config = Config { aas_id_short: "Test".to_string(), ..Default::default() };
+        config.registration.public_url = Some("http://edge:18000/".to_string());
+        config.registration.registry_url = Some(server.url().to_string());
+        let client = aas_client();
+
+        // The heartbeat replaces the endpoints of the first registration
+        register(&client, &config, rocksdb.clone()).await.unwrap();
+        register(&client, &config, rocksdb.clone()).await.unwrap();
+        let descriptor = registry.lock().unwrap().clone().unwrap();
+        assert_eq!(descriptor["endpoints"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            descriptor["endpoints"][1]["protocolInformation"]["href"],
+            format!("http://edge:18000/api/v3.0/shells/{}", encode_id("urn:aas:1"))
+        );
+        assert_eq!(descriptor["extensions"][0]["value"], "ONBOARDED");
+        let submodel_descriptors = descriptor["submodelDescriptors"].as_array().unwrap();
+        assert_eq!(submodel_descriptors.len(), 1);
+        assert_eq!(
+            submodel_descriptors[0]["endpoints"][0]["protocolInformation"]["href"],
+            format!("http://edge:18000/api/v3.0/submodels/{}", encode_id("urn:sm:1"))
+        );
+
+        config.registration.on_offboarding = OffboardingAction::Mark;
+        deregister(&client, &config, rocksdb.clone()).await.unwrap();
+        let descriptor = registry.lock().unwrap().clone().unwrap();
+        assert_eq!(descriptor["extensions"][0]["value"], "OFFBOARDED");
+
+        // Only the endpoint of the AAS server stays
+        config.registration.on_offboarding = OffboardingAction::Delete;
+        deregister(&client, &config, rocksdb.clone()).await.unwrap();
+        let descriptor = registry.lock().unwrap().clone().unwrap();
+        assert_eq!(descriptor["endpoints"], json!([server_endpoint]));
+        assert_eq!(descriptor["submodelDescriptors"], json!([]));
+        assert!(descriptor.get("extensions").is_none());
+    }
+}
@@ -0,0 +1,342 @@
+use chrono::Utc;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use rand::Rng;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::config::JobConfig;
+
+/// When a job fires.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Fixed rate, measured from the start of the previous run.
+    Interval(Duration),
+    /// Cron expression with a leading seconds field, evaluated in UTC.
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    /// Delay until the next run, or `None` if the cron expression never fires again.
+    fn next_delay(&self) -> Option<Duration> {
+        match self {
+            Schedule::Interval(interval) => Some(*interval),
+            Schedule::Cron(schedule) => {
+                let now = Utc::now();
+                let next = schedule.after(&now).next()?;
+                Some((next - now).to_std().unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// What happens when a job fires while its previous run is still going.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Drop the new run.
+    Skip,
+    /// Start the new run next to the old one.
+    Allow,
+}
+
+#[derive(Debug, Clone)]
+pub struct JobSpec {
+    pub name: String,
+    pub schedule: Schedule,
+    /// Upper bound of the random delay added before each run.
+    pub jitter: Duration,
+    /// Runs taking longer are cancelled.
+    pub timeout: Option<Duration>,
+    pub overlap: OverlapPolicy,
+}
+
+impl JobSpec {
+    /// Builds the spec of a job from its configuration, falling back to `default_interval`
+    /// (in seconds) if neither an interval nor a cron expression is configured.
+    pub fn from_config(name: &str, config: &JobConfig, default_interval: u64) -> Result<JobSpec, String> {
+        let schedule = match &config.cron {
+            Some(expression) => Schedule::Cron(Box::new(
+                cron::Schedule::from_str(expression)
+                    .map_err(|e| format!("Invalid cron expression '{}' for job {}: {}", expression, name, e))?,
+            )),
+            None => Schedule::Interval(Duration::from_secs(config.interval.unwrap_or(default_interval))),
+        };
+
+        Ok(JobSpec {
+            name: name.to_string(),
+            schedule,
+            jitter: Duration::from_millis(config.jitter_ms),
+            timeout: config.timeout.map(Duration::from_secs),
+            overlap: if config.allow_overlap { OverlapPolicy::Allow } else { OverlapPolicy::Skip },
+        })
+    }
+}
+
+type JobFn = Arc<dyn Fn() -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Runs registered jobs on their own tokio tasks.
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Vec<(JobSpec, JobFn)>,
+}
+
+/// Counts a run of a job as in flight until it ends, even if it panicked.
+struct RunningGuard(Arc<AtomicUsize>);
+
+impl RunningGuard {
+    fn new(running: &Arc<AtomicUsize>) -> Self {
+        running.fetch_add(1, Ordering::SeqCst);
+        RunningGuard(running.clone())
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler::default()
+    }
+
+    pub fn add<F, Fut>(&mut self, spec: JobSpec, job: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let job: JobFn = Arc::new(move || Box::pin(job()));
+        self.jobs.push((spec, job));
+    }
+
+    /// Starts one timer task per job.
+    pub fn start(self) -> SchedulerHandle {
+        let (stop, stopped) = watch::channel(false);
+        let tasks = self
+            .jobs
+            .into_iter()
+            .map(|(spec, job)| tokio::spawn(run_job(spec, job, stopped.clone())))
+            .collect();
+        SchedulerHandle { stop, tasks }
+    }
+}
+
+/// Stops the jobs of a started [`Scheduler`].
+pub struct SchedulerHandle {
+    stop: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl SchedulerHandle {
+    /// Starts no further runs and waits until `deadline` for the runs in flight.
+    /// Runs still going then are cancelled. Returns `false` if any run was cancelled.
+    pub async fn shutdown(self, deadline: tokio::time::Instant) -> bool {
+        let _ = self.stop.send(true);
+
+        let mut tasks = self.tasks;
+        let finished = tokio::time::timeout_at(deadline, async {
+            for task in tasks.iter_mut() {
+                let _ = task.await;
+            }
+        })
+        .await
+        .is_ok();
+
+        if !finished {
+            // Dropping the run set of a job aborts its runs
+            for task in &tasks {
+                task.abort();
+            }
+        }
+        finished
+    }
+}
+
+/// Resolves once a shutdown is requested. Never resolves if the handle was dropped instead,
+/// the jobs keep running then.
+async fn stop_requested(stopped: &mut watch::Receiver<bool>) {
+    if stopped.wait_for(|stopped| *stopped).await.is_err() {
+        std::future::pending::<()>().await
+    }
+}
+
+async fn run_job(spec: JobSpec, job: JobFn, mut stopped: watch::Receiver<bool>) {
+    // Runs in flight, several if overlapping runs are allowed
+    let running = Arc::new(AtomicUsize::new(0));
+    let mut runs = JoinSet::new();
+    let mut next_run = tokio::time::Instant::now();
+
+    loop {
+        match spec.schedule.next_delay() {
+            Some(delay) => {
+                next_run = match spec.schedule {
+                    // Keep a fixed rate instead of drifting by the time spent running
+                    Schedule::Interval(_) => (next_run + delay).max(tokio::time::Instant::now()),
+                    Schedule::Cron(_) => tokio::time::Instant::now() + delay,
+                };
+            }
+            None => {
+                eprintln!("Job {} has no upcoming run, stopping it", spec.name);
+                break;
+            }
+        }
+
+        let jitter = if spec.jitter.is_zero() {
+            Duration::ZERO
+        } else {
+            rand::thread_rng().gen_range(Duration::ZERO..=spec.jitter)
+        };
+        tokio::select! {
+            _ = tokio::time::sleep_until(next_run + jitter) => (),
+            _ = stop_requested(&mut stopped) => break,
+        }
+
+        // Forget the runs that already ended
+        while let Some(Some(_)) = runs.join_next().now_or_never() {}
+
+        if running.load(Ordering::SeqCst) > 0 && spec.overlap == OverlapPolicy::Skip {
+            eprintln!("Job {} is still running, skipping this run", spec.name);
+            continue;
+        }
+
+        let guard = RunningGuard::new(&running);
+        let run = job();
+        let name = spec.name.clone();
+        let timeout = spec.timeout;
+        runs.spawn(async move {
+            let _guard = guard;
+            match timeout {
+                Some(timeout) => {
+                    if tokio::time::timeout(timeout, run).await.is_err() {
+                        eprintln!("Job {} timed out after {:?} and was cancelled", name, timeout);
+                    }
+                }
+                None => run.await,
+            }
+        });
+    }
+
+    // Let the runs in flight end, the handle aborts this task at its deadline
+    while runs.join_next().await.is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn spec(interval_ms: u64, timeout_ms: Option<u64>) -> JobSpec {
+        JobSpec {
+            name: "test".to_string(),
+            schedule: Schedule::Interval(Duration::from_millis(interval_ms)),
+            jitter: Duration::ZERO,
+            timeout: timeout_ms.map(Duration::from_millis),
+            overlap: OverlapPolicy::Skip,
+        }
+    }
+
+    /// Job counting its started runs and the most runs in flight at once.
+    fn counted(run_ms: u64) -> (impl Fn() -> BoxFuture<'static, ()> + Send + Sync, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+        let (started, in_flight, max_in_flight) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let (started_clone, max_clone) = (started.clone(), max_in_flight.clone());
+        let job = move || {
+            let (started, in_flight, max_in_flight) = (started_clone.clone(), in_flight.clone(), max_clone.clone());
+            async move {
+                started.fetch_add(1, Ordering::SeqCst);
+                max_in_flight.fetch_max(in_flight.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(run_ms)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+            }
+            .boxed()
+        };
+        (job, started, max_in_flight)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_skips_runs_while_previous_run_is_active() {
+        let (job, started, max_in_flight) = counted(150);
+        let mut scheduler = Scheduler::new();
+        scheduler.add(spec(20, None), job);
+        scheduler.start();
+
+        // Fires at 20 ms, the run lasts until 170 ms
+        tokio::time::sleep(Duration::from_millis(130)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        // Fires again at 180 ms
+        tokio::time::sleep(Duration::from_millis(55)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_allowed_overlap_starts_runs_next_to_each_other() {
+        let (job, started, max_in_flight) = counted(50);
+        let mut scheduler = Scheduler::new();
+        scheduler.add(JobSpec { overlap: OverlapPolicy::Allow, ..spec(20, None) }, job);
+        scheduler.start();
+
+        // Fires at 20, 40, ..., 120 ms
+        tokio::time::sleep(Duration::from_millis(130)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 6);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout_cancels_run_and_frees_the_job() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let finished = Arc::new(AtomicUsize::new(0));
+        let (started_clone, finished_clone) = (started.clone(), finished.clone());
+
+        let mut scheduler = Scheduler::new();
+        scheduler.add(spec(50, Some(20)), move || {
+            let (started, finished) = (started_clone.clone(), finished_clone.clone());
+            async move {
+                started.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                finished.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        scheduler.start();
+
+        // Fires at 50, 100 and 150 ms, each run is cancelled after 20 ms
+        tokio::time::sleep(Duration::from_millis(180)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+        assert_eq!(finished.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_waits_for_runs_in_flight_until_deadline() {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let finished_clone = finished.clone();
+
+        let mut scheduler = Scheduler::new();
+        scheduler.add(spec(20, None), move || {
+            let finished = finished_clone.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                finished.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let handle = scheduler.start();
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(500);
+        assert!(handle.shutdown(deadline).await);
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+
+        let mut scheduler = Scheduler::new();
+        scheduler.add(spec(20, None), || tokio::time::sleep(Duration::from_secs(10)));
+        let handle = scheduler.start();
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let started = tokio::time::Instant::now();
+        assert!(!handle.shutdown(started + Duration::from_millis(50)).await);
+        assert_eq!(started.elapsed(), Duration::from_millis(50));
+    }
+}
@@ -1,14 +1,14 @@
 use actix_web::web;
 use chrono::{DateTime, Utc};
-use clokwerk::{Scheduler, TimeUnits};
 use serde_json::{json, Value};
 use std::sync::Arc;
-use std::time::Duration;
 use tokio::{self, sync::Mutex};
 use rocksdb::DB;
 
-use crate::functions::{aas_interfaces, bash_command};
+use crate::functions::{aas_interfaces, bash_command, concept_descriptions, operations, registration, timeseries};
+use crate::functions::scheduler::{JobSpec, Scheduler, SchedulerHandle};
 use crate::functions::transform_value_submodel::submodel_to_submodel_value;
+use crate::metrics;
 use crate::state::AppState;
 
 /// Parses a date-time string into a `DateTime<Utc>` object.
@@ -17,11 +17,18 @@ fn parse_date_time_from_string(date_time_str: &str) -> Result<DateTime<Utc>, chr
     Ok(date_time.with_timezone(&Utc))
 }
 
-/// Runs a bash script and returns the output as a JSON `Value`.
-async fn run_script() -> Result<Value, anyhow::Error> {
-    let script_output = bash_command::run_bash_script("./scripts/aas_client/sysInfo.sh").await?;
-    let json: Value = serde_json::from_str(&script_output)?;
-    Ok(json)
+/// Runs the collector script and returns its output as a JSON `Value`.
+pub async fn run_script() -> Result<Value, anyhow::Error> {
+    let timer = metrics::COLLECTOR_DURATION.start_timer();
+    let result = async {
+        let script_output = bash_command::run_bash_script("./scripts/aas_client/sysInfo.sh").await?;
+        let json: Value = serde_json::from_str(&script_output)?;
+        Ok(json)
+    }
+    .await;
+    timer.observe_duration();
+    metrics::COLLECTOR_RUNS.with_label_values(&[metrics::result_label(&result)]).inc();
+    result
 }
 
 async fn update_submodel_database(
@@ -42,41 +49,76 @@ async fn update_submodel_server(
     submodel_id_short: &str,
     json: &Value,
 ) -> Result<(), anyhow::Error> {
-    aas_interfaces::patch_submodel_server(
+    // Imported from an AASX package, nothing to keep in sync
+    if !app_data.config.has_aas_server() {
+        return Ok(());
+    }
+
+    let server = app_data.aas_server().await;
+    let result = aas_interfaces::patch_submodel_server(
+        server.as_ref(),
         rocksdb,
-        &app_data.aas_id_short,
+        &app_data.config.aas_id_short,
         submodel_id_short,
-        &app_data.aasx_server,
+        app_data.config.push_mode,
         json,
     )
-    .await
-    .map_err(|e| anyhow::anyhow!("Failed to patch submodel to AAS server: {}", e))?;
-    Ok(())
+    .await;
+
+    match result {
+        Ok(_) => {
+            app_data.health.push.succeeded().await;
+            Ok(())
+        }
+        Err(e) => {
+            app_data.health.push.failed(&e).await;
+            Err(anyhow::anyhow!("Failed to patch submodel to AAS server: {}", e))
+        }
+    }
 }
 
-async fn handle_offboarding(
+/// Sets `ManagedDevice.BoardingStatus` in RocksDB and on the AAS server.
+pub async fn update_boarding_status(
     rocksdb: Arc<Mutex<DB>>,
     app_data: &AppState,
+    boarding_status: &str,
     time_now: DateTime<Utc>,
 ) -> Result<(), anyhow::Error> {
-    let submodel_id_short = "ManagedDevice";
+    let config = &app_data.config;
+    let submodel_id_short = aas_interfaces::resolve_submodel(rocksdb.clone(), config, &config.submodels.managed_device).await;
     let json = json!({
-        "BoardingStatus": "OFFBOARDED",
+        "BoardingStatus": boarding_status,
         "LastUpdate": time_now.to_rfc3339()
     });
 
-    update_submodel_database(rocksdb.clone(), &app_data.aas_id_short, submodel_id_short, &json)
+    update_submodel_database(rocksdb.clone(), &config.aas_id_short, &submodel_id_short, &json)
         .await?;
-    update_submodel_server(rocksdb, app_data, submodel_id_short, &json).await?;
+    update_submodel_server(rocksdb, app_data, &submodel_id_short, &json).await?;
 
     Ok(())
 }
 
+async fn handle_offboarding(
+    rocksdb: Arc<Mutex<DB>>,
+    app_data: &AppState,
+    time_now: DateTime<Utc>,
+) -> Result<(), anyhow::Error> {
+    update_boarding_status(rocksdb.clone(), app_data, "OFFBOARDED", time_now).await?;
+    if registration::is_enabled(&app_data.config) {
+        registration::deregister(&app_data.http_client, &app_data.config, rocksdb)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to deregister from the AAS registry: {}", e))?;
+    }
+    Ok(())
+}
+
 async fn handle_onboarding(
     rocksdb: Arc<Mutex<DB>>,
     app_data: &AppState,
     time_now: DateTime<Utc>,
 ) -> Result<(), anyhow::Error> {
+    let config = &app_data.config;
+    let managed_device = aas_interfaces::resolve_submodel(rocksdb.clone(), config, &config.submodels.managed_device).await;
     let managed_device_json = json!({
         "BoardingStatus": "ONBOARDED",
         "LastUpdate": time_now.to_rfc3339()
@@ -84,21 +126,23 @@ async fn handle_onboarding(
 
     update_submodel_database(
         rocksdb.clone(),
-        &app_data.aas_id_short,
-        "ManagedDevice",
+        &config.aas_id_short,
+        &managed_device,
         &managed_device_json,
     )
     .await?;
     update_submodel_server(
         rocksdb.clone(),
         app_data,
-        "ManagedDevice",
+        &managed_device,
         &managed_device_json,
     )
     .await?;
 
     let json = run_script().await?;
-    update_submodel_server(rocksdb, app_data, "SystemInformation", &json).await?;
+    let system_information =
+        aas_interfaces::resolve_submodel(rocksdb.clone(), config, &config.submodels.system_information).await;
+    update_submodel_server(rocksdb, app_data, &system_information, &json).await?;
 
     Ok(())
 }
@@ -109,25 +153,35 @@ async fn handle_onboarded_status(
     app_data: &AppState,
     json: &Value,
 ) -> Result<(), anyhow::Error> {
-    update_submodel_server(rocksdb, app_data, "SystemInformation", json).await?;
+    let config = &app_data.config;
+    let system_information =
+        aas_interfaces::resolve_submodel(rocksdb.clone(), config, &config.submodels.system_information).await;
+    update_submodel_server(rocksdb, app_data, &system_information, json).await?;
     Ok(())
 }
 
 async fn server_pushing(app_data: web::Data<AppState>, rocksdb: Arc<Mutex<DB>>) {
-    let offboarding_time = app_data.offboarding_time;
+    let offboarding_time = app_data.config.offboarding_time;
 
     let json = match run_script().await {
-        Ok(json) => json,
+        Ok(json) => {
+            app_data.health.collector.succeeded().await;
+            json
+        }
         Err(e) => {
             eprintln!("Failed to execute script: {}", e);
+            app_data.health.collector.failed(&e).await;
             return;
         }
     };
 
+    let config = &app_data.config;
+    let system_information =
+        aas_interfaces::resolve_submodel(rocksdb.clone(), config, &config.submodels.system_information).await;
     if let Err(e) = update_submodel_database(
         rocksdb.clone(),
-        &app_data.aas_id_short,
-        "SystemInformation",
+        &config.aas_id_short,
+        &system_information,
         &json,
     )
     .await
@@ -136,7 +190,7 @@ async fn server_pushing(app_data: web::Data<AppState>, rocksdb: Arc<Mutex<DB>>)
         return;
     }
 
-    let managed_device_submodel = match aas_interfaces::read_managed_device(rocksdb.clone(), &app_data.aas_id_short).await {
+    let managed_device_submodel = match aas_interfaces::read_managed_device(rocksdb.clone(), &app_data.config).await {
         Ok(managed_device) => managed_device,
         Err(e) => {
             eprintln!("Failed to read managed device: {}", e);
@@ -186,54 +240,153 @@ async fn server_pushing(app_data: web::Data<AppState>, rocksdb: Arc<Mutex<DB>>)
 }
 
 async fn server_polling(app_data: web::Data<AppState>, rocksdb: Arc<Mutex<DB>>) {
-    let submodel_ids = vec!["ManagedDevice"]; // Add more submodel IDs if necessary
+    let config = &app_data.config;
+    let managed_device = aas_interfaces::resolve_submodel(rocksdb.clone(), config, &config.submodels.managed_device).await;
+    let submodel_ids = vec![managed_device]; // Add more submodels if necessary
+    let server = app_data.aas_server().await;
 
     for submodel_id_short in submodel_ids {
         match aas_interfaces::fetch_single_submodel_from_server(
-            &app_data.aasx_server,
-            &app_data.aas_id_short,
+            server.as_ref(),
+            &app_data.config.aas_id_short,
             &submodel_id_short,
             rocksdb.clone(),
         )
         .await
         {
-            Ok(_) => println!(
-                "Successfully fetched submodel {} from server",
-                submodel_id_short
-            ),
-            Err(e) => eprintln!(
-                "Failed to fetch submodel {} from server: {}",
-                submodel_id_short, e
-            ),
+            Ok(_) => {
+                println!(
+                    "Successfully fetched submodel {} from server",
+                    submodel_id_short
+                );
+                app_data.health.poll.succeeded().await;
+                if let Err(e) = operations::run_polled_command(app_data.clone()).await {
+                    eprintln!("Failed to run the operation requested by the AAS server: {}", e);
+                }
+            }
+            Err(e) => {
+                eprintln!(
+                    "Failed to fetch submodel {} from server: {}",
+                    submodel_id_short, e
+                );
+                app_data.health.poll.failed(&e).await;
+            }
         }
     }
 }
 
-pub async fn submodels_scheduler(app_state: web::Data<AppState>, rocksdb: Arc<Mutex<DB>>) {
-    let mut scheduler = Scheduler::with_tz(chrono::Utc);
+async fn timeseries_sampling(app_data: web::Data<AppState>, rocksdb: Arc<Mutex<DB>>) {
+    let config = &app_data.config;
+    let submodels = match config.timeseries_submodels.as_slice() {
+        [] => vec![aas_interfaces::resolve_submodel(rocksdb.clone(), config, &config.submodels.system_information).await],
+        submodels => submodels.to_vec(),
+    };
+    for submodel_id_short in &submodels {
+        if let Err(e) = timeseries::record_sample(
+            rocksdb.clone(),
+            &app_data.config.aas_id_short,
+            submodel_id_short,
+            app_data.config.timeseries_max_samples,
+        )
+        .await
+        {
+            eprintln!("Failed to record time series sample of {}: {}", submodel_id_short, e);
+        }
+    }
+}
+
+/// Refreshes the registration in the AAS registry while the device is not offboarded.
+async fn registration_heartbeat(app_data: web::Data<AppState>, rocksdb: Arc<Mutex<DB>>) {
+    let boarding_status = aas_interfaces::read_managed_device(rocksdb.clone(), &app_data.config)
+        .await
+        .map(submodel_to_submodel_value)
+        .ok()
+        .and_then(|value| value.get("BoardingStatus").and_then(|status| status.as_str()).map(str::to_string));
+    if boarding_status.as_deref() == Some("OFFBOARDED") {
+        return;
+    }
+
+    if let Err(e) = registration::register(&app_data.http_client, &app_data.config, rocksdb).await {
+        eprintln!("Failed to refresh the registration in the AAS registry: {}", e);
+    }
+}
+
+/// Fetches the ConceptDescriptions again, e.g. after the submodels or the server changed.
+async fn concept_descriptions_refresh(app_data: web::Data<AppState>, rocksdb: Arc<Mutex<DB>>) {
+    if let Err(e) = concept_descriptions::refresh(&app_data.http_client, &app_data.config, rocksdb).await {
+        eprintln!("Failed to refresh the ConceptDescriptions: {}", e);
+    }
+}
+
+pub async fn submodels_scheduler(
+    app_state: web::Data<AppState>,
+    rocksdb: Arc<Mutex<DB>>,
+) -> Result<SchedulerHandle, String> {
+    let config = &app_state.config;
+    let mut scheduler = Scheduler::new();
 
     let app_state_clone_push = app_state.clone();
     let rocksdb_clone_push = rocksdb.clone();
 
-    scheduler.every(5.seconds()).run(move || {
-        let task = server_pushing(app_state_clone_push.clone(), rocksdb_clone_push.clone());
-        tokio::spawn(task);
-    });
+    scheduler.add(
+        JobSpec::from_config("server_pushing", &config.jobs.server_pushing, config.client_polling_interval)?,
+        move || server_pushing(app_state_clone_push.clone(), rocksdb_clone_push.clone()),
+    );
 
-    let app_state_clone_poll = app_state.clone();
-    let rocksdb_clone_poll = rocksdb.clone();
+    if config.has_aas_server() {
+        let app_state_clone_poll = app_state.clone();
+        let rocksdb_clone_poll = rocksdb.clone();
 
-    scheduler.every(10.seconds()).run(move || {
-        let task = server_polling(app_state_clone_poll.clone(), rocksdb_clone_poll.clone());
-        tokio::spawn(task);
-    });
+        scheduler.add(
+            JobSpec::from_config("server_polling", &config.jobs.server_polling, config.server_polling_interval)?,
+            move || server_polling(app_state_clone_poll.clone(), rocksdb_clone_poll.clone()),
+        );
+    }
 
-    tokio::spawn(async move {
-        loop {
-            scheduler.run_pending();
-            tokio::time::sleep(Duration::from_millis(100)).await;
-        }
-    });
+    if config.activate_timeseries {
+        let app_state_clone_timeseries = app_state.clone();
+        let rocksdb_clone_timeseries = rocksdb.clone();
+
+        scheduler.add(
+            JobSpec::from_config("timeseries", &config.jobs.timeseries, config.timeseries_interval)?,
+            move || timeseries_sampling(app_state_clone_timeseries.clone(), rocksdb_clone_timeseries.clone()),
+        );
+    }
+
+    if registration::is_enabled(config) {
+        let app_state_clone_registration = app_state.clone();
+        let rocksdb_clone_registration = rocksdb.clone();
+
+        scheduler.add(
+            JobSpec::from_config("registration", &config.jobs.registration, config.registration.heartbeat_interval)?,
+            move || registration_heartbeat(app_state_clone_registration.clone(), rocksdb_clone_registration.clone()),
+        );
+    }
+
+    let concept_descriptions_job = &config.jobs.concept_descriptions;
+    let refresh_concept_descriptions = config.concept_descriptions.refresh_interval > 0
+        || concept_descriptions_job.interval.is_some()
+        || concept_descriptions_job.cron.is_some();
+    if concept_descriptions::is_enabled(config) && refresh_concept_descriptions {
+        let app_state_clone_concept_descriptions = app_state.clone();
+        let rocksdb_clone_concept_descriptions = rocksdb.clone();
+
+        scheduler.add(
+            JobSpec::from_config(
+                "concept_descriptions",
+                concept_descriptions_job,
+                config.concept_descriptions.refresh_interval,
+            )?,
+            move || {
+                concept_descriptions_refresh(
+                    app_state_clone_concept_descriptions.clone(),
+                    rocksdb_clone_concept_descriptions.clone(),
+                )
+            },
+        );
+    }
+
+    Ok(scheduler.start())
 }
 
 // async fn server_polling(
@@ -0,0 +1,98 @@
+use actix_web::{dev::ServerHandle, web};
+use chrono::Utc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use super::scheduler::SchedulerHandle;
+use super::{registration, scheduler_task};
+use crate::state::onboarding::OnboardingState;
+use crate::state::AppState;
+
+/// Resolves on SIGTERM or SIGINT and returns the name of the signal.
+pub async fn signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = terminate.recv() => "SIGTERM",
+            _ = tokio::signal::ctrl_c() => "SIGINT",
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "SIGINT"
+    }
+}
+
+/// Shuts the client down within `shutdown.grace_period`.
+///
+/// Stops the HTTP server and the scheduler, letting in-flight requests and runs finish
+/// until the deadline, pushes the configured `BoardingStatus` of `ManagedDevice` and
+/// withdraws the registration in the AAS registry if this run onboarded the device and
+/// finally flushes RocksDB.
+pub async fn shutdown(
+    app_state: web::Data<AppState>,
+    server: ServerHandle,
+    onboarding: JoinHandle<Option<SchedulerHandle>>,
+) {
+    let config = &app_state.config.shutdown;
+    let deadline = Instant::now() + Duration::from_secs(config.grace_period);
+
+    let scheduler = if onboarding.is_finished() {
+        onboarding.await.ok().flatten()
+    } else {
+        println!("Onboarding has not finished, aborting it");
+        onboarding.abort();
+        None
+    };
+
+    let stop_jobs = async {
+        if let Some(scheduler) = scheduler {
+            if scheduler.shutdown(deadline).await {
+                println!("All jobs stopped");
+            } else {
+                eprintln!("Jobs still running after {} seconds were cancelled", config.grace_period);
+            }
+        }
+    };
+    tokio::join!(server.stop(true), stop_jobs);
+
+    let onboarded = app_state.onboarding.status().await.state == OnboardingState::Onboarded;
+    if let (Some(boarding_status), true) = (&config.boarding_status, onboarded) {
+        let update = scheduler_task::update_boarding_status(
+            app_state.rocksdb.clone(),
+            &app_state,
+            boarding_status,
+            Utc::now(),
+        );
+        match tokio::time::timeout_at(deadline, update).await {
+            Ok(Ok(_)) => println!("Reported BoardingStatus {} to the AAS server", boarding_status),
+            Ok(Err(e)) => eprintln!("Failed to report BoardingStatus {}: {}", boarding_status, e),
+            Err(_) => eprintln!(
+                "Reporting BoardingStatus {} did not finish within {} seconds",
+                boarding_status, config.grace_period
+            ),
+        }
+    }
+
+    // The HTTP API is gone, so are the endpoints registered for it
+    if onboarded && registration::is_enabled(&app_state.config) {
+        let deregister = registration::deregister(&app_state.http_client, &app_state.config, app_state.rocksdb.clone());
+        match tokio::time::timeout_at(deadline, deregister).await {
+            Ok(Ok(_)) => println!("Withdrew the registration from the AAS registry"),
+            Ok(Err(e)) => eprintln!("Failed to withdraw the registration: {}", e),
+            Err(_) => eprintln!("Withdrawing the registration did not finish within {} seconds", config.grace_period),
+        }
+    }
+
+    let db = app_state.rocksdb.lock().await;
+    match db.flush() {
+        Ok(_) => println!("RocksDB flushed"),
+        Err(e) => eprintln!("Failed to flush RocksDB: {}", e),
+    }
+    db.cancel_all_background_work(true);
+}
@@ -0,0 +1,50 @@
+use chrono::Utc;
+use rocksdb::DB;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::error::AppError;
+use crate::functions::aas_interfaces;
+use crate::functions::transform_value_submodel::submodel_to_submodel_value;
+
+fn timeseries_id(aas_id_short: &str, submodel_id_short: &str) -> String {
+    format!("{}:timeseries:{}", aas_id_short, submodel_id_short)
+}
+
+/// Appends the current value of a locally stored submodel to its time series,
+/// keeping at most `max_samples` entries.
+pub async fn record_sample(
+    rocksdb: Arc<Mutex<DB>>,
+    aas_id_short: &str,
+    submodel_id_short: &str,
+    max_samples: usize,
+) -> Result<(), AppError> {
+    let submodel = aas_interfaces::get_submodel_database(rocksdb.clone(), aas_id_short, submodel_id_short).await?;
+
+    let _id = timeseries_id(aas_id_short, submodel_id_short);
+    let mut samples = match aas_interfaces::aas_find_one(_id.clone(), rocksdb.clone()).await {
+        Ok(Value::Array(samples)) => samples,
+        _ => Vec::new(), // No series recorded yet
+    };
+
+    samples.push(json!({
+        "timestamp": Utc::now().to_rfc3339(),
+        "value": submodel_to_submodel_value(submodel),
+    }));
+    if samples.len() > max_samples {
+        samples.drain(..samples.len() - max_samples);
+    }
+
+    aas_interfaces::aas_update_one(_id, rocksdb, Value::Array(samples), true).await?;
+    Ok(())
+}
+
+/// Returns the recorded samples of a submodel, oldest first.
+pub async fn get_samples(
+    rocksdb: Arc<Mutex<DB>>,
+    aas_id_short: &str,
+    submodel_id_short: &str,
+) -> Result<Value, AppError> {
+    aas_interfaces::aas_find_one(timeseries_id(aas_id_short, submodel_id_short), rocksdb).await
+}
@@ -1,132 +1,343 @@
-use serde_json::{json, Value};
-use basyx_rs::{prelude::SubmodelElement, submodel_element, Submodel};
-
-pub fn submodel_to_submodel_value(submodel: Value) -> Value{
-    fn submodel_elements_to_submodel_value(submodel_elements: Vec<SubmodelElement>) -> Value {
-        let mut submodel_element_value: Value = json!({});
-    
-        for submodel_element in submodel_elements.iter() {
-            match submodel_element {
-                // Handle `Property` element
-                SubmodelElement::Property(property) => {
-                    if let Some(id_short) = &property.id_short {
-                        if let Some(value) = &property.value {
-                            submodel_element_value[id_short] = json!(value);
-                        } else {
-                            submodel_element_value[id_short] = json!(null);
-                        }
-                    }
-                }
-    
-                // // Handle `MultiLanguageProperty` element
-                // SubmodelElement::MultiLanguageProperty(multi_lang_property) => {
-                //     if let Some(id_short) = &multi_lang_property.id_short {
-                //         let mut language_json = json!({});
-                //         if let Some(values) = &multi_lang_property.value {
-                //             for value in values {
-                //                 if let (Some(language), Some(text)) = (&value.language, &value.text) {
-                //                     language_json[language] = json!(text);
-                //                 }
-                //             }
-                //         }
-                //         submodel_element_value[id_short] = language_json;
-                //     }
-                // }
-    
-                // Handle `SubmodelElementCollection` recursively
-                SubmodelElement::SubmodelElementCollection(submodel_element_collection) => {
-                    if let Some(id_short) = &submodel_element_collection.id_short {
-                        if let Some(nested_elements) = &submodel_element_collection.value {
-                            let nested_value = submodel_elements_to_submodel_value(nested_elements.clone());
-                            submodel_element_value[id_short] = nested_value;
-                        }
-                    }
-                }
-    
-                // Handle other variants if necessary
-                _ => {}
-            }
+//! Value-only serialization (AAS Part 2, ValueOnly) of submodels and merging such values
+//! back. Works on the plain JSON so that every element kind survives a round trip, also
+//! the ones basyx-rs does not deserialize (annotations of relationships).
+
+use serde_json::{json, Map, Value};
+
+use super::value_type;
+
+pub fn submodel_to_submodel_value(submodel: Value) -> Value {
+    submodel_elements_to_submodel_value(&submodel["submodelElements"])
+}
+
+/// Value-only serialization of elements, an object keyed by their idShorts.
+fn submodel_elements_to_submodel_value(submodel_elements: &Value) -> Value {
+    let mut submodel_element_value = Map::new();
+
+    for submodel_element in submodel_elements.as_array().into_iter().flatten() {
+        if let (Some(id_short), Some(value)) = (element_id_short(submodel_element), submodel_element_to_value(submodel_element)) {
+            submodel_element_value.insert(id_short.to_string(), value);
         }
-    
-        submodel_element_value
     }
 
-    let submodel: Submodel = serde_json::from_value(submodel).unwrap();
-    let mut submodel_value: Value = json!({});
+    Value::Object(submodel_element_value)
+}
+
+fn element_id_short(submodel_element: &Value) -> Option<&str> {
+    submodel_element["idShort"].as_str()
+}
 
-    if let Some(submodel_elements) = submodel.submodel_elements {
-        submodel_value = submodel_elements_to_submodel_value(submodel_elements);
+/// Value-only serialization of a single element, `None` for kinds without one
+/// (`Operation`, `Capability`).
+pub fn submodel_element_to_value(submodel_element: &Value) -> Option<Value> {
+    let value = match submodel_element["modelType"].as_str()? {
+        "Property" => typed_attribute(submodel_element, "value"),
+        "ReferenceElement" => submodel_element["value"].clone(),
+        "MultiLanguageProperty" => Value::Array(
+            submodel_element["value"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|lang_string| Some(json!({ lang_string["language"].as_str()?: lang_string["text"] })))
+                .collect(),
+        ),
+        "Range" => Value::Object(
+            ["min", "max"]
+                .into_iter()
+                .filter(|key| submodel_element.get(*key).is_some_and(|value| !value.is_null()))
+                .map(|key| (key.to_string(), typed_attribute(submodel_element, key)))
+                .collect(),
+        ),
+        "File" | "Blob" => pick(submodel_element, &["contentType", "value"]),
+        "RelationshipElement" => pick(submodel_element, &["first", "second"]),
+        "AnnotatedRelationshipElement" => {
+            let mut value = pick(submodel_element, &["first", "second"]);
+            if let Some(annotations) = submodel_element["annotations"].as_array() {
+                // One single-key object per annotation
+                value["annotations"] = annotations
+                    .iter()
+                    .filter_map(|annotation| Some(json!({ element_id_short(annotation)?: submodel_element_to_value(annotation)? })))
+                    .collect();
+            }
+            value
+        }
+        "SubmodelElementCollection" => submodel_elements_to_submodel_value(&submodel_element["value"]),
+        // Items are addressed by index, so each keeps its position even without a value
+        "SubmodelElementList" => submodel_element["value"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|item| submodel_element_to_value(item).unwrap_or(Value::Null))
+            .collect(),
+        "Entity" => {
+            let mut value = pick(submodel_element, &["entityType", "globalAssetId", "specificAssetIds"]);
+            value["statements"] = submodel_elements_to_submodel_value(&submodel_element["statements"]);
+            value
+        }
+        "BasicEventElement" => pick(submodel_element, &["observed"]),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// JSON of the attribute `key` by the `valueType` of the element.
+fn typed_attribute(submodel_element: &Value, key: &str) -> Value {
+    match submodel_element[key].as_str() {
+        Some(lexical) => value_type::to_json(submodel_element["valueType"].as_str(), lexical),
+        None => submodel_element[key].clone(),
     }
+}
 
-    return submodel_value; 
+/// Object with those of `keys` that are set on `element`.
+fn pick(element: &Value, keys: &[&str]) -> Value {
+    let picked: Map<String, Value> = keys
+        .iter()
+        .filter_map(|key| Some((key.to_string(), element.get(*key).filter(|value| !value.is_null())?.clone())))
+        .collect();
+    Value::Object(picked)
 }
 
-pub fn merge_submodel_value_to_submodel(submodel: Value, submodel_value: Value) -> Value {
-    fn merge_submodel_value_to_submodel_elements(
-        submodel_elements: &mut Vec<SubmodelElement>,
-        submodel_value: Value,
-    ) {
-        for submodel_element in submodel_elements.iter_mut() {
-            match submodel_element {
-                // Match on Property variant
-                SubmodelElement::Property(ref mut property) => {
-                    if let Some(id_short) = &property.id_short {
-                        if let Some(new_value) = submodel_value.get(id_short) {
-                            if let Some(value_field) = &mut property.value {
-                                if let Some(string_value) = new_value.as_str() {
-                                    *value_field = string_value.to_string();  // Assign the new value
-                                }
-                                else {
-                                    *value_field = new_value.to_string();  // Assign the new value
-                                }
-                            } else {
-                                // If property.value is None, create a new value
-                                property.value = Some(
-                                    if let Some(string_value) = new_value.as_str() {
-                                        string_value.to_string()  // Create a new String from the string value
-                                    } else {
-                                        new_value.to_string()  // Create a new String from the JSON serialized value
-                                    }
-                                );
-                            }
-                        }
-                    }
+pub fn merge_submodel_value_to_submodel(mut submodel: Value, submodel_value: Value) -> Value {
+    // Device data is stored as it comes, also where it does not fit the valueType
+    let mut invalid = Vec::new();
+    merge_submodel_value_to_submodel_elements(submodel.get_mut("submodelElements"), &submodel_value, "", &mut invalid);
+    submodel
+}
+
+/// Paths of a value-only patch of `submodel` that do not fit it, with the reason: unknown
+/// idShorts, a wrong structure (e.g. an object for a property) or values that do not fit
+/// the `valueType` of their element. Empty if the patch fits.
+pub fn check_submodel_value(submodel: &Value, submodel_value: &Value) -> Vec<String> {
+    let mut submodel = submodel.clone();
+    let mut invalid = Vec::new();
+    merge_submodel_value_to_submodel_elements(submodel.get_mut("submodelElements"), submodel_value, "", &mut invalid);
+    invalid
+}
+
+/// Merges the value-only serialization `new_value` into the element at the idShortPath
+/// `path`. Attributes missing from `new_value` keep their current value, `null` clears
+/// them. Returns the paths that do not fit like `check_submodel_value`; values of the
+/// wrong structure are left out, values that do not fit their type are stored as given.
+pub fn merge_value_to_submodel_element(submodel_element: &mut Value, new_value: &Value, path: &str) -> Vec<String> {
+    let mut invalid = Vec::new();
+    merge_element(submodel_element, new_value, path, &mut invalid);
+    invalid
+}
+
+/// Merges an object keyed by idShorts into the matching elements. Elements without an
+/// entry are left as they are.
+fn merge_submodel_value_to_submodel_elements(
+    submodel_elements: Option<&mut Value>,
+    submodel_value: &Value,
+    path: &str,
+    invalid: &mut Vec<String>,
+) {
+    let Some(entries) = submodel_value.as_object() else {
+        invalid.push(at(path, "expected an object keyed by idShort"));
+        return;
+    };
+    let mut no_elements = Vec::new();
+    let submodel_elements = submodel_elements.and_then(Value::as_array_mut).unwrap_or(&mut no_elements);
+
+    for (id_short, new_value) in entries {
+        let path = if path.is_empty() { id_short.clone() } else { format!("{}.{}", path, id_short) };
+        match submodel_elements.iter_mut().find(|element| element_id_short(element) == Some(id_short.as_str())) {
+            Some(submodel_element) => merge_element(submodel_element, new_value, &path, invalid),
+            None => invalid.push(at(&path, "no such element")),
+        }
+    }
+}
+
+fn merge_element(submodel_element: &mut Value, new_value: &Value, path: &str, invalid: &mut Vec<String>) {
+    let model_type = submodel_element["modelType"].as_str().unwrap_or_default().to_string();
+    match model_type.as_str() {
+        "Property" => {
+            let value = lexical(submodel_element, new_value, path, invalid);
+            set_attribute(submodel_element, "value", value);
+        }
+        "MultiLanguageProperty" => {
+            // Accepts the array of single-key objects as well as one object with all languages
+            let entries: Vec<(&String, &Value)> = match new_value {
+                Value::Null => Vec::new(),
+                Value::Array(lang_strings) if lang_strings.iter().all(Value::is_object) => {
+                    lang_strings.iter().filter_map(Value::as_object).flatten().collect()
                 }
-    
-                // Match on SubmodelElementCollection variant and recurse
-                SubmodelElement::SubmodelElementCollection(ref mut submodel_element_collection) => {
-                    if let Some(id_short) = &submodel_element_collection.id_short {
-                        if let Some(new_value) = submodel_value.get(id_short) {
-                            if let Some(value_field) = &mut submodel_element_collection.value {
-                                merge_submodel_value_to_submodel_elements(value_field, new_value.clone());
-                            }
-                        }
-                    }
+                Value::Object(lang_strings) => lang_strings.iter().collect(),
+                _ => return invalid.push(at(path, "expected language strings like [{\"en\": \"Text\"}]")),
+            };
+            if entries.iter().any(|(_, text)| !text.is_string()) {
+                return invalid.push(at(path, "expected a string per language"));
+            }
+            let lang_strings: Vec<Value> = entries
+                .into_iter()
+                .map(|(language, text)| json!({ "language": language, "text": text }))
+                .collect();
+            set_attribute(submodel_element, "value", if new_value.is_null() { Value::Null } else { Value::Array(lang_strings) });
+        }
+        "Range" => {
+            if !expect_attributes(new_value, &["min", "max"], path, invalid) {
+                return;
+            }
+            for key in ["min", "max"] {
+                if let Some(value) = new_value.get(key) {
+                    let value = lexical(submodel_element, value, &format!("{} ({})", path, key), invalid);
+                    set_attribute(submodel_element, key, value);
                 }
-    
-                // Other variants are ignored
-                _ => {}
             }
         }
+        "File" | "Blob" => match new_value {
+            Value::Object(attributes) => {
+                if !expect_attributes(new_value, &["contentType", "value"], path, invalid) {
+                    return;
+                }
+                if attributes.values().any(|value| !value.is_string() && !value.is_null()) {
+                    return invalid.push(at(path, "expected strings for contentType and value"));
+                }
+                merge_attributes(submodel_element, new_value, &["contentType", "value"], Value::clone);
+            }
+            Value::String(_) | Value::Null => set_attribute(submodel_element, "value", new_value.clone()),
+            _ => invalid.push(at(path, "expected {\"contentType\", \"value\"} or the value as a string")),
+        },
+        "ReferenceElement" => {
+            if !new_value.is_null() && !is_reference(new_value) {
+                return invalid.push(at(path, "expected a reference"));
+            }
+            set_attribute(submodel_element, "value", new_value.clone());
+        }
+        "RelationshipElement" | "AnnotatedRelationshipElement" => {
+            let keys: &[&str] = match model_type.as_str() {
+                "RelationshipElement" => &["first", "second"],
+                _ => &["first", "second", "annotations"],
+            };
+            if !expect_attributes(new_value, keys, path, invalid) {
+                return;
+            }
+            if ["first", "second"].iter().any(|key| new_value.get(*key).is_some_and(|reference| !is_reference(reference))) {
+                return invalid.push(at(path, "expected references for first and second"));
+            }
+            merge_attributes(submodel_element, new_value, &["first", "second"], Value::clone);
+            if let Some(annotations) = new_value.get("annotations") {
+                let annotations = by_id_short(annotations);
+                merge_submodel_value_to_submodel_elements(submodel_element.get_mut("annotations"), &annotations, path, invalid);
+            }
+        }
+        "SubmodelElementCollection" => {
+            merge_submodel_value_to_submodel_elements(submodel_element.get_mut("value"), new_value, path, invalid)
+        }
+        "SubmodelElementList" => {
+            let Some(new_items) = new_value.as_array() else {
+                return invalid.push(at(path, "expected an array"));
+            };
+            let items = submodel_element.get_mut("value").and_then(Value::as_array_mut);
+            let item_count = items.as_ref().map_or(0, |items| items.len());
+            if new_items.len() > item_count {
+                invalid.push(at(path, &format!("has only {} items", item_count)));
+            }
+            for (index, (item, new_item)) in items.into_iter().flatten().zip(new_items).enumerate() {
+                merge_element(item, new_item, &format!("{}[{}]", path, index), invalid);
+            }
+        }
+        "Entity" => {
+            if !expect_attributes(new_value, &["statements", "entityType", "globalAssetId", "specificAssetIds"], path, invalid) {
+                return;
+            }
+            merge_attributes(submodel_element, new_value, &["entityType", "globalAssetId", "specificAssetIds"], Value::clone);
+            if let Some(statements) = new_value.get("statements") {
+                merge_submodel_value_to_submodel_elements(submodel_element.get_mut("statements"), statements, path, invalid);
+            }
+        }
+        "BasicEventElement" => {
+            if !expect_attributes(new_value, &["observed"], path, invalid) {
+                return;
+            }
+            if new_value.get("observed").is_some_and(|reference| !is_reference(reference)) {
+                return invalid.push(at(path, "expected a reference for observed"));
+            }
+            merge_attributes(submodel_element, new_value, &["observed"], Value::clone);
+        }
+        _ => invalid.push(at(path, &format!("{} has no value", model_type))),
+    }
+}
+
+/// Whether `new_value` is an object of attributes, noting those that are not in `keys`.
+fn expect_attributes(new_value: &Value, keys: &[&str], path: &str, invalid: &mut Vec<String>) -> bool {
+    let Some(attributes) = new_value.as_object() else {
+        invalid.push(at(path, &format!("expected an object with {}", keys.join(", "))));
+        return false;
+    };
+    for key in attributes.keys().filter(|key| !keys.contains(&key.as_str())) {
+        invalid.push(at(path, &format!("unknown attribute '{}'", key)));
+    }
+    true
+}
+
+fn is_reference(value: &Value) -> bool {
+    value["type"].is_string() && value["keys"].is_array()
+}
+
+fn at(path: &str, reason: &str) -> String {
+    if path.is_empty() {
+        return reason.to_string();
     }
-    
+    format!("{}: {}", path, reason)
+}
 
-    let mut submodel: Submodel = serde_json::from_value(submodel).unwrap();
-    // Copy
-    if let Some(submodel_elements) = submodel.submodel_elements.as_mut() {
-        // Directly modify `submodel_elements` via a mutable reference
-        merge_submodel_value_to_submodel_elements(submodel_elements, submodel_value);
+/// Canonical lexical form of `new_value` by the `valueType` of the element. Values that do
+/// not fit are noted in `invalid` and kept as given.
+fn lexical(submodel_element: &Value, new_value: &Value, path: &str, invalid: &mut Vec<String>) -> Value {
+    match value_type::canonicalize(submodel_element["valueType"].as_str(), new_value) {
+        Ok(lexical) => lexical.map_or(Value::Null, Value::String),
+        Err(reason) => {
+            invalid.push(at(path, &reason));
+            scalar(new_value)
+        }
     }
+}
+
+/// Values of data elements are strings in the full serialization.
+fn scalar(value: &Value) -> Value {
+    match value {
+        Value::Null | Value::String(_) => value.clone(),
+        _ => json!(value.to_string()),
+    }
+}
 
-    let merge_submodel: Value = serde_json::to_value(submodel).unwrap();
+/// Sets `key` on `element`, or removes it for `null` instead of storing "null".
+fn set_attribute(element: &mut Value, key: &str, value: Value) {
+    if let Some(element) = element.as_object_mut() {
+        if value.is_null() {
+            element.remove(key);
+        } else {
+            element.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// Sets those of `keys` that are present in `new_value`.
+fn merge_attributes(element: &mut Value, new_value: &Value, keys: &[&str], convert: fn(&Value) -> Value) {
+    for key in keys {
+        if let Some(value) = new_value.get(*key) {
+            set_attribute(element, key, convert(value));
+        }
+    }
+}
 
-    return merge_submodel;
+/// Annotations come as an array of single-key objects, merged as one object.
+fn by_id_short(annotations: &Value) -> Value {
+    match annotations {
+        Value::Array(annotations) => Value::Object(
+            annotations
+                .iter()
+                .filter_map(Value::as_object)
+                .flat_map(|annotation| annotation.clone())
+                .collect(),
+        ),
+        _ => annotations.clone(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::result;
-
     use super::*;
     use serde_json::{json, Value};
 
@@ -254,7 +465,7 @@ mod tests {
             "Hardware": {
                 "Processor": {
                     "CpuClock": "1.8 GHz",
-                    "CpuCores": "4",
+                    "CpuCores": 4,
                     "CpuTemperature": "55 °C",
                     "CpuUsage": "17 %",
                     "CpuType": "ARMv8"
@@ -439,7 +650,9 @@ mod tests {
                     "idShort": "HealthStatus"
                 },
                 {
+                    // Set although the server left it empty, see test_merge_sets_and_clears_property_values
                     "modelType": "Property",
+                    "value": "2022-01-01T12:00:00Z",
                     "valueType": "xs:dateTime",
                     "description": [
                         {
@@ -536,6 +749,29 @@ mod tests {
         println!("Expected:\n{}", serde_json::to_string_pretty(&expected_output).unwrap());
         assert_eq!(result, expected_output);
     }
+
+    #[test]
+    fn test_merge_sets_and_clears_property_values() {
+        // A value-only PATCH sets the value of a property, also if it has none yet, and
+        // null clears it instead of storing "null"
+        let submodel: Value = json!({
+            "modelType": "Submodel",
+            "submodelElements": [
+                { "modelType": "Property", "idShort": "LastUpdate", "valueType": "xs:dateTime" },
+                { "modelType": "Property", "idShort": "HealthStatus", "valueType": "xs:string", "value": "NORMAL" },
+                { "modelType": "Property", "idShort": "Comment", "valueType": "xs:string", "value": "kept" }
+            ]
+        });
+
+        let merged = merge_submodel_value_to_submodel(submodel, json!({
+            "LastUpdate": "2022-01-01T12:00:00Z",
+            "HealthStatus": null
+        }));
+        assert_eq!(merged["submodelElements"][0]["value"], "2022-01-01T12:00:00Z");
+        assert!(merged["submodelElements"][1].get("value").is_none());
+        assert_eq!(merged["submodelElements"][2]["value"], "kept");
+    }
+
     #[test]
     fn test_merge_json_objects() {
         // Define the input JSON objects
@@ -653,4 +889,81 @@ mod tests {
         println!("Expected:\n{}", serde_json::to_string_pretty(&expected_output).unwrap());
         assert_eq!(result, expected_output);
     }
+
+    #[test]
+    fn test_all_element_kinds_round_trip() {
+        let reference = json!({ "type": "ExternalReference", "keys": [{ "type": "GlobalReference", "value": "urn:x" }] });
+        let submodel: Value = json!({
+            "modelType": "Submodel",
+            "id": "urn:sm:nameplate",
+            "submodelElements": [
+                { "modelType": "MultiLanguageProperty", "idShort": "ManufacturerName", "value": [{ "language": "de", "text": "Hersteller" }] },
+                { "modelType": "Range", "idShort": "Voltage", "valueType": "xs:double", "min": "200.5" },
+                { "modelType": "File", "idShort": "Logo", "contentType": "image/png", "value": "/logo.png" },
+                { "modelType": "Blob", "idShort": "Key", "contentType": "application/octet-stream" },
+                { "modelType": "ReferenceElement", "idShort": "Link", "value": reference },
+                { "modelType": "AnnotatedRelationshipElement", "idShort": "Wiring", "first": reference, "second": reference, "annotations": [
+                    { "modelType": "Property", "idShort": "Cable", "valueType": "xs:string", "value": "A" }
+                ]},
+                { "modelType": "SubmodelElementList", "idShort": "Ports", "value": [
+                    { "modelType": "Property", "valueType": "xs:int", "value": "80" },
+                    { "modelType": "Property", "valueType": "xs:int", "value": "443" }
+                ]},
+                { "modelType": "Entity", "idShort": "Part", "entityType": "SelfManagedEntity", "globalAssetId": "urn:asset:2", "statements": [
+                    { "modelType": "Property", "idShort": "Serial", "valueType": "xs:string", "value": "42" }
+                ]},
+                { "modelType": "BasicEventElement", "idShort": "Alarm", "observed": reference, "direction": "output", "state": "on" },
+                { "modelType": "Operation", "idShort": "Reboot" }
+            ]
+        });
+
+        let value = submodel_to_submodel_value(submodel.clone());
+        assert_eq!(value, json!({
+            "ManufacturerName": [{ "de": "Hersteller" }],
+            "Voltage": { "min": 200.5 },
+            "Logo": { "contentType": "image/png", "value": "/logo.png" },
+            "Key": { "contentType": "application/octet-stream" },
+            "Link": reference,
+            "Wiring": { "first": reference, "second": reference, "annotations": [{ "Cable": "A" }] },
+            "Ports": [80, 443],
+            "Part": { "entityType": "SelfManagedEntity", "globalAssetId": "urn:asset:2", "statements": { "Serial": "42" } },
+            "Alarm": { "observed": reference }
+        }));
+        // Merging the value-only serialization back does not change anything
+        assert_eq!(merge_submodel_value_to_submodel(submodel.clone(), value), submodel);
+
+        let merged = merge_submodel_value_to_submodel(submodel.clone(), json!({
+            "ManufacturerName": [{ "de": "Hersteller" }, { "en": "Manufacturer" }],
+            "Voltage": { "max": 240 },
+            "Wiring": { "annotations": [{ "Cable": "B" }] },
+            "Ports": [null, 8443],
+            "Part": { "statements": { "Serial": "43" } }
+        }));
+        let merged = submodel_to_submodel_value(merged);
+        assert_eq!(merged["ManufacturerName"], json!([{ "de": "Hersteller" }, { "en": "Manufacturer" }]));
+        assert_eq!(merged["Voltage"], json!({ "min": 200.5, "max": 240.0 }));
+        assert_eq!(merged["Wiring"]["annotations"], json!([{ "Cable": "B" }]));
+        assert_eq!(merged["Ports"], json!([null, 8443]));
+
+        let invalid = check_submodel_value(&submodel, &json!({
+            "Ports": [80, "http", 8080],
+            "Voltage": { "max": "high", "step": 1 },
+            "Logo": 42,
+            "Part": { "statements": { "Serial": { "value": "42" } } },
+            "Reboot": true,
+            "Unknown": "x"
+        }));
+        assert_eq!(invalid, vec![
+            "Ports: has only 2 items",
+            "Ports[1]: 'http' is not a valid xs:int",
+            "Voltage: unknown attribute 'step'",
+            "Voltage (max): 'high' is not a valid xs:double",
+            "Logo: expected {\"contentType\", \"value\"} or the value as a string",
+            "Part.Serial: expected a single value",
+            "Reboot: Operation has no value",
+            "Unknown: no such element",
+        ]);
+        assert!(check_submodel_value(&submodel, &json!({ "Ports": [8080], "Voltage": { "max": 230 } })).is_empty());
+        assert_eq!(merged["Part"]["statements"]["Serial"], "43");
+    }
 }
@@ -0,0 +1,422 @@
+//! Local user accounts of the device UI and the tokens issued to them.
+//!
+//! Users are kept in the RocksDB document `{aas_id_short}:users`, keyed by name, with
+//! argon2 password hashes. The refresh tokens still valid are kept in
+//! `{aas_id_short}:refresh_tokens`, so each can be used once and revoked by logging out.
+
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header};
+use rocksdb::DB;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::api_auth::{self, REFRESH_TOKEN_TYPE};
+use crate::config::{ApiAuthAlgorithm, Config};
+use crate::error::AppError;
+use crate::functions::aas_interfaces::{aas_find_one, aas_update_one};
+
+/// Shortest password accepted for new users.
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+lazy_static::lazy_static! {
+    // Serializes the read-modify-write of the user and refresh token documents
+    static ref USERS_LOCK: Mutex<()> = Mutex::new(());
+    // Checked for unknown users, so that the answer time does not tell which users exist
+    static ref DUMMY_PASSWORD_HASH: String = Argon2::default()
+        .hash_password(b"no such user", &SaltString::generate(&mut OsRng))
+        .map(|hash| hash.to_string())
+        .unwrap_or_default();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Reads data.
+    Viewer,
+    /// Reads and changes data.
+    Operator,
+    /// Reads and changes data and manages the users.
+    Admin,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub username: String,
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub password_hash: String,
+    pub role: Role,
+    pub created_at: String,
+}
+
+impl User {
+    /// The user without the password hash, as answered by the API.
+    pub fn public(&self) -> User {
+        User { password_hash: String::new(), ..self.clone() }
+    }
+}
+
+/// Answer of `/login` and `/login/refresh`, shaped like an OAuth2 token response.
+#[derive(Debug, Serialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: &'static str,
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RefreshTokenEntry {
+    username: String,
+    expires_at: i64,
+}
+
+/// Scopes granted to a role, taken from the `api_auth` configuration.
+pub fn scopes(config: &Config, role: Role) -> String {
+    let api_auth = &config.api_auth;
+    match role {
+        Role::Viewer => api_auth.read_scope.clone(),
+        Role::Operator => format!("{} {}", api_auth.read_scope, api_auth.write_scope),
+        Role::Admin => format!("{} {} {}", api_auth.read_scope, api_auth.write_scope, api_auth.admin_scope),
+    }
+}
+
+/// Local users need the HS256 secret to sign their tokens.
+pub fn require_local_users(config: &Config) -> Result<(), AppError> {
+    if config.api_auth.algorithm == ApiAuthAlgorithm::HS256 {
+        Ok(())
+    } else {
+        Err(AppError::Conflict(format!(
+            "Local users need API_AUTH_ALGORITHM hs256, it is {}",
+            format!("{:?}", config.api_auth.algorithm).to_lowercase()
+        )))
+    }
+}
+
+/// Creates the configured admin if no user exists yet, e.g. on the first start.
+pub async fn bootstrap_admin(rocksdb: Arc<Mutex<DB>>, config: &Config) -> Result<(), AppError> {
+    let Some(password) = &config.users.admin_password else {
+        return Ok(());
+    };
+    if !load_users(rocksdb.clone(), config).await?.is_empty() {
+        return Ok(());
+    }
+    create_user(rocksdb, config, &config.users.admin_username, password, Role::Admin).await?;
+    println!("Created the admin user '{}'", config.users.admin_username);
+    Ok(())
+}
+
+pub async fn list_users(rocksdb: Arc<Mutex<DB>>, config: &Config) -> Result<Vec<User>, AppError> {
+    Ok(load_users(rocksdb, config).await?.values().map(User::public).collect())
+}
+
+pub async fn create_user(
+    rocksdb: Arc<Mutex<DB>>,
+    config: &Config,
+    username: &str,
+    password: &str,
+    role: Role,
+) -> Result<User, AppError> {
+    if username.is_empty() || !username.chars().all(|c| c.is_ascii_alphanumeric() || "._-@".contains(c)) {
+        return Err(AppError::Validation(format!("Invalid username '{}'", username)));
+    }
+    let password_hash = hash_password(password)?;
+
+    let _guard = USERS_LOCK.lock().await;
+    let mut users = load_users(rocksdb.clone(), config).await?;
+    if users.contains_key(username) {
+        return Err(AppError::Conflict(format!("User '{}' already exists", username)));
+    }
+    let user = User {
+        username: username.to_string(),
+        password_hash,
+        role,
+        created_at: Utc::now().to_rfc3339(),
+    };
+    users.insert(username.to_string(), user.clone());
+    save_users(rocksdb, config, &users).await?;
+    Ok(user.public())
+}
+
+/// Deletes a user and revokes their refresh tokens. The last admin cannot be deleted.
+pub async fn delete_user(rocksdb: Arc<Mutex<DB>>, config: &Config, username: &str) -> Result<(), AppError> {
+    let _guard = USERS_LOCK.lock().await;
+    let mut users = load_users(rocksdb.clone(), config).await?;
+    let user = users
+        .remove(username)
+        .ok_or_else(|| AppError::NotFound(format!("User '{}' not found", username)))?;
+    if user.role == Role::Admin && !users.values().any(|other| other.role == Role::Admin) {
+        return Err(AppError::Validation("The last admin cannot be deleted".to_string()));
+    }
+    save_users(rocksdb.clone(), config, &users).await?;
+
+    let mut refresh_tokens = load_refresh_tokens(rocksdb.clone(), config).await?;
+    refresh_tokens.retain(|_, entry| entry.username != username);
+    save_refresh_tokens(rocksdb, config, refresh_tokens).await
+}
+
+/// Changes the password of a user and revokes their refresh tokens. `current_password` is
+/// checked if given, admins change passwords without it.
+pub async fn set_password(
+    rocksdb: Arc<Mutex<DB>>,
+    config: &Config,
+    username: &str,
+    password: &str,
+    current_password: Option<&str>,
+) -> Result<(), AppError> {
+    let password_hash = hash_password(password)?;
+
+    let _guard = USERS_LOCK.lock().await;
+    let mut users = load_users(rocksdb.clone(), config).await?;
+    let user = users
+        .get_mut(username)
+        .ok_or_else(|| AppError::NotFound(format!("User '{}' not found", username)))?;
+    if current_password.is_some_and(|current_password| !verify_password(&user.password_hash, current_password)) {
+        return Err(AppError::Forbidden("The current password is wrong".to_string()));
+    }
+    user.password_hash = password_hash;
+    save_users(rocksdb.clone(), config, &users).await?;
+
+    let mut refresh_tokens = load_refresh_tokens(rocksdb.clone(), config).await?;
+    refresh_tokens.retain(|_, entry| entry.username != username);
+    save_refresh_tokens(rocksdb, config, refresh_tokens).await
+}
+
+/// Checks the password and issues a new token pair.
+pub async fn login(
+    rocksdb: Arc<Mutex<DB>>,
+    config: &Config,
+    username: &str,
+    password: &str,
+) -> Result<TokenPair, AppError> {
+    let users = load_users(rocksdb.clone(), config).await?;
+    let user = users.get(username);
+    let password_hash = user.map_or(DUMMY_PASSWORD_HASH.as_str(), |user| user.password_hash.as_str());
+    let valid = verify_password(password_hash, password);
+    let user = user
+        .filter(|_| valid)
+        .ok_or_else(|| AppError::Unauthorized("Invalid username or password".to_string()))?;
+
+    let _guard = USERS_LOCK.lock().await;
+    issue_tokens(rocksdb, config, user).await
+}
+
+/// Exchanges a refresh token for a new token pair. The refresh token is used up.
+pub async fn refresh(rocksdb: Arc<Mutex<DB>>, config: &Config, refresh_token: &str) -> Result<TokenPair, AppError> {
+    let jti = decode_refresh_token(config, refresh_token)?;
+
+    let _guard = USERS_LOCK.lock().await;
+    let mut refresh_tokens = load_refresh_tokens(rocksdb.clone(), config).await?;
+    let entry = refresh_tokens
+        .remove(&jti)
+        .ok_or_else(|| AppError::Unauthorized("Refresh token was already used or revoked".to_string()))?;
+    save_refresh_tokens(rocksdb.clone(), config, refresh_tokens).await?;
+
+    let users = load_users(rocksdb.clone(), config).await?;
+    let user = users
+        .get(&entry.username)
+        .ok_or_else(|| AppError::Unauthorized(format!("User '{}' no longer exists", entry.username)))?;
+    issue_tokens(rocksdb, config, user).await
+}
+
+/// Revokes a refresh token.
+pub async fn logout(rocksdb: Arc<Mutex<DB>>, config: &Config, refresh_token: &str) -> Result<(), AppError> {
+    let jti = decode_refresh_token(config, refresh_token)?;
+
+    let _guard = USERS_LOCK.lock().await;
+    let mut refresh_tokens = load_refresh_tokens(rocksdb.clone(), config).await?;
+    refresh_tokens.remove(&jti);
+    save_refresh_tokens(rocksdb, config, refresh_tokens).await
+}
+
+/// Signs an access token and a refresh token for `user`. Callers hold `USERS_LOCK`.
+async fn issue_tokens(rocksdb: Arc<Mutex<DB>>, config: &Config, user: &User) -> Result<TokenPair, AppError> {
+    let now = Utc::now().timestamp();
+    let access_lifetime = config.users.access_token_lifetime;
+    let refresh_lifetime = config.users.refresh_token_lifetime;
+    let jti = format!("{:032x}", rand::random::<u128>());
+
+    let mut access = registered_claims(config, &user.username, now, access_lifetime);
+    access.insert("role".to_string(), json!(user.role));
+    access.insert("scope".to_string(), json!(scopes(config, user.role)));
+    let mut refresh = registered_claims(config, &user.username, now, refresh_lifetime);
+    refresh.insert("typ".to_string(), json!(REFRESH_TOKEN_TYPE));
+    refresh.insert("jti".to_string(), json!(jti));
+
+    let mut refresh_tokens = load_refresh_tokens(rocksdb.clone(), config).await?;
+    refresh_tokens.insert(
+        jti,
+        RefreshTokenEntry { username: user.username.clone(), expires_at: now + refresh_lifetime as i64 },
+    );
+    save_refresh_tokens(rocksdb, config, refresh_tokens).await?;
+
+    Ok(TokenPair {
+        access_token: sign(config, &access)?,
+        refresh_token: sign(config, &refresh)?,
+        token_type: "Bearer",
+        expires_in: access_lifetime,
+    })
+}
+
+fn registered_claims(config: &Config, username: &str, now: i64, lifetime: u64) -> Map<String, Value> {
+    let mut claims = Map::new();
+    claims.insert("sub".to_string(), json!(username));
+    claims.insert("iat".to_string(), json!(now));
+    claims.insert("exp".to_string(), json!(now + lifetime as i64));
+    if let Some(issuer) = &config.api_auth.issuer {
+        claims.insert("iss".to_string(), json!(issuer));
+    }
+    if let Some(audience) = &config.api_auth.audience {
+        claims.insert("aud".to_string(), json!(audience));
+    }
+    claims
+}
+
+fn sign(config: &Config, claims: &Map<String, Value>) -> Result<String, AppError> {
+    let key = EncodingKey::from_secret(config.api_auth.secret.as_deref().unwrap_or_default().as_bytes());
+    jsonwebtoken::encode(&Header::new(Algorithm::HS256), claims, &key)
+        .map_err(|e| AppError::Internal(format!("Failed to sign token: {}", e)))
+}
+
+/// Validates a refresh token and returns its ID.
+fn decode_refresh_token(config: &Config, token: &str) -> Result<String, AppError> {
+    let key = DecodingKey::from_secret(config.api_auth.secret.as_deref().unwrap_or_default().as_bytes());
+    let validation = api_auth::validation(&config.api_auth, Algorithm::HS256);
+    let claims = jsonwebtoken::decode::<Map<String, Value>>(token, &key, &validation)
+        .map_err(|e| AppError::Unauthorized(format!("Invalid refresh token: {}", e)))?
+        .claims;
+
+    match (claims.get("typ").and_then(Value::as_str), claims.get("jti").and_then(Value::as_str)) {
+        (Some(REFRESH_TOKEN_TYPE), Some(jti)) => Ok(jti.to_string()),
+        _ => Err(AppError::Unauthorized("Not a refresh token".to_string())),
+    }
+}
+
+fn hash_password(password: &str) -> Result<String, AppError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::Validation(format!(
+            "Passwords need at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))
+}
+
+fn verify_password(password_hash: &str, password: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+        .unwrap_or(false)
+}
+
+async fn load_users(rocksdb: Arc<Mutex<DB>>, config: &Config) -> Result<BTreeMap<String, User>, AppError> {
+    load_document(rocksdb, format!("{}:users", config.aas_id_short)).await
+}
+
+async fn save_users(rocksdb: Arc<Mutex<DB>>, config: &Config, users: &BTreeMap<String, User>) -> Result<(), AppError> {
+    save_document(rocksdb, format!("{}:users", config.aas_id_short), users).await
+}
+
+async fn load_refresh_tokens(
+    rocksdb: Arc<Mutex<DB>>,
+    config: &Config,
+) -> Result<BTreeMap<String, RefreshTokenEntry>, AppError> {
+    load_document(rocksdb, format!("{}:refresh_tokens", config.aas_id_short)).await
+}
+
+/// Stores the refresh tokens, dropping the expired ones.
+async fn save_refresh_tokens(
+    rocksdb: Arc<Mutex<DB>>,
+    config: &Config,
+    mut refresh_tokens: BTreeMap<String, RefreshTokenEntry>,
+) -> Result<(), AppError> {
+    let now = Utc::now().timestamp();
+    refresh_tokens.retain(|_, entry| entry.expires_at > now);
+    save_document(rocksdb, format!("{}:refresh_tokens", config.aas_id_short), &refresh_tokens).await
+}
+
+async fn load_document<T: for<'de> Deserialize<'de> + Default>(
+    rocksdb: Arc<Mutex<DB>>,
+    id: String,
+) -> Result<T, AppError> {
+    match aas_find_one(id.clone(), rocksdb).await {
+        Ok(document) => serde_json::from_value(document)
+            .map_err(|e| AppError::Storage(format!("Invalid document '{}': {}", id, e))),
+        Err(AppError::NotFound(_)) => Ok(T::default()),
+        Err(e) => Err(e),
+    }
+}
+
+async fn save_document<T: Serialize>(rocksdb: Arc<Mutex<DB>>, id: String, document: &T) -> Result<(), AppError> {
+    let document = serde_json::to_value(document)
+        .map_err(|e| AppError::Storage(format!("Error serializing document '{}': {}", id, e)))?;
+    aas_update_one(id, rocksdb, document, true).await.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDb;
+
+    #[tokio::test]
+    async fn test_login_and_refresh_rotation() {
+        let db = TempDb::open();
+        let rocksdb = db.rocksdb();
+
+        let mut config = Config { aas_id_short: "Test".to_string(), ..Default::default() };
+        config.api_auth.algorithm = ApiAuthAlgorithm::HS256;
+        config.api_auth.secret = Some("0123456789abcdef0123456789abcdef".to_string());
+        config.users.admin_password = Some("initial-password".to_string());
+
+        bootstrap_admin(rocksdb.clone(), &config).await.unwrap();
+        // A second start keeps the existing admin
+        bootstrap_admin(rocksdb.clone(), &config).await.unwrap();
+        assert_eq!(list_users(rocksdb.clone(), &config).await.unwrap().len(), 1);
+
+        let wrong = login(rocksdb.clone(), &config, "admin", "wrong-password").await;
+        assert!(matches!(wrong, Err(AppError::Unauthorized(_))));
+        let tokens = login(rocksdb.clone(), &config, "admin", "initial-password").await.unwrap();
+
+        // The refresh token can be used once
+        let refreshed = refresh(rocksdb.clone(), &config, &tokens.refresh_token).await.unwrap();
+        assert!(matches!(
+            refresh(rocksdb.clone(), &config, &tokens.refresh_token).await,
+            Err(AppError::Unauthorized(_))
+        ));
+        // Access tokens are no refresh tokens and vice versa
+        assert!(refresh(rocksdb.clone(), &config, &refreshed.access_token).await.is_err());
+
+        logout(rocksdb.clone(), &config, &refreshed.refresh_token).await.unwrap();
+        assert!(refresh(rocksdb.clone(), &config, &refreshed.refresh_token).await.is_err());
+
+        // Unknown users fail like a wrong password
+        let unknown = login(rocksdb.clone(), &config, "nobody", "initial-password").await;
+        assert!(matches!(unknown, Err(AppError::Unauthorized(_))));
+
+        // A new password revokes the refresh tokens issued with the old one
+        let tokens = login(rocksdb.clone(), &config, "admin", "initial-password").await.unwrap();
+        let wrong = set_password(rocksdb.clone(), &config, "admin", "changed-password", Some("wrong-password")).await;
+        assert!(matches!(wrong, Err(AppError::Forbidden(_))));
+        refresh(rocksdb.clone(), &config, &tokens.refresh_token).await.unwrap();
+        let tokens = login(rocksdb.clone(), &config, "admin", "initial-password").await.unwrap();
+        set_password(rocksdb.clone(), &config, "admin", "changed-password", Some("initial-password")).await.unwrap();
+        assert!(matches!(
+            refresh(rocksdb.clone(), &config, &tokens.refresh_token).await,
+            Err(AppError::Unauthorized(_))
+        ));
+        assert!(login(rocksdb.clone(), &config, "admin", "initial-password").await.is_err());
+        login(rocksdb.clone(), &config, "admin", "changed-password").await.unwrap();
+
+        config.api_auth.algorithm = ApiAuthAlgorithm::RS256;
+        assert!(matches!(require_local_users(&config), Err(AppError::Conflict(_))));
+    }
+}
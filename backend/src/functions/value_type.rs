@@ -0,0 +1,161 @@
+//! Values of data elements by their XSD `valueType`: stored as the lexical string of the
+//! full serialization, served as JSON numbers, booleans and normalized timestamps in the
+//! value-only one.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+enum Kind {
+    Integer { min: i128, max: i128 },
+    Decimal,
+    Double,
+    Float,
+    Boolean,
+    DateTime,
+    Date,
+    Time,
+    Text,
+}
+
+fn kind(value_type: Option<&str>) -> Kind {
+    let integer = |min: i128, max: i128| Kind::Integer { min, max };
+    match value_type.unwrap_or_default() {
+        "xs:byte" => integer(i8::MIN.into(), i8::MAX.into()),
+        "xs:short" => integer(i16::MIN.into(), i16::MAX.into()),
+        "xs:int" => integer(i32::MIN.into(), i32::MAX.into()),
+        "xs:long" => integer(i64::MIN.into(), i64::MAX.into()),
+        "xs:unsignedByte" => integer(0, u8::MAX.into()),
+        "xs:unsignedShort" => integer(0, u16::MAX.into()),
+        "xs:unsignedInt" => integer(0, u32::MAX.into()),
+        "xs:unsignedLong" => integer(0, u64::MAX.into()),
+        "xs:integer" => integer(i128::MIN, i128::MAX),
+        "xs:nonNegativeInteger" => integer(0, i128::MAX),
+        "xs:positiveInteger" => integer(1, i128::MAX),
+        "xs:nonPositiveInteger" => integer(i128::MIN, 0),
+        "xs:negativeInteger" => integer(i128::MIN, -1),
+        "xs:decimal" => Kind::Decimal,
+        "xs:double" => Kind::Double,
+        "xs:float" => Kind::Float,
+        "xs:boolean" => Kind::Boolean,
+        "xs:dateTime" => Kind::DateTime,
+        "xs:date" => Kind::Date,
+        "xs:time" => Kind::Time,
+        _ => Kind::Text,
+    }
+}
+
+/// Value-only JSON of the stored `lexical` value. Values that do not match their type are
+/// served as the string they are stored as.
+pub fn to_json(value_type: Option<&str>, lexical: &str) -> Value {
+    let Ok(Some(canonical)) = canonicalize(value_type, &json!(lexical)) else {
+        return json!(lexical);
+    };
+    let typed = match kind(value_type) {
+        Kind::Integer { .. } => canonical.parse::<i64>().map(Value::from).ok(),
+        Kind::Decimal | Kind::Double | Kind::Float => canonical
+            .parse::<f64>()
+            .ok()
+            .filter(|number| number.is_finite())
+            .map(Value::from),
+        Kind::Boolean => Some(json!(canonical == "true")),
+        _ => None,
+    };
+    // Integers beyond i64 and the special floats stay strings
+    typed.unwrap_or(Value::String(canonical))
+}
+
+/// Canonical lexical form of a value-only `value` for `value_type`, `None` for `null`, or
+/// why it does not fit the type.
+pub fn canonicalize(value_type: Option<&str>, value: &Value) -> Result<Option<String>, String> {
+    let lexical = match value {
+        Value::Null => return Ok(None),
+        Value::String(lexical) => lexical.trim().to_string(),
+        Value::Number(_) | Value::Bool(_) => value.to_string(),
+        Value::Array(_) | Value::Object(_) => return Err("expected a single value".to_string()),
+    };
+    let type_name = value_type.unwrap_or("xs:string");
+    let invalid = || format!("'{}' is not a valid {}", lexical, type_name);
+
+    let canonical = match kind(value_type) {
+        Kind::Text => return Ok(Some(value.as_str().map_or(lexical, str::to_string))),
+        Kind::Integer { min, max } => {
+            let integer: i128 = lexical.parse().map_err(|_| invalid())?;
+            if integer < min || integer > max {
+                return Err(format!("{} is out of range for {}", integer, type_name));
+            }
+            integer.to_string()
+        }
+        Kind::Decimal => {
+            let digits = lexical.strip_prefix(['+', '-']).unwrap_or(&lexical);
+            let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+            let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+            if whole.len() + fraction.len() == 0 || !all_digits(whole) || !all_digits(fraction) {
+                return Err(invalid());
+            }
+            lexical.strip_prefix('+').unwrap_or(&lexical).to_string()
+        }
+        Kind::Double | Kind::Float => {
+            let number: f64 = match lexical.as_str() {
+                "INF" | "+INF" => f64::INFINITY,
+                "-INF" => f64::NEG_INFINITY,
+                "NaN" => f64::NAN,
+                _ if lexical.chars().any(char::is_alphabetic) && !lexical.contains(['e', 'E']) => return Err(invalid()),
+                _ => lexical.parse().map_err(|_| invalid())?,
+            };
+            if matches!(kind(value_type), Kind::Float) && number.is_finite() && number.abs() > f32::MAX.into() {
+                return Err(format!("{} is out of range for {}", lexical, type_name));
+            }
+            if number.is_nan() {
+                "NaN".to_string()
+            } else if number.is_infinite() {
+                if number > 0.0 { "INF" } else { "-INF" }.to_string()
+            } else {
+                json!(number).to_string()
+            }
+        }
+        Kind::Boolean => match lexical.as_str() {
+            "true" | "1" => "true".to_string(),
+            "false" | "0" => "false".to_string(),
+            _ => return Err(invalid()),
+        },
+        Kind::DateTime => match DateTime::parse_from_rfc3339(&lexical) {
+            Ok(date_time) => date_time.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            // Without a timezone the time is kept as local time
+            Err(_) => NaiveDateTime::parse_from_str(&lexical, "%Y-%m-%dT%H:%M:%S%.f")
+                .map_err(|_| invalid())?
+                .format("%Y-%m-%dT%H:%M:%S%.f")
+                .to_string(),
+        },
+        Kind::Date => NaiveDate::parse_from_str(&lexical, "%Y-%m-%d").map_err(|_| invalid())?.to_string(),
+        Kind::Time => NaiveTime::parse_from_str(&lexical, "%H:%M:%S%.f").map_err(|_| invalid())?.to_string(),
+    };
+    Ok(Some(canonical))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json_and_canonicalize() {
+        assert_eq!(to_json(Some("xs:integer"), "4"), json!(4));
+        assert_eq!(to_json(Some("xs:double"), "1.50"), json!(1.5));
+        assert_eq!(to_json(Some("xs:boolean"), "1"), json!(true));
+        assert_eq!(to_json(Some("xs:dateTime"), "2024-01-01T02:00:00.500+02:00"), json!("2024-01-01T00:00:00.500Z"));
+        assert_eq!(to_json(Some("xs:int"), "n/a"), json!("n/a"));
+        assert_eq!(to_json(Some("xs:string"), "4"), json!("4"));
+
+        assert_eq!(canonicalize(Some("xs:int"), &json!(8)), Ok(Some("8".to_string())));
+        assert_eq!(canonicalize(Some("xs:int"), &json!("+08")), Ok(Some("8".to_string())));
+        assert_eq!(canonicalize(Some("xs:boolean"), &json!(false)), Ok(Some("false".to_string())));
+        assert_eq!(canonicalize(Some("xs:double"), &json!("-INF")), Ok(Some("-INF".to_string())));
+        assert_eq!(canonicalize(Some("xs:decimal"), &json!("+1.20")), Ok(Some("1.20".to_string())));
+        assert_eq!(canonicalize(Some("xs:string"), &json!(null)), Ok(None));
+        assert!(canonicalize(Some("xs:unsignedByte"), &json!(256)).unwrap_err().contains("out of range"));
+        assert!(canonicalize(Some("xs:int"), &json!(1.5)).is_err());
+        assert!(canonicalize(Some("xs:double"), &json!("infinity")).is_err());
+        assert!(canonicalize(Some("xs:float"), &json!(1e300)).is_err());
+        assert!(canonicalize(Some("xs:dateTime"), &json!("yesterday")).is_err());
+        assert!(canonicalize(Some("xs:string"), &json!({ "a": 1 })).is_err());
+    }
+}
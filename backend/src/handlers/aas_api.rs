@@ -0,0 +1,172 @@
+//! AAS API v3 (Part 2) subset under `/api/v3.0`, served from the cached shell and
+//! submodels so that standard AAS tools can talk to the device directly.
+
+use actix_web::{web, HttpResponse};
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::functions::aas_api::{self, Modifiers, Paging, SubmodelFilter};
+use crate::functions::{aas_interfaces, concept_descriptions};
+use crate::functions::discovery::decode_id;
+use crate::functions::id_short_path;
+use crate::handlers::onboarding::require_local_data;
+use crate::state::AppState;
+
+pub async fn get_shells(app_state: web::Data<AppState>, paging: web::Query<Paging>) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_state).await?;
+    let shell = aas_interfaces::aas_find_one(app_state.config.aas_id_short.clone(), app_state.rocksdb.clone()).await?;
+    Ok(HttpResponse::Ok().json(aas_api::paged(vec![shell], &paging)?))
+}
+
+pub async fn get_shell(app_state: web::Data<AppState>, aas_id: web::Path<String>) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_state).await?;
+    let shell = aas_api::find_shell(app_state.rocksdb.clone(), &app_state.config.aas_id_short, &decode_id(&aas_id)?).await?;
+    Ok(HttpResponse::Ok().json(shell))
+}
+
+pub async fn get_submodel_refs(
+    app_state: web::Data<AppState>,
+    aas_id: web::Path<String>,
+    paging: web::Query<Paging>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_state).await?;
+    let shell = aas_api::find_shell(app_state.rocksdb.clone(), &app_state.config.aas_id_short, &decode_id(&aas_id)?).await?;
+    let references = shell["submodels"].as_array().cloned().unwrap_or_default();
+    Ok(HttpResponse::Ok().json(aas_api::paged(references, &paging)?))
+}
+
+pub async fn get_submodels(
+    app_state: web::Data<AppState>,
+    modifiers: web::Query<Modifiers>,
+    paging: web::Query<Paging>,
+    filter: web::Query<SubmodelFilter>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_state).await?;
+    let aas_id_short = &app_state.config.aas_id_short;
+    let dictionary = aas_interfaces::aas_find_one(format!("{}:submodels_dictionary", aas_id_short), app_state.rocksdb.clone()).await?;
+    let semantic_id = filter.semantic_id.as_deref().map(decode_id).transpose()?;
+
+    let mut submodels = Vec::new();
+    for submodel_id_short in dictionary.as_object().into_iter().flat_map(|dictionary| dictionary.keys()) {
+        let submodel = aas_interfaces::get_submodel_database(app_state.rocksdb.clone(), aas_id_short, submodel_id_short).await?;
+        if semantic_id.is_some() && aas_interfaces::semantic_id(&submodel) != semantic_id.as_deref() {
+            continue;
+        }
+        submodels.push(aas_api::submodel(submodel, &modifiers)?);
+    }
+    Ok(HttpResponse::Ok().json(aas_api::paged(submodels, &paging)?))
+}
+
+pub async fn get_submodel(
+    app_state: web::Data<AppState>,
+    submodel_id: web::Path<String>,
+    modifiers: web::Query<Modifiers>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_state).await?;
+    let (_, submodel) = aas_api::find_submodel(app_state.rocksdb.clone(), &app_state.config.aas_id_short, &decode_id(&submodel_id)?).await?;
+    Ok(HttpResponse::Ok().json(aas_api::submodel(submodel, &modifiers)?))
+}
+
+pub async fn get_submodel_value(
+    app_state: web::Data<AppState>,
+    submodel_id: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_state).await?;
+    let (_, submodel) = aas_api::find_submodel(app_state.rocksdb.clone(), &app_state.config.aas_id_short, &decode_id(&submodel_id)?).await?;
+    let modifiers = Modifiers { content: aas_api::Content::Value, ..Default::default() };
+    Ok(HttpResponse::Ok().json(aas_api::submodel(submodel, &modifiers)?))
+}
+
+pub async fn get_submodel_elements(
+    app_state: web::Data<AppState>,
+    submodel_id: web::Path<String>,
+    modifiers: web::Query<Modifiers>,
+    paging: web::Query<Paging>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_state).await?;
+    let (_, submodel) = aas_api::find_submodel(app_state.rocksdb.clone(), &app_state.config.aas_id_short, &decode_id(&submodel_id)?).await?;
+
+    let mut elements = Vec::new();
+    for element in submodel["submodelElements"].as_array().into_iter().flatten() {
+        let Some(id_short) = element["idShort"].as_str() else { continue };
+        let path = [id_short_path::PathSegment::IdShort(id_short.to_string())];
+        elements.push(aas_api::submodel_element(&submodel, &path, &modifiers)?);
+    }
+    Ok(HttpResponse::Ok().json(aas_api::paged(elements, &paging)?))
+}
+
+pub async fn get_submodel_element(
+    app_state: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    modifiers: web::Query<Modifiers>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_state).await?;
+    let (submodel_id, id_short_path) = path.into_inner();
+    let (_, submodel) = aas_api::find_submodel(app_state.rocksdb.clone(), &app_state.config.aas_id_short, &decode_id(&submodel_id)?).await?;
+    let path = id_short_path::parse(&id_short_path)?;
+    Ok(HttpResponse::Ok().json(aas_api::submodel_element(&submodel, &path, &modifiers)?))
+}
+
+pub async fn get_submodel_element_value(
+    app_state: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_state).await?;
+    let (submodel_id, id_short_path) = path.into_inner();
+    let (_, submodel) = aas_api::find_submodel(app_state.rocksdb.clone(), &app_state.config.aas_id_short, &decode_id(&submodel_id)?).await?;
+    let element = id_short_path::find_element(&submodel, &id_short_path::parse(&id_short_path)?)?;
+    Ok(HttpResponse::Ok().json(aas_api::element_value(element)))
+}
+
+/// Handler to set the value of an element, written to RocksDB and pushed to the AAS server.
+pub async fn patch_submodel_element_value(
+    app_state: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    value: web::Json<Value>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_state).await?;
+    let (submodel_id, id_short_path) = path.into_inner();
+    let (submodel_id_short, _) =
+        aas_api::find_submodel(app_state.rocksdb.clone(), &app_state.config.aas_id_short, &decode_id(&submodel_id)?).await?;
+    let path = id_short_path::parse(&id_short_path)?;
+    aas_api::patch_element_value(
+        app_state.aas_server().await.as_ref(),
+        &app_state.config,
+        app_state.rocksdb.clone(),
+        &submodel_id_short,
+        &path,
+        value.into_inner(),
+    )
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_concept_descriptions(
+    app_state: web::Data<AppState>,
+    paging: web::Query<Paging>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(aas_api::paged(cached_concept_descriptions(&app_state).await?, &paging)?))
+}
+
+/// The cached ConceptDescriptions as a plain array, for the legacy `/concept-descriptions`
+/// route. A single one is served by `get_concept_description` on both routes.
+pub async fn get_concept_descriptions_array(app_state: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(cached_concept_descriptions(&app_state).await?))
+}
+
+async fn cached_concept_descriptions(app_state: &AppState) -> Result<Vec<Value>, AppError> {
+    require_local_data(app_state).await?;
+    let cached = concept_descriptions::cached(app_state.rocksdb.clone(), &app_state.config.aas_id_short).await?;
+    Ok(cached.into_iter().map(|(_, concept_description)| concept_description).collect())
+}
+
+pub async fn get_concept_description(
+    app_state: web::Data<AppState>,
+    cd_id: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_state).await?;
+    let concept_description =
+        concept_descriptions::find(app_state.rocksdb.clone(), &app_state.config.aas_id_short, &decode_id(&cd_id)?).await?;
+    Ok(HttpResponse::Ok().json(concept_description))
+}
@@ -0,0 +1,20 @@
+use actix_web::{web::Data, HttpResponse};
+
+use crate::error::AppError;
+use crate::functions::aasx;
+use crate::handlers::onboarding::require_local_data;
+use crate::state::AppState;
+
+/// Handler to download the cached shell and submodels as an AASX package.
+pub async fn get_aasx(app_data: Data<AppState>) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_data).await?;
+
+    let package = aasx::export_package(&app_data.config, app_data.rocksdb.clone()).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/asset-administration-shell-package")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}.aasx\"", app_data.config.aas_id_short),
+        ))
+        .body(package))
+}
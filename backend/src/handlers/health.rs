@@ -0,0 +1,195 @@
+use actix_web::{web::Data, HttpResponse, Responder};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::http_client::AasClient;
+use crate::metrics;
+use crate::state::health::Activity;
+use crate::state::AppState;
+
+const UP: &str = "UP";
+const DOWN: &str = "DOWN";
+const UNKNOWN: &str = "UNKNOWN";
+/// How long the probes wait for the RocksDB lock before reporting `DOWN`.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Liveness probe. Fails only if the client is wedged, i.e. the RocksDB lock shared by
+/// every job and handler is not released, so that a restart is the right reaction.
+pub async fn live(app_data: Data<AppState>) -> impl Responder {
+    match tokio::time::timeout(LOCK_TIMEOUT, app_data.rocksdb.lock()).await {
+        Ok(_) => HttpResponse::Ok().json(json!({ "status": UP })),
+        Err(_) => HttpResponse::ServiceUnavailable().json(json!({
+            "status": DOWN,
+            "message": format!("RocksDB lock has not been released for {} seconds", LOCK_TIMEOUT.as_secs()),
+        })),
+    }
+}
+
+/// Readiness probe.
+///
+/// Answers 503 (`DOWN`) if RocksDB is not accessible or no local data exists yet, as
+/// nothing can be served then. An unreachable AAS server, stale pushes or polls and a
+/// failing collector script are reported as `DEGRADED` with 200, since the device keeps
+/// serving its local data.
+pub async fn ready(app_data: Data<AppState>) -> impl Responder {
+    let config = &app_data.config;
+
+    let rocksdb = check_rocksdb(&app_data).await;
+    // Without AASX_SERVER the registry is the server the client depends on
+    let aas_server_url = config.discovery.registry_url.as_deref().filter(|_| config.aasx_server.is_empty());
+    let aas_server = if config.has_aas_server() {
+        check_aas_server(
+            &app_data.http_client,
+            aas_server_url.unwrap_or(&config.aasx_server),
+            config.health.server_timeout,
+        )
+        .await
+    } else {
+        json!({ "status": UNKNOWN, "message": "No AAS server configured, onboarded from an AASX package" })
+    };
+    let onboarding = app_data.onboarding.status().await;
+    let local_data = json!({
+        "status": if onboarding.local_data { UP } else { DOWN },
+        "onboarding": onboarding,
+    });
+    let push = check_activity(
+        &app_data.health.push,
+        config.health.max_push_age.unwrap_or(3 * config.client_polling_interval),
+    )
+    .await;
+    let poll = check_activity(
+        &app_data.health.poll,
+        config.health.max_poll_age.unwrap_or(3 * config.server_polling_interval),
+    )
+    .await;
+    let collector = {
+        let activity = &app_data.health.collector;
+        let status = if activity.is_failing().await { DOWN } else if activity.age().await.is_some() { UP } else { UNKNOWN };
+        json!({ "status": status, "details": activity.to_json().await })
+    };
+
+    let is_down = |check: &Value| check["status"] == DOWN;
+    let (status, ready) = if is_down(&rocksdb) || is_down(&local_data) {
+        (DOWN, false)
+    } else if [&aas_server, &push, &poll, &collector].into_iter().any(is_down) {
+        ("DEGRADED", true)
+    } else {
+        (UP, true)
+    };
+
+    let body = json!({
+        "status": status,
+        "checks": {
+            "rocksdb": rocksdb,
+            "localData": local_data,
+            "aasServer": aas_server,
+            "push": push,
+            "poll": poll,
+            "collector": collector,
+        }
+    });
+
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+/// Reads the shell, which fails if RocksDB is not accessible. A read keeps frequent
+/// probes from writing to the flash storage of the device. A lock that is not released in
+/// time counts as not accessible, so that the probe answers before its own timeout.
+async fn check_rocksdb(app_data: &AppState) -> Value {
+    let Ok(db) = tokio::time::timeout(LOCK_TIMEOUT, app_data.rocksdb.lock()).await else {
+        return json!({
+            "status": DOWN,
+            "message": format!("RocksDB lock has not been released for {} seconds", LOCK_TIMEOUT.as_secs()),
+        });
+    };
+    metrics::record_rocksdb_read();
+    match db.get(&app_data.config.aas_id_short) {
+        Ok(_) => json!({ "status": UP }),
+        Err(e) => json!({ "status": DOWN, "message": e.to_string() }),
+    }
+}
+
+/// Any HTTP answer below 500 counts as reachable, servers differ in what they serve at `/description`.
+async fn check_aas_server(client: &AasClient, aasx_server: &str, timeout: u64) -> Value {
+    let url = format!("{}description", aasx_server);
+
+    let response = client.send(client.get(&url).timeout(Duration::from_secs(timeout))).await;
+    metrics::record_server_response("get_description", response.as_ref().ok().map(|r| r.status()));
+
+    match response {
+        Ok(response) if !response.status().is_server_error() => {
+            json!({ "status": UP, "url": url, "httpStatus": response.status().as_u16() })
+        }
+        Ok(response) => json!({ "status": DOWN, "url": url, "httpStatus": response.status().as_u16() }),
+        Err(e) => json!({ "status": DOWN, "url": url, "message": e.to_string() }),
+    }
+}
+
+async fn check_activity(activity: &Activity, max_age: u64) -> Value {
+    let age = activity.age().await;
+    let status = if activity.is_failing().await {
+        DOWN
+    } else {
+        match age {
+            None => UNKNOWN, // Nothing ran yet
+            Some(age) if age > max_age as i64 => DOWN,
+            Some(_) => UP,
+        }
+    };
+
+    json!({
+        "status": status,
+        "ageSeconds": age,
+        "maxAgeSeconds": max_age,
+        "details": activity.to_json().await,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::routes;
+    use crate::test_utils::{app_state, TempDb};
+    use actix_web::{test, web, App};
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn test_ready_depends_on_local_data() {
+        let db = TempDb::open();
+        // Without an AAS server only RocksDB and the local data decide
+        let config = Config { aas_id_short: "Test".to_string(), ..Default::default() };
+        let state = app_state(config, &db, false);
+        let app = test::init_service(App::new().app_data(state.clone()).app_data(web::Data::new(db.rocksdb())).configure(routes::config)).await;
+        let ready = || test::TestRequest::get().uri("/health/ready").to_request();
+
+        let response = test::call_service(&app, ready()).await;
+        assert_eq!(response.status(), 503);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["status"], "DOWN");
+        assert_eq!(body["checks"]["rocksdb"]["status"], "UP");
+        assert_eq!(body["checks"]["localData"]["status"], "DOWN");
+
+        state.onboarding.succeed().await;
+        let response = test::call_service(&app, ready()).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["status"], "UP");
+        assert_eq!(body["checks"]["aasServer"]["status"], "UNKNOWN");
+
+        let live = || test::TestRequest::get().uri("/health/live").to_request();
+        assert_eq!(test::call_service(&app, live()).await.status(), 200);
+
+        // A RocksDB lock that is held on fails both probes instead of blocking them
+        let rocksdb = db.rocksdb();
+        let _guard = rocksdb.lock().await;
+        let response = test::call_service(&app, ready()).await;
+        assert_eq!(response.status(), 503);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["checks"]["rocksdb"]["status"], "DOWN");
+        assert_eq!(test::call_service(&app, live()).await.status(), 503);
+    }
+}
@@ -4,53 +4,47 @@ use serde_json::{json, Value};
 // use std::sync::Arc;
 // use rocksdb::DB;
 
+use crate::config::{ApiAuthAlgorithm, ApiAuthConfig};
+use crate::error::AppError;
 use crate::state::AppState;
 use crate::functions::aas_interfaces;
 
 pub async fn index(
     req: actix_web::HttpRequest,
     app_state: web::Data<AppState>,
-) -> impl actix_web::Responder {
+) -> Result<actix_web::HttpResponse, AppError> {
     // Extract connection info
-    let conn_info = req.connection_info();
-    let ip = conn_info.host();
-    let scheme_str = conn_info.scheme();
-    let protocol = if scheme_str.is_empty() {
-        "http".to_string()
-    } else {
-        scheme_str.to_string()
-    };
+    let url = {
+        let conn_info = req.connection_info();
+        let ip = conn_info.host();
+        let scheme_str = conn_info.scheme();
+        let protocol = if scheme_str.is_empty() {
+            "http".to_string()
+        } else {
+            scheme_str.to_string()
+        };
 
-    let url = format!("{}://{}/", protocol, ip);
+        format!("{}://{}/", protocol, ip)
+    };
 
     // Read and parse asset info JSON
     let asset_info_path = "./static/asset_info.json";
-    let asset_info = match std::fs::read_to_string(asset_info_path) {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!("Failed to read asset info: {}", e);
-            return actix_web::HttpResponse::InternalServerError().finish();
-        }
-    };
-    let asset_info_json: Value = match serde_json::from_str(&asset_info) {
-        Ok(json) => json,
-        Err(e) => {
-            eprintln!("Failed to parse asset info JSON: {}", e);
-            return actix_web::HttpResponse::InternalServerError().finish();
-        }
-    };
+    let asset_info = std::fs::read_to_string(asset_info_path)
+        .map_err(|e| AppError::Internal(format!("Failed to read asset info: {}", e)))?;
+    let asset_info_json: Value = serde_json::from_str(&asset_info)
+        .map_err(|e| AppError::Internal(format!("Failed to parse asset info JSON: {}", e)))?;
 
-    // Fetch the submodels dictionary from RocksDB
-    let _id_submodels_dictionary = format!("{}:submodels_dictionary", &app_state.aas_id_short);
+    // Fetch the submodels dictionary from RocksDB. The index stays reachable while
+    // onboarding, without links until the submodels are stored.
+    let submodels_dictionary = if app_state.onboarding.has_local_data() {
+        let _id_submodels_dictionary = format!("{}:submodels_dictionary", &app_state.config.aas_id_short);
 
-    let submodels_dictionary_result =
-        aas_interfaces::aas_find_one(_id_submodels_dictionary, app_state.rocksdb.clone()).await;
-    let submodels_dictionary = match submodels_dictionary_result {
-        Ok(dictionary) => dictionary,
-        Err(e) => {
-            let error_message = format!("Failed to get submodel dictionary: {}", e);
-            return actix_web::HttpResponse::InternalServerError().body(error_message);
-        }
+        let submodels_dictionary_result =
+            aas_interfaces::aas_find_one(_id_submodels_dictionary, app_state.rocksdb.clone()).await;
+        submodels_dictionary_result
+            .map_err(|e| e.context("Failed to get submodel dictionary"))?
+    } else {
+        json!({})
     };
 
     // Build the links array from the submodels dictionary
@@ -70,17 +64,61 @@ pub async fn index(
     let json_data = json!({
         "@context": "https://www.w3.org/2022/wot/td/v1.1",
         "id": url,
-        "title": "LNI Edge Device", // TODO: update this if necessary
+        "title": app_state.config.device_name,
         "version": asset_info_json,
+        "securityDefinitions": security_definitions(&app_state.config.api_auth),
         "security": [
-            "bearer_sc"
+            if app_state.config.api_auth.algorithm == ApiAuthAlgorithm::None { "nosec_sc" } else { "bearer_sc" }
         ],
         "base": format!("{}api/", url),
         "links": links
     });
 
     // Return the response
-    actix_web::HttpResponse::Ok()
+    Ok(actix_web::HttpResponse::Ok()
         .content_type("application/json")
-        .body(json_data.to_string())
-}
\ No newline at end of file
+        .body(json_data.to_string()))
+}
+/// Security schemes of the Thing Description matching the configured token check.
+fn security_definitions(api_auth: &ApiAuthConfig) -> Value {
+    match api_auth.algorithm {
+        ApiAuthAlgorithm::None => json!({ "nosec_sc": { "scheme": "nosec" } }),
+        algorithm => json!({
+            "bearer_sc": {
+                "scheme": "bearer",
+                "in": "header",
+                "format": "jwt",
+                "alg": if algorithm == ApiAuthAlgorithm::HS256 { "HS256" } else { "RS256" },
+            }
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::routes;
+    use crate::test_utils::{app_state, TempDb};
+    use actix_web::{test, web, App};
+    use serde_json::{json, Value};
+
+    #[actix_web::test]
+    async fn test_index_is_served_while_onboarding() {
+        let db = TempDb::open();
+        let config = Config { aas_id_short: "Test".to_string(), ..Default::default() };
+        let state = app_state(config, &db, false);
+        let app = test::init_service(App::new().app_data(state.clone()).app_data(web::Data::new(db.rocksdb())).configure(routes::config)).await;
+
+        let index: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(index["links"], json!([]));
+        // Data routes answer 503 with a hint to retry until the submodels are stored
+        let response = test::call_service(&app, test::TestRequest::get().uri("/submodels/Network").to_request()).await;
+        assert_eq!(response.status(), 503);
+        assert!(response.headers().contains_key("retry-after"));
+
+        db.rocksdb().lock().await.put("Test:submodels_dictionary", json!({ "Network": "urn:sm:1" }).to_string()).unwrap();
+        state.onboarding.succeed().await;
+        let index: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(index["links"], json!([{ "href": "/submodels/Network", "rel": "Network", "method": "GET" }]));
+    }
+}
@@ -0,0 +1,14 @@
+use actix_web::{HttpResponse, Responder};
+use prometheus::{Encoder, TextEncoder};
+
+/// Handler to get the metrics in the Prometheus text format.
+pub async fn get_metrics() -> impl Responder {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+
+    if let Err(e) = encoder.encode(&prometheus::gather(), &mut buffer) {
+        return HttpResponse::InternalServerError().body(format!("Failed to encode metrics: {}", e));
+    }
+
+    HttpResponse::Ok().content_type(encoder.format_type()).body(buffer)
+}
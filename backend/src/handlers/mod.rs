@@ -1,4 +1,10 @@
+pub mod aas_api;
+pub mod aasx;
+pub mod health;
 pub mod home;
+pub mod metrics;
+pub mod onboarding;
 pub mod submodels;
 pub mod openapi;
-pub mod picture;
\ No newline at end of file
+pub mod picture;
+pub mod users;
@@ -0,0 +1,26 @@
+use actix_web::{web::Data, HttpResponse, Responder};
+
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// Handler to get the onboarding progress.
+pub async fn get_onboarding_status(app_data: Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(app_data.onboarding.status().await)
+}
+
+/// Fails with 503 while no submodels are stored locally, i.e. before the first
+/// successful onboarding.
+pub async fn require_local_data(app_data: &AppState) -> Result<(), AppError> {
+    if app_data.onboarding.has_local_data() {
+        return Ok(());
+    }
+
+    let status = app_data.onboarding.status().await;
+    Err(AppError::Unavailable {
+        message: format!(
+            "No local data yet, the device is still onboarding (state {:?}, {} attempt(s), see /onboarding)",
+            status.state, status.attempts
+        ),
+        retry_after: Some(app_data.config.onboarding_retry_interval),
+    })
+}
@@ -1,20 +1,21 @@
-use actix_web::{web::Data, HttpResponse, Error};
+use actix_web::{web::Data, HttpResponse};
 
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 
+use crate::error::AppError;
+use crate::functions::aasx;
 use crate::state::AppState;
 
 pub async fn get_picture(
     app_data: Data<AppState>
-) -> Result<HttpResponse, Error> {
+) -> Result<HttpResponse, AppError> {
     // Get the reference to the inner data
-    let aas_id_short = &app_data.aas_id_short;
+    let aas_id_short = &app_data.config.aas_id_short;
     let base_path = "./static/asset_images/";
     
-    let thumbnail_png = format!("{}.png", aas_id_short);
     // List of potential files in order of priority
-    let file_names = vec!["product.svg", "product.png", &thumbnail_png];
+    let file_names = vec!["product.svg", "product.png"];
     
     let mut contents = Vec::new();
     let mut content_type = "image/png"; // Default content type
@@ -33,7 +34,12 @@ pub async fn get_picture(
             }
         }
     }
+
+    // Then the thumbnail of an imported package, as whatever image type it came in
+    if let Some((extension, image)) = aasx::stored_thumbnail(aas_id_short).await {
+        return Ok(HttpResponse::Ok().content_type(aasx::content_type(extension)).body(image));
+    }
     
     // If no file was found or read successfully
-    Ok(HttpResponse::NotFound().body("Image not found"))
+    Err(AppError::NotFound("Image not found".to_string()))
 }
\ No newline at end of file
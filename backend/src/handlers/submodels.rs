@@ -1,4 +1,5 @@
-use actix_web::{web::{self, Data, Path}, HttpResponse, Responder};
+use actix_web::{web::{self, Data, Path, Query}, HttpResponse};
+use serde::Deserialize;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -7,45 +8,77 @@ use chrono::Utc;
 use futures::future::try_join_all;
 use rocksdb::DB;
 
-use crate::functions::aas_interfaces;
+use crate::error::AppError;
+use crate::functions::{aas_api, aas_interfaces, concept_descriptions, id_short_path, operations, timeseries};
+use crate::functions::discovery::decode_id;
+use crate::functions::operations::OperationRequest;
+use crate::handlers::onboarding::require_local_data;
 use crate::state::AppState;
-use crate::functions::transform_value_submodel::{submodel_to_submodel_value, merge_submodel_value_to_submodel};
+use crate::functions::transform_value_submodel::{check_submodel_value, submodel_to_submodel_value};
 
-/// Handler to get all submodels.
+#[derive(Deserialize)]
+pub struct ElementQuery {
+    /// `value` (the default) for the value-only representation, `normal` for the full element.
+    content: Option<aas_api::Content>,
+    /// Wraps the response as `{"value", "annotations"}` with names and units by idShortPath.
+    #[serde(default)]
+    annotations: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SubmodelQuery {
+    /// Wraps the response as `{"value", "annotations"}` with names and units by idShortPath.
+    #[serde(default)]
+    annotations: bool,
+}
+
+/// Handler to get all submodels, or the one with `?semanticId=` (base64url encoded as in the
+/// AAS API). No submodel has the semanticId if the map is empty.
 pub async fn get_submodels(
     rocksdb: Data<Arc<Mutex<DB>>>,
+    filter: Query<aas_api::SubmodelFilter>,
     app_data: Data<AppState>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_data).await?;
+
     // Clone the RocksDB instance
     let rocksdb = rocksdb.get_ref().clone();
 
     // Construct the key for the submodels dictionary
-    let submodels_dictionary_id = format!("{}:submodels_dictionary", &app_data.aas_id_short);
+    let submodels_dictionary_id = format!("{}:submodels_dictionary", &app_data.config.aas_id_short);
 
     // Fetch the submodels dictionary from RocksDB
-    let submodels_dictionary = match aas_interfaces::aas_find_one(
+    let submodels_dictionary = aas_interfaces::aas_find_one(
         submodels_dictionary_id,
         rocksdb.clone(),
     )
     .await
-    {
-        Ok(submodels_dictionary) => submodels_dictionary,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Error getting submodels dictionary: {}", e))
-        }
-    };
+    .map_err(|e| e.context("Error getting submodels dictionary"))?;
 
     // Extract the keys (submodel IDs) from the dictionary
-    let keys: Vec<String> = submodels_dictionary
+    let mut keys: Vec<String> = submodels_dictionary
         .as_object()
         .map(|map| map.keys().cloned().collect())
         .unwrap_or_default();
+    if let Some(semantic_id) = &filter.semantic_id {
+        let submodel_id_short = match aas_interfaces::find_by_semantic_id(
+            rocksdb.clone(),
+            &app_data.config.aas_id_short,
+            &decode_id(semantic_id)?,
+        )
+        .await
+        {
+            Ok(submodel_id_short) => Some(submodel_id_short),
+            Err(AppError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        keys.retain(|key| Some(key) == submodel_id_short.as_ref());
+    }
 
     // Create asynchronous tasks to fetch each submodel
     let fetch_tasks = keys.into_iter().map(|key| {
         let rocksdb_clone = rocksdb.clone();
-        let aas_id_short_clone = app_data.aas_id_short.clone();
+        let aas_id_short_clone = app_data.config.aas_id_short.clone();
         async move {
             aas_interfaces::get_submodel_database(
                 rocksdb_clone,
@@ -58,42 +91,42 @@ pub async fn get_submodels(
     });
 
     // Execute all fetch tasks concurrently
-    match try_join_all(fetch_tasks).await {
-        Ok(results) => {
-            let submodels_map: HashMap<String, Value> = results.into_iter().collect();
-            HttpResponse::Ok().json(submodels_map) // Send the map as a JSON response
-        }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error getting submodels: {}", e)),
-    }
+    let results = try_join_all(fetch_tasks).await?;
+    let submodels_map: HashMap<String, Value> = results.into_iter().collect();
+    Ok(HttpResponse::Ok().json(submodels_map)) // Send the map as a JSON response
 }
 
 /// Handler to get a single submodel.
 pub async fn get_submodel(
     rocksdb: Data<Arc<Mutex<DB>>>,
     path: Path<String>,
+    query: Query<SubmodelQuery>,
     app_data: Data<AppState>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_data).await?;
+
     let submodel_id_short = path.into_inner();
 
     // Clone the RocksDB instance
     let rocksdb_clone = rocksdb.get_ref().clone();
 
     // Fetch the submodel from RocksDB
-    let aas_submodel = match aas_interfaces::get_submodel_database(
-        rocksdb_clone,
-        &app_data.aas_id_short,
+    let aas_submodel = aas_interfaces::get_submodel_database(
+        rocksdb_clone.clone(),
+        &app_data.config.aas_id_short,
         &submodel_id_short,
     )
-    .await
-    {
-        Ok(aas_submodel) => aas_submodel,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Error getting submodel: {}", e))
-        }
-    };
-    let aas_submodel_value = submodel_to_submodel_value(aas_submodel);
-    HttpResponse::Ok().json(aas_submodel_value)
+    .await?;
+    if !query.annotations {
+        return Ok(HttpResponse::Ok().json(submodel_to_submodel_value(aas_submodel)));
+    }
+
+    let cached = concept_descriptions::cached(rocksdb_clone, &app_data.config.aas_id_short).await?;
+    let elements = aas_submodel["submodelElements"].as_array().into_iter().flatten().map(|element| {
+        (element["idShort"].as_str().unwrap_or_default().to_string(), element)
+    });
+    let annotated = concept_descriptions::annotate(submodel_to_submodel_value(aas_submodel.clone()), elements, &cached);
+    Ok(HttpResponse::Ok().json(annotated))
 }
 
 /// Handler to patch (update) a submodel.
@@ -102,7 +135,9 @@ pub async fn patch_submodel(
     path: Path<String>,
     app_data: Data<AppState>,
     json: web::Json<Value>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_data).await?;
+
     let submodel_id_short = path.into_inner();
     // Convert the JSON payload into a mutable `Value`
     let mut json = json.into_inner();
@@ -115,59 +150,295 @@ pub async fn patch_submodel(
     // Clone the RocksDB instance
     let rocksdb = rocksdb.get_ref().clone();
 
-    // Fetch the submodel from RocksDB
-    let aas_submodel = match aas_interfaces::get_submodel_database(
+    // Make sure the submodel exists and the values fit it, both patches below merge the
+    // value into it
+    let submodel = aas_interfaces::get_submodel_database(
         rocksdb.clone(),
-        &app_data.aas_id_short,
+        &app_data.config.aas_id_short,
         &submodel_id_short,
     )
-    .await
-    {
-        Ok(aas_submodel) => aas_submodel,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Error getting submodel: {}", e))
-        }
-    };
-
-    // convert json payload into submodel format
-    let json = merge_submodel_value_to_submodel(json, aas_submodel);
+    .await?;
+    aas_api::validate_patch(app_data.config.patch_validation, check_submodel_value(&submodel, &json))?;
 
     // Patch the submodel in the local RocksDB database
-    if let Err(e) = aas_interfaces::patch_submodel_database(
+    aas_interfaces::patch_submodel_database(
         rocksdb.clone(),
-        &app_data.aas_id_short,
+        &app_data.config.aas_id_short,
         &submodel_id_short,
         &json,
     )
     .await
-    {
-        return HttpResponse::InternalServerError()
-            .body(format!("Error patching submodel in database: {}", e));
-    };
+    .map_err(|e| e.context("Error patching submodel in database"))?;
 
     // Read the managed device information
-    if let Err(e) = aas_interfaces::read_managed_device(
+    aas_interfaces::read_managed_device(
         rocksdb.clone(),
-        &app_data.aas_id_short,
+        &app_data.config,
     )
     .await
-    {
-        return HttpResponse::InternalServerError()
-            .body(format!("Error reading managed device: {}", e));
-    };
+    .map_err(|e| e.context("Error reading managed device"))?;
 
     // Patch the submodel on the AAS server
-    match aas_interfaces::patch_submodel_server(
-        rocksdb,
-        &app_data.aas_id_short,
+    if app_data.config.has_aas_server() {
+        aas_interfaces::patch_submodel_server(
+            app_data.aas_server().await.as_ref(),
+            rocksdb,
+            &app_data.config.aas_id_short,
+            &submodel_id_short,
+            app_data.config.push_mode,
+            &json,
+        )
+        .await
+        .map_err(|e| e.context("Error patching submodel to server"))?;
+    }
+
+    Ok(HttpResponse::Ok().body("Submodel patched successfully"))
+}
+
+
+/// Handler to get a nested element by its idShortPath, e.g. `Hardware.Processor.CpuUsage`.
+pub async fn get_submodel_element(
+    path: Path<(String, String)>,
+    query: Query<ElementQuery>,
+    app_data: Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_data).await?;
+
+    let (submodel_id_short, id_short_path) = path.into_inner();
+    let submodel = aas_interfaces::get_submodel_database(
+        app_data.rocksdb.clone(),
+        &app_data.config.aas_id_short,
+        &submodel_id_short,
+    )
+    .await?;
+
+    let modifiers = aas_api::Modifiers {
+        content: query.content.unwrap_or(aas_api::Content::Value),
+        ..Default::default()
+    };
+    let path = id_short_path::parse(&id_short_path)?;
+    let element = aas_api::submodel_element(&submodel, &path, &modifiers)?;
+    if !query.annotations {
+        return Ok(HttpResponse::Ok().json(element));
+    }
+
+    let cached = concept_descriptions::cached(app_data.rocksdb.clone(), &app_data.config.aas_id_short).await?;
+    let stored = id_short_path::find_element(&submodel, &path)?;
+    Ok(HttpResponse::Ok().json(concept_descriptions::annotate(element, [(id_short_path::format(&path), stored)], &cached)))
+}
+
+/// Handler to patch the value of a nested element, given in the value-only representation.
+pub async fn patch_submodel_element(
+    path: Path<(String, String)>,
+    app_data: Data<AppState>,
+    json: web::Json<Value>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_data).await?;
+
+    let (submodel_id_short, id_short_path) = path.into_inner();
+    aas_api::patch_element_value(
+        app_data.aas_server().await.as_ref(),
+        &app_data.config,
+        app_data.rocksdb.clone(),
+        &submodel_id_short,
+        &id_short_path::parse(&id_short_path)?,
+        json.into_inner(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().body("Submodel element patched successfully"))
+}
+
+/// Handler to invoke an Operation and wait for its `OperationResult`.
+pub async fn invoke_operation(
+    path: Path<(String, String)>,
+    app_data: Data<AppState>,
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_data).await?;
+
+    let (submodel_id_short, id_short_path) = path.into_inner();
+    let path = id_short_path::parse(&id_short_path)?;
+    let invocation = operations::prepare(&app_data, &submodel_id_short, &path, &operation_request(&body)?).await?;
+
+    Ok(HttpResponse::Ok().json(operations::run(&app_data, &invocation).await))
+}
+
+/// Handler to start an Operation, answering the location of its status.
+pub async fn invoke_operation_async(
+    path: Path<(String, String)>,
+    app_data: Data<AppState>,
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_data).await?;
+
+    let (submodel_id_short, id_short_path) = path.into_inner();
+    let path = id_short_path::parse(&id_short_path)?;
+    let invocation = operations::prepare(&app_data, &submodel_id_short, &path, &operation_request(&body)?).await?;
+
+    let handle_id = app_data.operations.start(invocation.operation.clone()).await;
+    let app_state = app_data.clone();
+    let handle = handle_id.clone();
+    tokio::spawn(async move {
+        let result = operations::run(&app_state, &invocation).await;
+        app_state.operations.finish(&handle, result).await;
+    });
+
+    let location = format!(
+        "/submodels/{}/elements/{}/operation-status/{}",
+        submodel_id_short, id_short_path, handle_id
+    );
+    Ok(HttpResponse::Accepted()
+        .insert_header(("Location", location))
+        .json(json!({ "handleId": handle_id })))
+}
+
+/// Handler to get the execution state of an asynchronous invocation.
+pub async fn get_operation_status(
+    path: Path<(String, String, String)>,
+    app_data: Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_data).await?;
+
+    let result = find_invocation(&app_data, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(result.status()))
+}
+
+/// Handler to get the `OperationResult` of an asynchronous invocation.
+pub async fn get_operation_result(
+    path: Path<(String, String, String)>,
+    app_data: Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_data).await?;
+
+    let result = find_invocation(&app_data, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn find_invocation(
+    app_data: &AppState,
+    (submodel_id_short, id_short_path, handle_id): (String, String, String),
+) -> Result<operations::OperationResult, AppError> {
+    let operation = operations::operation_name(&submodel_id_short, &id_short_path::parse(&id_short_path)?);
+    app_data
+        .operations
+        .get(&operation, &handle_id)
+        .await
+        .ok_or_else(|| AppError::NotFound(format!("No invocation '{}' of operation {}", handle_id, operation)))
+}
+
+/// An empty body invokes the Operation with the values its variables are declared with.
+fn operation_request(body: &[u8]) -> Result<OperationRequest, AppError> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(OperationRequest::default());
+    }
+    serde_json::from_slice(body).map_err(|e| AppError::BadRequest(format!("Invalid OperationRequest: {}", e)))
+}
+
+/// Handler to get the recorded time series of a submodel.
+pub async fn get_submodel_timeseries(
+    rocksdb: Data<Arc<Mutex<DB>>>,
+    path: Path<String>,
+    app_data: Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    require_local_data(&app_data).await?;
+
+    let submodel_id_short = path.into_inner();
+
+    let samples = timeseries::get_samples(
+        rocksdb.get_ref().clone(),
+        &app_data.config.aas_id_short,
         &submodel_id_short,
-        &app_data.aasx_server,
-        &json,
     )
     .await
-    {
-        Ok(_) => HttpResponse::Ok().body("Submodel patched successfully"),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error patching submodel to server: {}", e)),
+    .map_err(|e| e.context("Error getting time series"))?;
+
+    Ok(HttpResponse::Ok().json(samples))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::functions::{aas_interfaces, discovery::encode_id};
+    use crate::routes;
+    use crate::test_utils::{app_state, TempDb};
+    use actix_web::{test, web, App};
+    use serde_json::{json, Value};
+
+    #[actix_web::test]
+    async fn test_reads_and_writes_nested_elements() {
+        let db = TempDb::open();
+        let address = |value: &str| json!({ "modelType": "Property", "idShort": "Address", "valueType": "xs:string", "value": value });
+        let interface = |address: Value| json!({ "modelType": "SubmodelElementCollection", "value": [address] });
+        let submodel = json!({
+            "modelType": "Submodel",
+            "idShort": "Network",
+            "submodelElements": [
+                { "modelType": "SubmodelElementList", "idShort": "Interfaces", "value": [
+                    interface(address("10.0.0.1")), interface(address("10.0.0.2"))
+                ]},
+                { "modelType": "SubmodelElementList", "idShort": "Ports", "typeValueListElement": "Property", "valueTypeListElement": "xs:int", "value": [
+                    { "modelType": "Property", "valueType": "xs:int", "value": "80" },
+                    { "modelType": "Property", "valueType": "xs:int", "value": "443" }
+                ]}
+            ]
+        });
+        db.rocksdb().lock().await.put("Test:Network", submodel.to_string()).unwrap();
+        let config = Config { aas_id_short: "Test".to_string(), ..Default::default() };
+        let app = test::init_service(App::new().app_data(app_state(config, &db, true)).app_data(web::Data::new(db.rocksdb())).configure(routes::config)).await;
+        let get = |uri: &str| test::TestRequest::get().uri(&format!("/submodels/Network/elements/{}", uri)).to_request();
+        let patch = |uri: &str, value: Value| {
+            test::TestRequest::patch().uri(&format!("/submodels/Network/elements/{}", uri)).set_json(value).to_request()
+        };
+
+        // Value-only by default, the full element with ?content=normal
+        let value: Value = test::call_and_read_body_json(&app, get("Interfaces%5B1%5D.Address")).await;
+        assert_eq!(value, json!("10.0.0.2"));
+        let element: Value = test::call_and_read_body_json(&app, get("Interfaces%5B1%5D.Address?content=normal")).await;
+        assert_eq!(element, address("10.0.0.2"));
+        let value: Value = test::call_and_read_body_json(&app, get("Ports%5B0%5D")).await;
+        assert_eq!(value, json!(80));
+        assert_eq!(test::call_service(&app, get("Interfaces%5B2%5D.Address")).await.status(), 404);
+
+        // Elements inside lists are written by their index
+        assert!(test::call_service(&app, patch("Interfaces%5B0%5D.Address", json!("192.168.0.1"))).await.status().is_success());
+        assert!(test::call_service(&app, patch("Ports%5B1%5D", json!(8443))).await.status().is_success());
+        let value: Value = test::call_and_read_body_json(&app, get("Interfaces%5B0%5D.Address")).await;
+        assert_eq!(value, json!("192.168.0.1"));
+        let value: Value = test::call_and_read_body_json(&app, get("Ports")).await;
+        assert_eq!(value, json!([80, 8443]));
+        // Values that do not fit the element are rejected
+        assert_eq!(test::call_service(&app, patch("Ports%5B1%5D", json!("https"))).await.status(), 422);
+    }
+
+    #[actix_web::test]
+    async fn test_semantic_id_filter_matches_aas_api() {
+        let db = TempDb::open();
+        let semantic_id = |id: &str| json!({ "type": "ExternalReference", "keys": [{ "type": "GlobalReference", "value": id }] });
+        let system_information = json!({ "modelType": "Submodel", "idShort": "SystemInformation", "semanticId": semantic_id("urn:sm:sysinfo") });
+        {
+            let rocksdb = db.rocksdb();
+            let rocksdb = rocksdb.lock().await;
+            rocksdb.put("Test:submodels_dictionary", json!({ "SystemInformation": "urn:sm:1", "Network": "urn:sm:2" }).to_string()).unwrap();
+            rocksdb.put("Test:SystemInformation", system_information.to_string()).unwrap();
+            rocksdb.put("Test:Network", json!({ "modelType": "Submodel", "idShort": "Network" }).to_string()).unwrap();
+        }
+        aas_interfaces::index_semantic_ids(db.rocksdb(), "Test").await.unwrap();
+        let config = Config { aas_id_short: "Test".to_string(), ..Default::default() };
+        let app = test::init_service(App::new().app_data(app_state(config, &db, true)).app_data(web::Data::new(db.rocksdb())).configure(routes::config)).await;
+        let get = |uri: String| test::TestRequest::get().uri(&uri).to_request();
+
+        // Both routes take the semanticId base64url encoded
+        let submodels: Value = test::call_and_read_body_json(&app, get(format!("/submodels?semanticId={}", encode_id("urn:sm:sysinfo")))).await;
+        assert_eq!(submodels, json!({ "SystemInformation": system_information }));
+        let page: Value = test::call_and_read_body_json(&app, get(format!("/api/v3.0/submodels?semanticId={}", encode_id("urn:sm:sysinfo")))).await;
+        assert_eq!(page["result"][0]["idShort"], "SystemInformation");
+
+        // and answer an empty result if no submodel has it
+        let submodels: Value = test::call_and_read_body_json(&app, get(format!("/submodels?semanticId={}", encode_id("urn:sm:other")))).await;
+        assert_eq!(submodels, json!({}));
+        let page: Value = test::call_and_read_body_json(&app, get(format!("/api/v3.0/submodels?semanticId={}", encode_id("urn:sm:other")))).await;
+        assert_eq!(page["result"], json!([]));
+        assert_eq!(test::call_service(&app, get("/submodels?semanticId=urn:sm:sysinfo".to_string())).await.status(), 400);
     }
 }
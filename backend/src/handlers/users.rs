@@ -0,0 +1,174 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use serde::Deserialize;
+
+use crate::api_auth::Claims;
+use crate::error::AppError;
+use crate::functions::users::{self, Role};
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateUserRequest {
+    username: String,
+    password: String,
+    role: Role,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordRequest {
+    password: String,
+    /// Needed to change the own password, unless the token grants the admin scope.
+    current_password: Option<String>,
+}
+
+/// Handler to log in a local user, answering an access and a refresh token.
+pub async fn login(app_state: web::Data<AppState>, body: web::Json<LoginRequest>) -> Result<HttpResponse, AppError> {
+    users::require_local_users(&app_state.config)?;
+    let tokens = users::login(app_state.rocksdb.clone(), &app_state.config, &body.username, &body.password).await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+/// Handler to exchange a refresh token for new tokens.
+pub async fn refresh(app_state: web::Data<AppState>, body: web::Json<RefreshRequest>) -> Result<HttpResponse, AppError> {
+    users::require_local_users(&app_state.config)?;
+    let tokens = users::refresh(app_state.rocksdb.clone(), &app_state.config, &body.refresh_token).await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+/// Handler to revoke a refresh token.
+pub async fn logout(app_state: web::Data<AppState>, body: web::Json<RefreshRequest>) -> Result<HttpResponse, AppError> {
+    users::require_local_users(&app_state.config)?;
+    users::logout(app_state.rocksdb.clone(), &app_state.config, &body.refresh_token).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_users(req: HttpRequest, app_state: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &app_state)?;
+    Ok(HttpResponse::Ok().json(users::list_users(app_state.rocksdb.clone(), &app_state.config).await?))
+}
+
+pub async fn create_user(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    body: web::Json<CreateUserRequest>,
+) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &app_state)?;
+    let user =
+        users::create_user(app_state.rocksdb.clone(), &app_state.config, &body.username, &body.password, body.role).await?;
+    Ok(HttpResponse::Created().json(user))
+}
+
+pub async fn delete_user(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    username: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &app_state)?;
+    users::delete_user(app_state.rocksdb.clone(), &app_state.config, &username).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Handler to change a password, allowed for admins and for the user themselves with their
+/// current password. Needs only the read scope, so that viewers can change their own.
+pub async fn set_password(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    username: web::Path<String>,
+    body: web::Json<PasswordRequest>,
+) -> Result<HttpResponse, AppError> {
+    users::require_local_users(&app_state.config)?;
+    let (own_account, admin) = match req.extensions().get::<Claims>() {
+        Some(claims) => (
+            claims.0.get("sub").and_then(|sub| sub.as_str()) == Some(username.as_str()),
+            claims.has_scope(&app_state.config.api_auth.admin_scope),
+        ),
+        None => (false, false),
+    };
+    if !own_account && !admin {
+        // Answers 401 without a token and 403 without the admin scope
+        require_admin(&req, &app_state)?;
+    }
+    // A token alone does not prove that the user knows the password, it may be a leaked one
+    let current_password = match admin {
+        true => None,
+        false => Some(body.current_password.as_deref().ok_or_else(|| {
+            AppError::BadRequest("current_password is needed to change the own password".to_string())
+        })?),
+    };
+    users::set_password(app_state.rocksdb.clone(), &app_state.config, &username, &body.password, current_password).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Fails unless the bearer token grants the admin scope.
+fn require_admin(req: &HttpRequest, app_state: &AppState) -> Result<(), AppError> {
+    users::require_local_users(&app_state.config)?;
+    let admin_scope = &app_state.config.api_auth.admin_scope;
+    match req.extensions().get::<Claims>() {
+        Some(claims) if claims.has_scope(admin_scope) => Ok(()),
+        Some(_) => Err(AppError::Forbidden(format!("The token lacks the scope '{}'", admin_scope))),
+        None => Err(AppError::Unauthorized("Missing bearer token".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api_auth::{ApiAuth, JwtAuth};
+    use crate::config::{ApiAuthAlgorithm, Config};
+    use crate::functions::users::{self, Role};
+    use crate::routes;
+    use crate::test_utils::{app_state, TempDb};
+    use actix_web::{test, web, App};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[actix_web::test]
+    async fn test_own_password_needs_the_current_one() {
+        let db = TempDb::open();
+        let mut config = Config { aas_id_short: "Test".to_string(), ..Default::default() };
+        config.api_auth.algorithm = ApiAuthAlgorithm::HS256;
+        config.api_auth.secret = Some("0123456789abcdef0123456789abcdef".to_string());
+        config.users.admin_password = Some("admin-password".to_string());
+        users::bootstrap_admin(db.rocksdb(), &config).await.unwrap();
+        users::create_user(db.rocksdb(), &config, "viewer", "viewer-password", Role::Viewer).await.unwrap();
+        let admin = users::login(db.rocksdb(), &config, "admin", "admin-password").await.unwrap().access_token;
+        let viewer = users::login(db.rocksdb(), &config, "viewer", "viewer-password").await.unwrap().access_token;
+
+        let auth = Arc::new(ApiAuth::new(config.api_auth.clone(), reqwest::Client::new()).await.unwrap());
+        let app = test::init_service(
+            App::new()
+                .wrap(JwtAuth::new(auth))
+                .app_data(app_state(config.clone(), &db, true))
+                .app_data(web::Data::new(db.rocksdb()))
+                .configure(routes::config),
+        )
+        .await;
+        let put = |username: &str, token: &str, body: serde_json::Value| {
+            let request = test::TestRequest::put()
+                .uri(&format!("/users/{}/password", username))
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .set_json(body)
+                .to_request();
+            let app = &app;
+            async move { test::call_service(app, request).await.status().as_u16() }
+        };
+
+        // A viewer token alone does not change the password
+        assert_eq!(put("viewer", &viewer, json!({ "password": "new-password" })).await, 400);
+        assert_eq!(put("viewer", &viewer, json!({ "password": "new-password", "current_password": "wrong-password" })).await, 403);
+        assert_eq!(put("viewer", &viewer, json!({ "password": "new-password", "current_password": "viewer-password" })).await, 204);
+        assert_eq!(put("admin", &viewer, json!({ "password": "new-password", "current_password": "admin-password" })).await, 403);
+        // Admins set passwords without the current one
+        assert_eq!(put("viewer", &admin, json!({ "password": "reset-password" })).await, 204);
+        users::login(db.rocksdb(), &config, "viewer", "reset-password").await.unwrap();
+    }
+}
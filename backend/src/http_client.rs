@@ -0,0 +1,199 @@
+//! The HTTP client shared by all requests to the AAS server, so that connections are pooled
+//! and TLS and credentials are configured in one place.
+
+use reqwest::{Certificate, Client, Identity, NoProxy, Proxy, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::HttpClientConfig;
+use crate::error::AppError;
+use crate::metrics;
+use crate::server_auth::ServerAuth;
+
+/// Client for the AAS server: the pooled connections plus the configured credentials.
+/// Cloning is cheap, clones share both.
+#[derive(Clone)]
+pub struct AasClient {
+    client: Client,
+    auth: Arc<ServerAuth>,
+}
+
+impl AasClient {
+    pub fn new(client: Client, auth: ServerAuth) -> Self {
+        AasClient { client, auth: Arc::new(auth) }
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn put(&self, url: &str) -> RequestBuilder {
+        self.client.put(url)
+    }
+
+    pub fn patch(&self, url: &str) -> RequestBuilder {
+        self.client.patch(url)
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    pub fn delete(&self, url: &str) -> RequestBuilder {
+        self.client.delete(url)
+    }
+
+    /// Sends a request built with this client along with the credentials. A 401 is retried
+    /// once with renewed credentials if they can be renewed.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, AppError> {
+        let retry = request.try_clone();
+        let response = self.auth.authorize(&self.client, request).await?.send().await?;
+
+        match retry {
+            Some(retry) if response.status() == StatusCode::UNAUTHORIZED && self.auth.can_renew() => {
+                eprintln!("AAS server answered 401, retrying with a new access token");
+                self.auth.invalidate().await;
+                Ok(self.auth.authorize(&self.client, retry).await?.send().await?)
+            }
+            _ => Ok(response),
+        }
+    }
+
+    /// Sends a request built with this client without the credentials, for services other
+    /// than the AAS server.
+    pub async fn send_unauthenticated(&self, request: RequestBuilder) -> Result<Response, AppError> {
+        Ok(request.send().await?)
+    }
+
+    /// GETs a JSON document with the credentials, recording the answer under `operation`.
+    /// A 404 is `NotFound`, any other failure an upstream error.
+    pub async fn get_json(&self, url: &str, operation: &str) -> Result<Value, AppError> {
+        let response = self.send(self.get(url)).await;
+        metrics::record_server_response(operation, response.as_ref().ok().map(|r| r.status()));
+        let response = response?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Err(AppError::NotFound(format!("{} not found", url)));
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::upstream(status, format!("Failed to fetch {}: {}", url, body)));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| AppError::upstream(status, format!("Invalid JSON from {}: {}", url, e)))
+    }
+}
+
+/// Builds the shared client, reading the configured certificate files.
+pub fn build_client(config: &HttpClientConfig) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .connect_timeout(Duration::from_secs(config.connect_timeout))
+        .timeout(Duration::from_secs(config.timeout))
+        .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout))
+        .tcp_keepalive(Duration::from_secs(config.tcp_keepalive));
+
+    if let Some(path) = &config.ca_bundle {
+        let pem = read_file(path)?;
+        let certificates = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("Invalid CA bundle {}: {}", path, e))?;
+        if certificates.is_empty() {
+            return Err(format!("CA bundle {} contains no certificate", path));
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    if let (Some(cert_path), Some(key_path)) = (&config.client_cert, &config.client_key) {
+        let identity = Identity::from_pkcs8_pem(&read_file(cert_path)?, &read_file(key_path)?)
+            .map_err(|e| format!("Invalid client certificate {} or key {}: {}", cert_path, key_path, e))?;
+        builder = builder.identity(identity);
+    }
+
+    if let Some(url) = &config.proxy {
+        let proxy = Proxy::all(url)
+            .map_err(|e| format!("Invalid proxy '{}': {}", url, e))?
+            .no_proxy(config.no_proxy.as_deref().and_then(NoProxy::from_string));
+        builder = builder.proxy(proxy);
+    }
+
+    if config.insecure {
+        eprintln!("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
+        eprintln!("!! WARNING: HTTP_CLIENT_INSECURE is set, certificates of the AAS server are !!");
+        eprintln!("!! NOT verified. Anyone on the network can impersonate the server.         !!");
+        eprintln!("!! Never use this in production.                                           !!");
+        eprintln!("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    builder.build().map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AuthMethod, ServerAuthConfig};
+    use crate::test_utils::MockServer;
+    use actix_web::{web, HttpRequest, HttpResponse};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_reports_unusable_certificate_files() {
+        let missing = HttpClientConfig { ca_bundle: Some("/nonexistent/ca.pem".to_string()), ..Default::default() };
+        assert!(build_client(&missing).unwrap_err().contains("Cannot read /nonexistent/ca.pem"));
+
+        let path = std::env::temp_dir().join(format!("aas_client_ca_{}.pem", std::process::id()));
+        std::fs::write(&path, "not a certificate").unwrap();
+        let empty = HttpClientConfig { ca_bundle: Some(path.display().to_string()), ..Default::default() };
+        let result = build_client(&empty);
+        let _ = std::fs::remove_file(&path);
+        assert!(result.is_err());
+
+        assert!(build_client(&HttpClientConfig::default()).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_oauth2_token_is_cached_and_renewed_on_401() {
+        // Mock token endpoint handing out token-1, token-2, ... and a resource accepting only token-2
+        let issued = web::Data::new(AtomicUsize::new(0));
+        let issued_clone = issued.clone();
+        let server = MockServer::start(move |app| {
+            app.app_data(issued_clone.clone())
+                .route("/token", web::post().to(|issued: web::Data<AtomicUsize>, req: HttpRequest| async move {
+                    assert!(req.headers().contains_key("authorization"));
+                    let n = issued.fetch_add(1, Ordering::SeqCst) + 1;
+                    HttpResponse::Ok().json(serde_json::json!({ "access_token": format!("token-{}", n), "expires_in": 300 }))
+                }))
+                .route("/resource", web::get().to(|req: HttpRequest| async move {
+                    match req.headers().get("authorization").and_then(|v| v.to_str().ok()) {
+                        Some("Bearer token-2") => HttpResponse::Ok().finish(),
+                        _ => HttpResponse::Unauthorized().finish(),
+                    }
+                }));
+        });
+
+        let auth = ServerAuth::new(ServerAuthConfig {
+            method: AuthMethod::OAuth2,
+            token_url: Some(format!("{}token", server.url())),
+            client_id: Some("device".to_string()),
+            client_secret: Some("secret".to_string()),
+            ..Default::default()
+        });
+        let client = AasClient::new(build_client(&HttpClientConfig::default()).unwrap(), auth);
+
+        // token-1 is rejected, token-2 is fetched and accepted
+        let url = format!("{}resource", server.url());
+        assert_eq!(client.send(client.get(&url)).await.unwrap().status(), StatusCode::OK);
+        // token-2 is reused from the cache
+        assert_eq!(client.send(client.get(&url)).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(issued.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,9 +1,9 @@
 use actix_web::{web, App, HttpServer, middleware::Logger};
-use std::{env, sync::Arc};
-use tokio::time::{self, Duration};
+use std::sync::Arc;
 use actix_cors::Cors;
 use tokio::sync::Mutex;
 use rocksdb::{DB, Options};
+use std::os::unix::fs::FileTypeExt;
 
 async fn init_rocksdb(path: &str) -> Arc<Mutex<DB>> {
     let mut opts = Options::default();
@@ -14,13 +14,20 @@ async fn init_rocksdb(path: &str) -> Arc<Mutex<DB>> {
     Arc::new(Mutex::new(db))
 }
 
+mod api_auth;
+mod config;
+mod error;
 mod handlers;
+mod http_client;
+mod server_auth;
+mod tls;
+mod metrics;
 mod routes;
 mod state;
 mod models;
 mod functions;
-
-use functions::scheduler_task;
+#[cfg(test)]
+mod test_utils;
 
 
 
@@ -31,55 +38,88 @@ async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_BACKTRACE", "1");
     env_logger::init();
 
-    // Fetch environment variables
-    let rocksdb_path = env::var("ROCKSDB_PATH").unwrap_or_else(|_| "rocksdb".to_string());
-    let aas_id_short = env::var("AAS_IDSHORT").expect("AAS_IDSHORT must be set");
-    let aas_identifier = env::var("AAS_IDENTIFIER").expect("AAS_IDENTIFIER must be set");
-    let aasx_server = env::var("AASX_SERVER").expect("AASX_SERVER must be set");
-    let device_name = env::var("DEVICE_NAME").expect("DEVICE_NAME must be set");
-    let offboarding_time = env::var("OFFBOARDING_TIME").expect("OFFBOARDING_TIME must be set").parse::<i64>().expect("OFFBOARDING_TIME must be an integer");
+    // Load the configuration file and environment variables
+    let config = match config::Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let client = match http_client::build_client(&config.http_client) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let http_client = http_client::AasClient::new(client.clone(), server_auth::ServerAuth::new(config.server_auth.clone()));
+
+    // Tokens of the clients of the API, the JWKS is fetched with the shared client
+    let api_auth = match api_auth::ApiAuth::new(config.api_auth.clone(), client).await {
+        Ok(api_auth) => Arc::new(api_auth),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if !api_auth.is_enabled() {
+        eprintln!("WARNING: API_AUTH_ALGORITHM is not set, the HTTP API accepts requests without a token");
+    }
 
     // Initialize RocksDB
-    let rocksdb = init_rocksdb(&rocksdb_path).await;
+    let rocksdb = init_rocksdb(&config.rocksdb_path).await;
+
+    // The admin of the device UI on the first start
+    if let Err(e) = functions::users::bootstrap_admin(rocksdb.clone(), &config).await {
+        eprintln!("Failed to create the admin user: {}", e);
+        std::process::exit(1);
+    }
+
+    // Submodels stored by an earlier run can be served before onboarding completes
+    let local_data = functions::aas_interfaces::aas_find_one(
+        format!("{}:submodels_dictionary", config.aas_id_short),
+        rocksdb.clone(),
+    )
+    .await
+    .is_ok();
 
     // Initialize AppState
     let app_state = web::Data::new(state::AppState {
-        health_check_response: Mutex::new(String::from("I'm OK!")),
         rocksdb: rocksdb.clone(),
-        aas_identifier,
-        aas_id_short,
-        aasx_server,
-        device_name,
-        offboarding_time,
+        config,
+        http_client,
+        onboarding: state::onboarding::Onboarding::new(local_data),
+        health: state::health::Health::default(),
+        operations: state::operations::Invocations::default(),
+        aas_server: tokio::sync::OnceCell::new(),
     });
 
-    // Onboard the device
-    loop {
-        let result = functions::onboarding::edge_device_onboarding(
-            &app_state.aasx_server,
-            &app_state.aas_identifier,
-            &app_state.aas_id_short,
-            rocksdb.clone(),
-        ).await;
-
-        match result {
-            Ok(_) => {
-                println!("Device onboarded successfully!");
-                break; // Exit loop on success
-            },
-            Err(err) => {
-                eprintln!("Failed to onboard device: {}. \nRetrying in 10 seconds...", err);
-                time::sleep(Duration::from_secs(10)).await; // Wait for 10 seconds before retrying
-            }
-        }
-    }
-
-    // only after is onboarded
+    // Onboard the device in the background, the API answers 503 until local data exists
+    let onboarding = tokio::spawn(functions::onboarding::onboarding_loop(app_state.clone()));
 
-    scheduler_task::submodels_scheduler(app_state.clone(), rocksdb.clone()).await;
+    // Certificate of the https:// listeners, reloaded when its files change
+    let http_server_config = app_state.config.http_server.clone();
+    let tls_config = match (&http_server_config.tls_cert, &http_server_config.tls_key) {
+        (Some(cert), Some(key)) => match tls::CertificateReloader::new(cert, key) {
+            Ok(reloader) => {
+                let reloader = Arc::new(reloader);
+                reloader.clone().spawn_reload(http_server_config.tls_reload_interval);
+                Some(tls::server_config(reloader))
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
 
-    // Start HTTP server
-    HttpServer::new(move || {
+    // Start HTTP server, signals are handled below to shut the whole client down
+    let shutdown_state = app_state.clone();
+    let grace_period = app_state.config.shutdown.grace_period;
+    let mut server = HttpServer::new(move || {
         let cors = Cors::default()
             .allow_any_origin()
             .allow_any_method()
@@ -87,13 +127,48 @@ async fn main() -> std::io::Result<()> {
             .max_age(3600);
 
         App::new()
+            .wrap(api_auth::JwtAuth::new(api_auth.clone()))
             .wrap(Logger::default())
             .wrap(cors)
             .app_data(app_state.clone())
             .app_data(web::Data::new(rocksdb.clone()))
             .configure(routes::config) // Setup routes
     })
-    .bind("0.0.0.0:18000")?
-    .run()
-    .await
-}
\ No newline at end of file
+    .disable_signals()
+    .shutdown_timeout(grace_period);
+    for address in &http_server_config.listen {
+        server = match address {
+            config::ListenAddress::Http(address) => server.bind(address)?,
+            config::ListenAddress::Https(address) => match &tls_config {
+                Some(tls_config) => server.bind_rustls_021(address, tls_config.clone())?,
+                None => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("{} needs HTTP_SERVER_TLS_CERT and HTTP_SERVER_TLS_KEY", address),
+                    ))
+                }
+            },
+            config::ListenAddress::Unix(path) => {
+                // A socket left behind by an earlier run blocks the bind
+                if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+                    std::fs::remove_file(path)?;
+                }
+                server.bind_uds(path)?
+            }
+        };
+        println!("Listening on {}", address);
+    }
+    let mut server = server.run();
+    let server_handle = server.handle();
+
+    tokio::select! {
+        result = &mut server => return result,
+        signal = functions::shutdown::signal() => println!("Received {}, shutting down", signal),
+    }
+    // The server future has to be polled for the stop to make progress
+    let (result, _) = tokio::join!(
+        server,
+        functions::shutdown::shutdown(shutdown_state, server_handle, onboarding)
+    );
+    result
+}
@@ -0,0 +1,94 @@
+//! Prometheus metrics of the sync with the AAS server, the collector script and RocksDB.
+//! Everything is registered in the default registry served at `/metrics`.
+
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter_vec, Histogram, HistogramVec,
+    IntCounterVec,
+};
+
+lazy_static! {
+    pub static ref PUSH_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "aas_client_push_total",
+        "Pushes of submodels to the AAS server",
+        &["submodel", "result"]
+    )
+    .unwrap();
+    pub static ref PUSH_DURATION: HistogramVec = register_histogram_vec!(
+        "aas_client_push_duration_seconds",
+        "Duration of pushes of submodels to the AAS server",
+        &["submodel"]
+    )
+    .unwrap();
+    pub static ref POLL_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "aas_client_poll_total",
+        "Polls of submodels from the AAS server",
+        &["submodel", "result"]
+    )
+    .unwrap();
+    pub static ref POLL_DURATION: HistogramVec = register_histogram_vec!(
+        "aas_client_poll_duration_seconds",
+        "Duration of polls of submodels from the AAS server",
+        &["submodel"]
+    )
+    .unwrap();
+    pub static ref SERVER_RESPONSES: IntCounterVec = register_int_counter_vec!(
+        "aas_client_aas_server_responses_total",
+        "Responses of the AAS server by operation and status class (2xx..5xx, or error if none was received)",
+        &["operation", "status_class"]
+    )
+    .unwrap();
+    pub static ref ONBOARDING_ATTEMPTS: IntCounterVec = register_int_counter_vec!(
+        "aas_client_onboarding_attempts_total",
+        "Onboarding attempts",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref COLLECTOR_RUNS: IntCounterVec = register_int_counter_vec!(
+        "aas_client_collector_runs_total",
+        "Runs of the sysInfo.sh collector script",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref COLLECTOR_DURATION: Histogram = register_histogram!(
+        "aas_client_collector_duration_seconds",
+        "Duration of the sysInfo.sh collector script"
+    )
+    .unwrap();
+    pub static ref ROCKSDB_OPERATIONS: IntCounterVec = register_int_counter_vec!(
+        "aas_client_rocksdb_operations_total",
+        "RocksDB reads and writes",
+        &["operation"]
+    )
+    .unwrap();
+}
+
+/// Label value of a success or failure.
+pub fn result_label<T, E>(result: &Result<T, E>) -> &'static str {
+    if result.is_ok() {
+        "success"
+    } else {
+        "failure"
+    }
+}
+
+/// Counts a response of the AAS server, `None` if the request failed before a response arrived.
+pub fn record_server_response(operation: &str, status: Option<reqwest::StatusCode>) {
+    let status_class = match status.map(|status| status.as_u16() / 100) {
+        Some(1) => "1xx",
+        Some(2) => "2xx",
+        Some(3) => "3xx",
+        Some(4) => "4xx",
+        Some(5) => "5xx",
+        _ => "error",
+    };
+    SERVER_RESPONSES.with_label_values(&[operation, status_class]).inc();
+}
+
+pub fn record_rocksdb_read() {
+    ROCKSDB_OPERATIONS.with_label_values(&["read"]).inc();
+}
+
+pub fn record_rocksdb_write() {
+    ROCKSDB_OPERATIONS.with_label_values(&["write"]).inc();
+}
@@ -15,11 +15,126 @@ pub fn config(cfg: &mut web::ServiceConfig) {
                 .route(web::get().to(handlers::submodels::get_submodel))
                 .route(web::patch().to(handlers::submodels::patch_submodel))
         )
+        .service(
+            web::resource("/submodels/{submodel_id_short}/elements/{id_short_path}")
+                .route(web::get().to(handlers::submodels::get_submodel_element))
+                .route(web::patch().to(handlers::submodels::patch_submodel_element))
+        )
+        .service(
+            web::resource("/submodels/{submodel_id_short}/elements/{id_short_path}/invoke")
+                .route(web::post().to(handlers::submodels::invoke_operation))
+        )
+        .service(
+            web::resource("/submodels/{submodel_id_short}/elements/{id_short_path}/invoke-async")
+                .route(web::post().to(handlers::submodels::invoke_operation_async))
+        )
+        .service(
+            web::resource("/submodels/{submodel_id_short}/elements/{id_short_path}/operation-status/{handle_id}")
+                .route(web::get().to(handlers::submodels::get_operation_status))
+        )
+        .service(
+            web::resource("/submodels/{submodel_id_short}/elements/{id_short_path}/operation-results/{handle_id}")
+                .route(web::get().to(handlers::submodels::get_operation_result))
+        )
+        .service(
+            web::resource("/submodels/{submodel_id_short}/timeseries")
+                .route(web::get().to(handlers::submodels::get_submodel_timeseries))
+        )
+        .service(
+            web::resource("/health/live").route(web::get().to(handlers::health::live))
+        )
+        .service(
+            web::resource("/health/ready").route(web::get().to(handlers::health::ready))
+        )
+        .service(
+            web::resource("/metrics").route(web::get().to(handlers::metrics::get_metrics))
+        )
+        .service(
+            web::resource("/onboarding")
+                .route(web::get().to(handlers::onboarding::get_onboarding_status))
+        )
         .service(
             web::resource("/openapi")
                 .route(web::get().to(handlers::openapi::openapi_endpoint)))
         .service(
             web::resource("/picture").route(web::get().to(handlers::picture::get_picture))
+        )
+        .service(
+            web::resource("/aasx").route(web::get().to(handlers::aasx::get_aasx))
+        )
+        .service(
+            web::resource("/concept-descriptions")
+                .route(web::get().to(handlers::aas_api::get_concept_descriptions_array))
+        )
+        .service(
+            web::resource("/concept-descriptions/{cd_id}")
+                .route(web::get().to(handlers::aas_api::get_concept_description))
+        )
+        .service(
+            web::resource("/login").route(web::post().to(handlers::users::login))
+        )
+        .service(
+            web::resource("/login/refresh").route(web::post().to(handlers::users::refresh))
+        )
+        .service(
+            web::resource("/logout").route(web::post().to(handlers::users::logout))
+        )
+        .service(
+            web::resource("/users")
+                .route(web::get().to(handlers::users::get_users))
+                .route(web::post().to(handlers::users::create_user))
+        )
+        .service(
+            web::resource("/users/{username}")
+                .route(web::delete().to(handlers::users::delete_user))
+        )
+        .service(
+            web::resource("/users/{username}/password")
+                .route(web::put().to(handlers::users::set_password))
+        )
+        .service(
+            web::scope("/api/v3.0")
+                .service(
+                    web::resource("/shells").route(web::get().to(handlers::aas_api::get_shells))
+                )
+                .service(
+                    web::resource("/shells/{aas_id}").route(web::get().to(handlers::aas_api::get_shell))
+                )
+                .service(
+                    web::resource("/shells/{aas_id}/submodel-refs")
+                        .route(web::get().to(handlers::aas_api::get_submodel_refs))
+                )
+                .service(
+                    web::resource("/submodels").route(web::get().to(handlers::aas_api::get_submodels))
+                )
+                .service(
+                    web::resource("/submodels/{submodel_id}").route(web::get().to(handlers::aas_api::get_submodel))
+                )
+                .service(
+                    web::resource("/submodels/{submodel_id}/$value")
+                        .route(web::get().to(handlers::aas_api::get_submodel_value))
+                )
+                .service(
+                    web::resource("/submodels/{submodel_id}/submodel-elements")
+                        .route(web::get().to(handlers::aas_api::get_submodel_elements))
+                )
+                .service(
+                    web::resource("/submodels/{submodel_id}/submodel-elements/{id_short_path}")
+                        .route(web::get().to(handlers::aas_api::get_submodel_element))
+                )
+                .service(
+                    web::resource("/submodels/{submodel_id}/submodel-elements/{id_short_path}/$value")
+                        .route(web::get().to(handlers::aas_api::get_submodel_element_value))
+                        .route(web::patch().to(handlers::aas_api::patch_submodel_element_value))
+                )
+                .service(
+                    web::resource("/concept-descriptions")
+                        .route(web::get().to(handlers::aas_api::get_concept_descriptions))
+                )
+                .service(
+                    web::resource("/concept-descriptions/{cd_id}")
+                        .route(web::get().to(handlers::aas_api::get_concept_description))
+                )
         );
 
 }
@@ -0,0 +1,123 @@
+//! Credentials sent with the requests to the AAS server: a static bearer token, basic
+//! authentication or an OAuth2 access token obtained with the client credentials grant.
+
+use reqwest::RequestBuilder;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::config::{AuthMethod, ServerAuthConfig};
+use crate::error::AppError;
+use crate::metrics;
+
+/// Lifetime assumed for tokens whose response has no `expires_in`.
+const DEFAULT_TOKEN_LIFETIME: u64 = 60;
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+struct CachedToken {
+    access_token: String,
+    /// When the token has to be replaced, i.e. its expiry minus the refresh margin.
+    refresh_at: Instant,
+}
+
+pub struct ServerAuth {
+    config: ServerAuthConfig,
+    token: Mutex<Option<CachedToken>>,
+}
+
+impl ServerAuth {
+    pub fn new(config: ServerAuthConfig) -> Self {
+        ServerAuth { config, token: Mutex::new(None) }
+    }
+
+    /// Adds the credentials to a request, fetching an OAuth2 token first if none is cached
+    /// or the cached one is about to expire.
+    pub async fn authorize(
+        &self,
+        client: &reqwest::Client,
+        request: RequestBuilder,
+    ) -> Result<RequestBuilder, AppError> {
+        Ok(match self.config.method {
+            AuthMethod::None => request,
+            AuthMethod::Bearer => request.bearer_auth(self.config.token.as_deref().unwrap_or_default()),
+            AuthMethod::Basic => request.basic_auth(
+                self.config.username.as_deref().unwrap_or_default(),
+                self.config.password.as_deref(),
+            ),
+            AuthMethod::OAuth2 => request.bearer_auth(self.access_token(client).await?),
+        })
+    }
+
+    /// Whether a 401 can go away with fresh credentials, which only holds for OAuth2 tokens.
+    pub fn can_renew(&self) -> bool {
+        self.config.method == AuthMethod::OAuth2
+    }
+
+    /// Drops the cached token, e.g. after the server rejected it.
+    pub async fn invalidate(&self) {
+        *self.token.lock().await = None;
+    }
+
+    async fn access_token(&self, client: &reqwest::Client) -> Result<String, AppError> {
+        // Held while fetching, so concurrent requests wait for one token instead of each fetching one
+        let mut token = self.token.lock().await;
+        if let Some(cached) = token.as_ref() {
+            if Instant::now() < cached.refresh_at {
+                return Ok(cached.access_token.clone());
+            }
+        }
+
+        let fetched = self.fetch_token(client).await?;
+        let access_token = fetched.access_token.clone();
+        *token = Some(fetched);
+        Ok(access_token)
+    }
+
+    async fn fetch_token(&self, client: &reqwest::Client) -> Result<CachedToken, AppError> {
+        let token_url = self.config.token_url.as_deref().unwrap_or_default();
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = &self.config.scope {
+            form.push(("scope", scope.as_str()));
+        }
+
+        let response = client
+            .post(token_url)
+            .basic_auth(
+                self.config.client_id.as_deref().unwrap_or_default(),
+                self.config.client_secret.as_deref(),
+            )
+            .form(&form)
+            .send()
+            .await;
+        metrics::record_server_response("get_token", response.as_ref().ok().map(|r| r.status()));
+        let response = response.map_err(|e| AppError::from(e).context("Failed to fetch access token"))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::upstream(
+                status,
+                format!("Token endpoint {} rejected the client credentials: {}", token_url, body),
+            ));
+        }
+
+        let token: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| AppError::upstream(status, format!("Invalid token response from {}: {}", token_url, e)))?;
+        let lifetime = token.expires_in.unwrap_or(DEFAULT_TOKEN_LIFETIME);
+        // Tokens living shorter than the margin are used for half their lifetime
+        let valid_for = lifetime.checked_sub(self.config.refresh_margin).unwrap_or(lifetime / 2);
+
+        Ok(CachedToken {
+            access_token: token.access_token,
+            refresh_at: Instant::now() + Duration::from_secs(valid_for),
+        })
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+
+#[derive(Debug, Clone, Default)]
+struct ActivityRecord {
+    last_success: Option<DateTime<Utc>>,
+    last_failure: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+/// Outcome of the latest runs of a recurring activity such as pushing to the AAS server.
+#[derive(Default)]
+pub struct Activity {
+    record: RwLock<ActivityRecord>,
+}
+
+impl Activity {
+    pub async fn succeeded(&self) {
+        let mut record = self.record.write().await;
+        record.last_success = Some(Utc::now());
+    }
+
+    pub async fn failed(&self, error: impl ToString) {
+        let mut record = self.record.write().await;
+        record.last_failure = Some(Utc::now());
+        record.last_error = Some(error.to_string());
+    }
+
+    /// Seconds since the last successful run, if there was one.
+    pub async fn age(&self) -> Option<i64> {
+        let record = self.record.read().await;
+        record.last_success.map(|last_success| (Utc::now() - last_success).num_seconds())
+    }
+
+    /// Whether the latest run failed.
+    pub async fn is_failing(&self) -> bool {
+        let record = self.record.read().await;
+        match (record.last_success, record.last_failure) {
+            (Some(success), Some(failure)) => failure > success,
+            (None, Some(_)) => true,
+            _ => false,
+        }
+    }
+
+    pub async fn to_json(&self) -> Value {
+        let record = self.record.read().await;
+        json!({
+            "lastSuccess": record.last_success.map(|t| t.to_rfc3339()),
+            "lastFailure": record.last_failure.map(|t| t.to_rfc3339()),
+            "lastError": record.last_error,
+        })
+    }
+}
+
+/// Activities reported by the health endpoints.
+#[derive(Default)]
+pub struct Health {
+    /// Pushes of collected data to the AAS server.
+    pub push: Activity,
+    /// Polls of submodels from the AAS server.
+    pub poll: Activity,
+    /// Runs of the `sysInfo.sh` collector script.
+    pub collector: Activity,
+}
@@ -1,14 +1,46 @@
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, OnceCell};
 use rocksdb::DB;
 
+use crate::config::{Config, ServerDialect};
+use crate::functions::aas_server::{self, AasServerClient};
+use crate::http_client::AasClient;
+
+pub mod health;
+pub mod onboarding;
+pub mod operations;
+
 pub struct AppState {
-    pub health_check_response: Mutex<String>,
     // Replacing the MongoDB URI with RocksDB
     pub rocksdb: Arc<Mutex<DB>>, // RocksDB instance wrapped in Arc<Mutex<>> for shared state across threads
-    pub aas_identifier: String,
-    pub aas_id_short: String,
-    pub aasx_server: String,
-    pub device_name: String,
-    pub offboarding_time: i64,
-}
\ No newline at end of file
+    pub config: Config,
+    /// Client for all requests to the AAS server, see `http_client::build_client`.
+    pub http_client: AasClient,
+    pub onboarding: onboarding::Onboarding,
+    pub health: health::Health,
+    /// Asynchronous invocations of Operations.
+    pub operations: operations::Invocations,
+    /// The AAS server in its dialect, see `aas_server()`.
+    pub aas_server: OnceCell<Arc<dyn AasServerClient>>,
+}
+
+impl AppState {
+    /// Client of the AAS server in the configured dialect, or the one detected on first use.
+    /// While the server cannot be reached the generic dialect is used and detection is
+    /// retried with the next request.
+    pub async fn aas_server(&self) -> Arc<dyn AasServerClient> {
+        if let Some(server) = self.aas_server.get() {
+            return server.clone();
+        }
+        match aas_server::connect(&self.http_client, &self.config).await {
+            Ok(server) => {
+                println!("AAS server dialect: {:?} at {}", server.dialect(), server.base_url());
+                self.aas_server.get_or_init(|| async { server }).await.clone()
+            }
+            Err(e) => {
+                eprintln!("Failed to detect the AAS server dialect: {}", e);
+                aas_server::for_dialect(ServerDialect::Generic, self.http_client.clone(), self.config.aasx_server.clone())
+            }
+        }
+    }
+}
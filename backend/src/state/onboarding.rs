@@ -0,0 +1,81 @@
+use chrono::Utc;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::RwLock;
+
+/// Steps of the onboarding state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OnboardingState {
+    Pending,
+    FetchingShell,
+    FetchingSubmodels,
+    Onboarded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OnboardingStatus {
+    pub state: OnboardingState,
+    pub attempts: u32,
+    /// Error of the last failed attempt, kept while retrying.
+    pub last_error: Option<String>,
+    pub last_change: String,
+    /// Whether submodels from this or an earlier run are stored locally.
+    pub local_data: bool,
+}
+
+/// Tracks the onboarding progress shared between the onboarding task and the handlers.
+pub struct Onboarding {
+    status: RwLock<OnboardingStatus>,
+    local_data: AtomicBool,
+}
+
+impl Onboarding {
+    pub fn new(local_data: bool) -> Self {
+        Onboarding {
+            status: RwLock::new(OnboardingStatus {
+                state: OnboardingState::Pending,
+                attempts: 0,
+                last_error: None,
+                last_change: Utc::now().to_rfc3339(),
+                local_data,
+            }),
+            local_data: AtomicBool::new(local_data),
+        }
+    }
+
+    pub async fn status(&self) -> OnboardingStatus {
+        let mut status = self.status.read().await.clone();
+        status.local_data = self.has_local_data();
+        status
+    }
+
+    pub fn has_local_data(&self) -> bool {
+        self.local_data.load(Ordering::SeqCst)
+    }
+
+    pub async fn set_state(&self, state: OnboardingState) {
+        let mut status = self.status.write().await;
+        status.state = state;
+        status.last_change = Utc::now().to_rfc3339();
+    }
+
+    /// Starts a new attempt by fetching the shell.
+    pub async fn start_attempt(&self) {
+        self.status.write().await.attempts += 1;
+        self.set_state(OnboardingState::FetchingShell).await;
+    }
+
+    pub async fn fail(&self, error: String) {
+        self.status.write().await.last_error = Some(error);
+        self.set_state(OnboardingState::Failed).await;
+    }
+
+    pub async fn succeed(&self) {
+        self.local_data.store(true, Ordering::SeqCst);
+        self.status.write().await.last_error = None;
+        self.set_state(OnboardingState::Onboarded).await;
+    }
+}
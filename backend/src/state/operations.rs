@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+use tokio::sync::RwLock;
+
+use crate::functions::operations::OperationResult;
+
+/// Asynchronous invocations kept for their results. The oldest are dropped once there are
+/// more than `MAX_INVOCATIONS`.
+const MAX_INVOCATIONS: usize = 100;
+
+struct Invocation {
+    handle_id: String,
+    /// `{submodel idShort}/{idShortPath}` of the Operation.
+    operation: String,
+    result: OperationResult,
+}
+
+/// Results of the operations invoked through `invoke-async`, by handle.
+#[derive(Default)]
+pub struct Invocations {
+    invocations: RwLock<VecDeque<Invocation>>,
+}
+
+impl Invocations {
+    /// Registers a running invocation of `operation` and returns its handle.
+    pub async fn start(&self, operation: String) -> String {
+        let handle_id = format!("{:032x}", rand::random::<u128>());
+        let mut invocations = self.invocations.write().await;
+        invocations.push_back(Invocation {
+            handle_id: handle_id.clone(),
+            operation,
+            result: OperationResult::running(),
+        });
+        while invocations.len() > MAX_INVOCATIONS {
+            invocations.pop_front();
+        }
+        handle_id
+    }
+
+    pub async fn finish(&self, handle_id: &str, result: OperationResult) {
+        let mut invocations = self.invocations.write().await;
+        if let Some(invocation) = invocations.iter_mut().find(|invocation| invocation.handle_id == handle_id) {
+            invocation.result = result;
+        }
+    }
+
+    /// Current result of the invocation `handle_id` of `operation`.
+    pub async fn get(&self, operation: &str, handle_id: &str) -> Option<OperationResult> {
+        let invocations = self.invocations.read().await;
+        invocations
+            .iter()
+            .find(|invocation| invocation.handle_id == handle_id && invocation.operation == operation)
+            .map(|invocation| invocation.result.clone())
+    }
+}
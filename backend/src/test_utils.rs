@@ -0,0 +1,94 @@
+//! Helpers of the tests: a mock server (AAS server, registry, token endpoint, ...) on a
+//! random local port and a RocksDB in a temporary directory. Both are cleaned up when
+//! dropped, also when an assertion fails.
+
+use actix_web::dev::ServerHandle;
+use actix_web::{web, App, HttpServer};
+use rocksdb::{Options, DB};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::config::{Config, HttpClientConfig};
+use crate::http_client::{build_client, AasClient};
+use crate::server_auth::ServerAuth;
+use crate::state::{health::Health, onboarding::Onboarding, operations::Invocations, AppState};
+
+/// HTTP server with the routes added by `configure`, stopped when dropped.
+/// Runs on the actix runtime of `#[actix_web::test]`.
+pub struct MockServer {
+    url: String,
+    handle: ServerHandle,
+}
+
+impl MockServer {
+    pub fn start<F>(configure: F) -> MockServer
+    where
+        F: Fn(&mut web::ServiceConfig) + Clone + Send + 'static,
+    {
+        let server = HttpServer::new(move || App::new().configure(configure.clone()))
+            .workers(1)
+            .bind(("127.0.0.1", 0))
+            .unwrap();
+        let url = format!("http://{}/", server.addrs()[0]);
+        let server = server.run();
+        let handle = server.handle();
+        tokio::spawn(server);
+        MockServer { url, handle }
+    }
+
+    /// Base URL of the server, ending with '/'.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        // The stop command is sent right away, waiting for it is not needed
+        drop(self.handle.stop(false));
+    }
+}
+
+/// RocksDB in a temporary directory, which is removed when dropped.
+pub struct TempDb {
+    rocksdb: Arc<Mutex<DB>>,
+    _dir: TempDir,
+}
+
+impl TempDb {
+    pub fn open() -> TempDb {
+        let dir = tempfile::Builder::new().prefix("aas_client_").tempdir().unwrap();
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        let rocksdb = Arc::new(Mutex::new(DB::open(&options, dir.path()).unwrap()));
+        TempDb { rocksdb, _dir: dir }
+    }
+
+    pub fn rocksdb(&self) -> Arc<Mutex<DB>> {
+        self.rocksdb.clone()
+    }
+}
+
+/// Client without credentials and with the default connection settings.
+pub fn aas_client() -> AasClient {
+    AasClient::new(build_client(&HttpClientConfig::default()).unwrap(), ServerAuth::new(Default::default()))
+}
+
+/// State of the handlers and jobs, with or without local data from an onboarding. The
+/// client sends the credentials of `config.server_auth`.
+pub fn app_state(config: Config, db: &TempDb, local_data: bool) -> web::Data<AppState> {
+    let http_client = AasClient::new(
+        build_client(&HttpClientConfig::default()).unwrap(),
+        ServerAuth::new(config.server_auth.clone()),
+    );
+    web::Data::new(AppState {
+        rocksdb: db.rocksdb(),
+        config,
+        http_client,
+        onboarding: Onboarding::new(local_data),
+        health: Health::default(),
+        operations: Invocations::default(),
+        aas_server: OnceCell::new(),
+    })
+}
@@ -0,0 +1,191 @@
+//! HTTPS of the HTTP API with rustls. The certificate is read again when its files change,
+//! so a renewed certificate is served without a restart.
+
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use std::io::BufReader;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// Serves the certificate of `cert_path` and `key_path` as loaded last.
+pub struct CertificateReloader {
+    cert_path: String,
+    key_path: String,
+    current: RwLock<Arc<CertifiedKey>>,
+    /// Modification times of both files when they were loaded last.
+    loaded: Mutex<(Option<SystemTime>, Option<SystemTime>)>,
+}
+
+impl CertificateReloader {
+    pub fn new(cert_path: &str, key_path: &str) -> Result<Self, String> {
+        let loaded = modification_times(cert_path, key_path);
+        Ok(CertificateReloader {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            current: RwLock::new(Arc::new(load_certified_key(cert_path, key_path)?)),
+            loaded: Mutex::new(loaded),
+        })
+    }
+
+    /// Loads the files again if either was modified since the last load. On errors the
+    /// previous certificate stays in use.
+    pub fn reload_if_changed(&self) -> Result<bool, String> {
+        let modified = modification_times(&self.cert_path, &self.key_path);
+        let mut loaded = self.loaded.lock().unwrap();
+        if *loaded == modified {
+            return Ok(false);
+        }
+        // Remembered before loading, so a broken file is reported once and not every check
+        *loaded = modified;
+        let certified_key = load_certified_key(&self.cert_path, &self.key_path)?;
+        *self.current.write().unwrap() = Arc::new(certified_key);
+        Ok(true)
+    }
+
+    /// Checks the files every `interval` seconds for the lifetime of the process.
+    pub fn spawn_reload(self: Arc<Self>, interval: u64) {
+        if interval == 0 {
+            return;
+        }
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(interval)).await;
+                match self.reload_if_changed() {
+                    Ok(true) => println!("Reloaded TLS certificate {}", self.cert_path),
+                    Ok(false) => (),
+                    Err(e) => eprintln!("Keeping the previous TLS certificate: {}", e),
+                }
+            }
+        });
+    }
+}
+
+impl ResolvesServerCert for CertificateReloader {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+/// rustls configuration serving the certificate of `reloader`.
+pub fn server_config(reloader: Arc<CertificateReloader>) -> ServerConfig {
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_cert_resolver(reloader)
+}
+
+fn modification_times(cert_path: &str, key_path: &str) -> (Option<SystemTime>, Option<SystemTime>) {
+    let modified = |path: &str| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    (modified(cert_path), modified(key_path))
+}
+
+fn load_certified_key(cert_path: &str, key_path: &str) -> Result<CertifiedKey, String> {
+    let certificates: Vec<Certificate> = rustls_pemfile::certs(&mut open(cert_path)?)
+        .map_err(|e| format!("Invalid certificate {}: {}", cert_path, e))?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    if certificates.is_empty() {
+        return Err(format!("{} contains no certificate", cert_path));
+    }
+
+    let key = rustls_pemfile::read_all(&mut open(key_path)?)
+        .map_err(|e| format!("Invalid private key {}: {}", key_path, e))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key) | rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::ECKey(key) => {
+                Some(PrivateKey(key))
+            }
+            _ => None,
+        })
+        .ok_or_else(|| format!("{} contains no private key", key_path))?;
+    let signing_key = rustls::sign::any_supported_type(&key)
+        .map_err(|e| format!("Unsupported private key {}: {}", key_path, e))?;
+
+    Ok(CertifiedKey::new(certificates, signing_key))
+}
+
+fn open(path: &str) -> Result<BufReader<std::fs::File>, String> {
+    std::fs::File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("Cannot read {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::{ClientConfig, ClientConnection, Connection, RootCertStore, ServerConnection};
+    use std::path::Path;
+    use std::time::UNIX_EPOCH;
+
+    /// Writes a new self-signed certificate for localhost and its key, modified at
+    /// `modified` seconds. Returns the certificate.
+    fn write_certificate(cert_path: &Path, key_path: &Path, modified: u64) -> Certificate {
+        let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::write(cert_path, certificate.serialize_pem().unwrap()).unwrap();
+        std::fs::write(key_path, certificate.serialize_private_key_pem()).unwrap();
+        for path in [cert_path, key_path] {
+            touch(path, modified);
+        }
+        let der = rustls_pemfile::certs(&mut open(cert_path.to_str().unwrap()).unwrap()).unwrap().remove(0);
+        Certificate(der)
+    }
+
+    fn touch(path: &Path, modified: u64) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(modified)).unwrap();
+    }
+
+    /// Whether a client trusting only `trusted` completes a handshake with `server_config`.
+    fn handshake(server_config: &Arc<ServerConfig>, trusted: &Certificate) -> bool {
+        let mut roots = RootCertStore::empty();
+        roots.add(trusted).unwrap();
+        let client_config = ClientConfig::builder().with_safe_defaults().with_root_certificates(roots).with_no_client_auth();
+        let mut client = Connection::Client(ClientConnection::new(Arc::new(client_config), "localhost".try_into().unwrap()).unwrap());
+        let mut server = Connection::Server(ServerConnection::new(server_config.clone()).unwrap());
+
+        let transfer = |from: &mut Connection, to: &mut Connection| -> Result<(), rustls::Error> {
+            let mut buffer = Vec::new();
+            while from.wants_write() {
+                from.write_tls(&mut buffer).unwrap();
+            }
+            to.read_tls(&mut buffer.as_slice()).unwrap();
+            to.process_new_packets().map(|_| ())
+        };
+        for _ in 0..10 {
+            if !client.is_handshaking() && !server.is_handshaking() {
+                return true;
+            }
+            if transfer(&mut client, &mut server).is_err() || transfer(&mut server, &mut client).is_err() {
+                return false;
+            }
+        }
+        false
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_serves_the_rewritten_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert_path, key_path) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+        let first = write_certificate(&cert_path, &key_path, 1_000);
+        let reloader = Arc::new(CertificateReloader::new(cert_path.to_str().unwrap(), key_path.to_str().unwrap()).unwrap());
+        let server_config = Arc::new(server_config(reloader.clone()));
+        assert!(handshake(&server_config, &first));
+
+        reloader.clone().spawn_reload(5);
+        let second = write_certificate(&cert_path, &key_path, 2_000);
+        // Served once the files were checked again
+        assert!(!handshake(&server_config, &second));
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        assert!(handshake(&server_config, &second));
+        assert!(!handshake(&server_config, &first));
+
+        // A broken key keeps the certificate in use
+        std::fs::write(&key_path, "not a key").unwrap();
+        touch(&key_path, 3_000);
+        assert!(reloader.reload_if_changed().unwrap_err().contains("contains no private key"));
+        assert!(handshake(&server_config, &second));
+        assert!(!reloader.reload_if_changed().unwrap());
+    }
+}